@@ -0,0 +1,137 @@
+use serde::Serialize;
+use std::cmp::Reverse;
+use wg_2024::config::Config;
+use wg_2024::network::NodeId;
+
+use crate::utils::{input_validator, topology};
+
+/// Which crashes the failure impact simulates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FailureScope {
+    None,
+    Drones,
+    DronePairs,
+}
+
+/// What happens to the client-server paths when some drones crash
+#[derive(Debug, Clone, Serialize)]
+pub struct FailureImpact {
+    pub crashed: Vec<NodeId>,
+    /// Whether the topology stays valid, with every client still reaching every server,
+    /// i.e. whether the simulation controller may crash these drones
+    pub allowed: bool,
+    /// Client-server pairs that lose their connection
+    pub disconnected: usize,
+    /// Hops added to the shortest paths of the pairs that stay connected
+    pub added_hops: usize,
+    pub pairs: Vec<PairImpact>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PairImpact {
+    pub client: NodeId,
+    pub server: NodeId,
+    pub hops_before: Option<usize>,
+    pub hops_after: Option<usize>,
+}
+
+/// Simulates the crash of each drone and, with `FailureScope::DronePairs`, of each pair of drones.
+/// The failures are ranked from the most harmful: first by disconnected pairs, then by added hops.
+pub fn failure_impact(config: &Config, scope: FailureScope) -> Vec<FailureImpact> {
+    if scope == FailureScope::None {
+        return Vec::new();
+    }
+    let drones: Vec<NodeId> = config.drone.iter().map(|d| d.id).collect();
+    let mut failures: Vec<Vec<NodeId>> = drones.iter().map(|id| vec![*id]).collect();
+    if scope == FailureScope::DronePairs {
+        for (i, a) in drones.iter().enumerate() {
+            failures.extend(drones[i + 1..].iter().map(|b| vec![*a, *b]));
+        }
+    }
+
+    let before = topology::client_server_distances(config);
+    let mut impacts: Vec<FailureImpact> = failures
+        .into_iter()
+        .map(|crashed| {
+            let mut projected = config.clone();
+            for id in &crashed {
+                topology::remove_drone(&mut projected, *id).unwrap();
+            }
+            let allowed = input_validator::validate_config(&projected)
+                .and_then(|_| input_validator::check_reachability(&projected))
+                .is_ok();
+
+            let after = topology::client_server_distances(&projected);
+            let pairs: Vec<PairImpact> = before
+                .iter()
+                .map(|((client, server), hops_before)| PairImpact {
+                    client: *client,
+                    server: *server,
+                    hops_before: *hops_before,
+                    hops_after: after.get(&(*client, *server)).copied().flatten(),
+                })
+                .collect();
+
+            let disconnected = pairs.iter().filter(|p| p.hops_before.is_some() && p.hops_after.is_none()).count();
+            let added_hops = pairs
+                .iter()
+                .filter_map(|p| Some(p.hops_after?.saturating_sub(p.hops_before?)))
+                .sum();
+            FailureImpact { crashed, allowed, disconnected, added_hops, pairs }
+        })
+        .collect();
+
+    impacts.sort_by_key(|impact| (Reverse(impact.disconnected), Reverse(impact.added_hops), impact.crashed.clone()));
+    impacts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::topology::tests::config_with_links;
+
+    /// 100 - 1 - 2 - 200, with 1 - 3 - 4 - 200 as a longer way around drone 2
+    fn detour() -> Config {
+        config_with_links(
+            &[(1, 0.1), (2, 0.1), (3, 0.1), (4, 0.1)],
+            &[100],
+            &[200],
+            &[(100, 1), (1, 2), (2, 200), (1, 3), (3, 4), (4, 200)],
+        )
+    }
+
+    #[test]
+    fn ranks_the_drones_from_the_most_harmful_crash() {
+        let impacts = failure_impact(&detour(), FailureScope::Drones);
+        let ranking: Vec<(Vec<NodeId>, usize, usize)> =
+            impacts.iter().map(|impact| (impact.crashed.clone(), impact.disconnected, impact.added_hops)).collect();
+        assert_eq!(ranking, [(vec![1], 1, 0), (vec![2], 0, 1), (vec![3], 0, 0), (vec![4], 0, 0)]);
+    }
+
+    #[test]
+    fn allows_only_the_crashes_that_keep_the_topology_valid() {
+        let impacts = failure_impact(&detour(), FailureScope::DronePairs);
+        assert_eq!(impacts.len(), 4 + 6);
+        let allowed: Vec<Vec<NodeId>> =
+            impacts.iter().filter(|impact| impact.allowed).map(|impact| impact.crashed.clone()).collect();
+        // Crashing 2 or 4 keeps the client connected, but leaves the server with a single drone
+        assert_eq!(allowed, [vec![3]]);
+        let crash_4 = impacts.iter().find(|impact| impact.crashed == [4]).unwrap();
+        assert_eq!((crash_4.allowed, crash_4.disconnected), (false, 0));
+    }
+
+    #[test]
+    fn counts_the_hops_added_to_the_connected_pairs() {
+        let impacts = failure_impact(&detour(), FailureScope::Drones);
+        let crash_2 = impacts.iter().find(|impact| impact.crashed == [2]).unwrap();
+        assert_eq!(crash_2.pairs.len(), 1);
+        assert_eq!((crash_2.pairs[0].hops_before, crash_2.pairs[0].hops_after), (Some(3), Some(4)));
+        assert_eq!(crash_2.added_hops, 1);
+
+        let crash_1 = impacts.iter().find(|impact| impact.crashed == [1]).unwrap();
+        assert_eq!((crash_1.pairs[0].hops_before, crash_1.pairs[0].hops_after), (Some(3), None));
+        assert_eq!(crash_1.added_hops, 0);
+
+        assert!(failure_impact(&detour(), FailureScope::None).is_empty());
+    }
+}
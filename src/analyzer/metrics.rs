@@ -0,0 +1,255 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use wg_2024::config::Config;
+use wg_2024::network::NodeId;
+
+use crate::utils::topology;
+
+/// Longest shortest path between two nodes, in hops, None if the topology has a single node
+pub fn diameter(config: &Config) -> Option<usize> {
+    topology::node_ids(config)
+        .into_iter()
+        .flat_map(|id| topology::hop_distances(config, id).into_values())
+        .max()
+        .filter(|hops| *hops > 0)
+}
+
+/// Number of drones with each number of neighbours
+pub fn degree_distribution(config: &Config) -> BTreeMap<usize, usize> {
+    let mut distribution = BTreeMap::new();
+    for drone in &config.drone {
+        *distribution.entry(drone.connected_node_ids.len()).or_insert(0) += 1;
+    }
+    distribution
+}
+
+/// Betweenness centrality of each drone, restricted to the traffic of the network:
+/// the share of the shortest client-server paths that pass through the drone, in [0, 1].
+/// Computed with Brandes' algorithm, from each client.
+pub fn betweenness(config: &Config) -> HashMap<NodeId, f64> {
+    let mut centrality: HashMap<NodeId, f64> = config.drone.iter().map(|d| (d.id, 0.)).collect();
+    let pairs = config.client.len() * config.server.len();
+
+    for client in &config.client {
+        // Shortest paths from the client, counting how many there are to each node
+        let mut order = Vec::new();
+        let mut predecessors: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
+        let mut paths: HashMap<NodeId, f64> = HashMap::from([(client.id, 1.)]);
+        let mut distances: HashMap<NodeId, usize> = HashMap::from([(client.id, 0)]);
+        let mut queue = VecDeque::from([client.id]);
+
+        while let Some(id) = queue.pop_front() {
+            order.push(id);
+            if id != client.id && !topology::is_drone(config, id) {
+                continue;
+            }
+            for neighbour in topology::neighbours(config, id).into_iter().flatten() {
+                if !distances.contains_key(neighbour) {
+                    distances.insert(*neighbour, distances[&id] + 1);
+                    queue.push_back(*neighbour);
+                }
+                if distances[neighbour] == distances[&id] + 1 {
+                    *paths.entry(*neighbour).or_insert(0.) += paths[&id];
+                    predecessors.entry(*neighbour).or_default().push(id);
+                }
+            }
+        }
+
+        // Dependencies of the drones on the paths towards the servers, from the farthest nodes
+        let mut dependency: HashMap<NodeId, f64> = HashMap::new();
+        for id in order.iter().rev() {
+            let target = if topology::is_server(config, *id) { 1. } else { 0. };
+            let through = dependency.get(id).copied().unwrap_or(0.) + target;
+            for predecessor in predecessors.get(id).into_iter().flatten() {
+                *dependency.entry(*predecessor).or_insert(0.) += paths[predecessor] / paths[id] * through;
+            }
+            if let Some(value) = centrality.get_mut(id) {
+                *value += dependency.get(id).copied().unwrap_or(0.);
+            }
+        }
+    }
+
+    if pairs > 0 {
+        for value in centrality.values_mut() {
+            *value /= pairs as f64;
+        }
+    }
+    centrality
+}
+
+/// Maximum sets of paths from `from` to `to` that share no link (edge-disjoint)
+/// or no drone (node-disjoint), found with a unit-capacity maximum flow (Menger's theorem).
+pub fn disjoint_paths(config: &Config, from: NodeId, to: NodeId, node_disjoint: bool) -> Vec<Vec<NodeId>> {
+    let mut network = FlowNetwork::new(config, from, to, node_disjoint);
+    while network.augment() {}
+    network.paths()
+}
+
+// Flow network where only drones forward: with `split` each drone is an "in" node (its index)
+// and an "out" node (index + n) joined by a unit arc, so at most one path crosses it.
+struct FlowNetwork {
+    ids: Vec<NodeId>,
+    capacity: Vec<Vec<i32>>,
+    flow: Vec<Vec<i32>>,
+    source: usize,
+    sink: usize,
+    split: bool,
+}
+
+impl FlowNetwork {
+    fn new(config: &Config, from: NodeId, to: NodeId, split: bool) -> Self {
+        // The two endpoints and the drones: other hosts cannot be on the paths
+        let mut ids: Vec<NodeId> = vec![from, to];
+        ids.extend(config.drone.iter().map(|d| d.id).filter(|id| *id != from && *id != to));
+        let index: HashMap<NodeId, usize> = ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+        let n = ids.len();
+        let size = if split { 2 * n } else { n };
+        let out = |i: usize| if split && i >= 2 { i + n } else { i };
+
+        let mut capacity = vec![vec![0; size]; size];
+        for (i, id) in ids.iter().enumerate() {
+            if split && i >= 2 {
+                capacity[i][out(i)] = 1;
+            }
+            if *id == to {
+                continue;
+            }
+            for neighbour in topology::neighbours(config, *id).into_iter().flatten() {
+                if let Some(j) = index.get(neighbour).filter(|j| **j != 0) {
+                    capacity[out(i)][*j] = 1;
+                }
+            }
+        }
+
+        Self {
+            ids,
+            capacity,
+            flow: vec![vec![0; size]; size],
+            source: 0,
+            sink: 1,
+            split,
+        }
+    }
+
+    // Pushes one more unit of flow along a shortest augmenting path, if there is one
+    fn augment(&mut self) -> bool {
+        let size = self.capacity.len();
+        let mut parent: Vec<Option<usize>> = vec![None; size];
+        parent[self.source] = Some(self.source);
+        let mut queue = VecDeque::from([self.source]);
+
+        while let Some(u) = queue.pop_front() {
+            let reachable: Vec<usize> = (0..size)
+                .filter(|v| parent[*v].is_none() && self.capacity[u][*v] - self.flow[u][*v] > 0)
+                .collect();
+            for v in reachable {
+                parent[v] = Some(u);
+                queue.push_back(v);
+            }
+        }
+        if parent[self.sink].is_none() {
+            return false;
+        }
+
+        let mut v = self.sink;
+        while v != self.source {
+            let u = parent[v].unwrap();
+            self.flow[u][v] += 1;
+            self.flow[v][u] -= 1;
+            v = u;
+        }
+        true
+    }
+
+    // Follows the units of flow from the source to the sink
+    fn paths(&mut self) -> Vec<Vec<NodeId>> {
+        let n = self.ids.len();
+        let mut paths = Vec::new();
+
+        loop {
+            let mut path = vec![self.ids[self.source]];
+            let mut u = self.source;
+            while u != self.sink {
+                let Some(v) = (0..self.flow.len()).find(|v| self.flow[u][*v] > 0) else {
+                    return paths;
+                };
+                self.flow[u][v] -= 1;
+                self.flow[v][u] += 1;
+                if !self.split || v < n {
+                    path.push(self.ids[v]);
+                }
+                u = v;
+            }
+            paths.push(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::topology::tests::config_with_links;
+
+    // 100 - 1 - {2, 3} - 4 - 200
+    fn diamond() -> Config {
+        config_with_links(
+            &[(1, 0.1), (2, 0.1), (3, 0.1), (4, 0.1)],
+            &[100],
+            &[200],
+            &[(100, 1), (1, 2), (1, 3), (2, 4), (3, 4), (4, 200)],
+        )
+    }
+
+    #[test]
+    fn measures_the_diamond() {
+        let config = diamond();
+        assert_eq!(diameter(&config), Some(4));
+        assert_eq!(degree_distribution(&config), BTreeMap::from([(2, 2), (3, 2)]));
+    }
+
+    #[test]
+    fn splits_the_betweenness_between_equal_paths() {
+        let centrality = betweenness(&diamond());
+        assert_eq!(centrality[&1], 1.);
+        assert_eq!(centrality[&2], 0.5);
+        assert_eq!(centrality[&3], 0.5);
+        assert_eq!(centrality[&4], 1.);
+    }
+
+    #[test]
+    fn counts_only_client_server_paths() {
+        // A second server on drone 2 makes it the only way to one of the two pairs
+        let mut config = diamond();
+        config.server.push(wg_2024::config::Server { id: 201, connected_drone_ids: Vec::new() });
+        topology::add_link(&mut config, 2, 201).unwrap();
+
+        let centrality = betweenness(&config);
+        assert_eq!(centrality[&1], 1.);
+        assert_eq!(centrality[&2], 0.75);
+        assert_eq!(centrality[&3], 0.25);
+        assert_eq!(centrality[&4], 0.5);
+    }
+
+    #[test]
+    fn finds_disjoint_paths() {
+        let config = diamond();
+        let mut paths = disjoint_paths(&config, 1, 4, true);
+        paths.sort();
+        assert_eq!(paths, [vec![1, 2, 4], vec![1, 3, 4]]);
+
+        // Drone 1 is shared by every path of the client
+        assert_eq!(disjoint_paths(&config, 100, 200, false).len(), 1);
+    }
+
+    #[test]
+    fn node_disjoint_paths_cannot_share_a_drone() {
+        // Two diamonds joined by drone 4
+        let config = config_with_links(
+            &[(1, 0.), (2, 0.), (3, 0.), (4, 0.), (5, 0.), (6, 0.), (7, 0.)],
+            &[],
+            &[],
+            &[(1, 2), (1, 3), (2, 4), (3, 4), (4, 5), (4, 6), (5, 7), (6, 7)],
+        );
+        assert_eq!(disjoint_paths(&config, 1, 7, false).len(), 2);
+        assert_eq!(disjoint_paths(&config, 1, 7, true), [vec![1, 2, 4, 5, 7]]);
+    }
+}
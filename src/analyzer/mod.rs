@@ -0,0 +1,239 @@
+pub mod impact;
+pub mod metrics;
+pub mod reliability;
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use wg_2024::config::Config;
+use wg_2024::network::NodeId;
+
+use crate::utils::{config_loader, topology};
+pub use impact::{FailureImpact, FailureScope};
+pub use reliability::{DeliveryOptions, PairDelivery};
+
+// Failures printed in the matrix when drone pairs are simulated, the JSON report has all of them
+const PRINTED_FAILURES: usize = 20;
+
+/// Graph metrics of a topology
+#[derive(Debug, Clone, Serialize)]
+pub struct AnalysisReport {
+    pub drones: usize,
+    pub clients: usize,
+    pub servers: usize,
+    /// Longest shortest path between two nodes, in hops
+    pub diameter: Option<usize>,
+    /// Number of drones with each number of neighbours
+    pub degree_distribution: BTreeMap<usize, usize>,
+    pub pairs: Vec<PairReport>,
+    pub drone_metrics: Vec<DroneReport>,
+    /// Simulated crashes, from the most harmful
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub failure_impact: Vec<FailureImpact>,
+    /// Expected delivery of fragments and messages along the best routes of each pair
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub delivery: Vec<PairDelivery>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PairReport {
+    pub client: NodeId,
+    pub server: NodeId,
+    /// Length of the shortest path, None if the server is unreachable
+    pub hops: Option<usize>,
+    /// Paths that share no link
+    pub edge_disjoint_paths: Vec<Vec<NodeId>>,
+    /// Paths that share no drone
+    pub node_disjoint_paths: Vec<Vec<NodeId>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DroneReport {
+    pub id: NodeId,
+    pub degree: usize,
+    pub pdr: f32,
+    /// Share of the shortest client-server paths through the drone
+    pub betweenness: f64,
+}
+
+/// Loads and validates the topology file, prints its metrics and optionally writes them as JSON
+pub fn analyze(
+    config_path: &str,
+    failures: FailureScope,
+    delivery: Option<DeliveryOptions>,
+    json: Option<&str>,
+) -> Result<(), String> {
+    let config = config_loader::load_config(config_path)?;
+    let report = analyze_config(&config, failures, delivery.as_ref());
+
+    print!("{}", format_report(config_path, &report));
+    if failures != FailureScope::None {
+        print!("{}", format_impact(&report.failure_impact, failures));
+    }
+    if let Some(options) = &delivery {
+        print!("{}", format_delivery(&report.delivery, options));
+    }
+
+    if let Some(path) = json {
+        let data = serde_json::to_string_pretty(&report).map_err(|e| format!("Unable to serialize the report: {}", e))?;
+        fs::write(path, data).map_err(|e| format!("Unable to write \"{}\": {}", path, e))?;
+    }
+    Ok(())
+}
+
+pub fn analyze_config(config: &Config, failures: FailureScope, delivery: Option<&DeliveryOptions>) -> AnalysisReport {
+    let centrality = metrics::betweenness(config);
+
+    let pairs = topology::client_server_distances(config)
+        .into_iter()
+        .map(|((client, server), hops)| PairReport {
+            client,
+            server,
+            hops,
+            edge_disjoint_paths: metrics::disjoint_paths(config, client, server, false),
+            node_disjoint_paths: metrics::disjoint_paths(config, client, server, true),
+        })
+        .collect();
+
+    let drone_metrics = config
+        .drone
+        .iter()
+        .map(|drone| DroneReport {
+            id: drone.id,
+            degree: drone.connected_node_ids.len(),
+            pdr: drone.pdr,
+            betweenness: centrality.get(&drone.id).copied().unwrap_or(0.),
+        })
+        .collect();
+
+    AnalysisReport {
+        drones: config.drone.len(),
+        clients: config.client.len(),
+        servers: config.server.len(),
+        diameter: metrics::diameter(config),
+        degree_distribution: metrics::degree_distribution(config),
+        pairs,
+        drone_metrics,
+        failure_impact: impact::failure_impact(config, failures),
+        delivery: delivery.map(|options| reliability::delivery(config, options)).unwrap_or_default(),
+    }
+}
+
+fn format_report(config_path: &str, report: &AnalysisReport) -> String {
+    let mut text = String::new();
+    let optional = |value: Option<usize>| value.map_or("-".to_string(), |v| v.to_string());
+
+    writeln!(text, "Topology \"{}\": {} drones, {} clients, {} servers", config_path, report.drones, report.clients, report.servers).unwrap();
+    writeln!(text, "Diameter: {} hops", optional(report.diameter)).unwrap();
+
+    writeln!(text, "\nClient  Server  Hops  Edge-disjoint  Node-disjoint  Node-disjoint paths").unwrap();
+    for pair in &report.pairs {
+        let paths: Vec<String> = pair
+            .node_disjoint_paths
+            .iter()
+            .map(|path| path.iter().map(|id| id.to_string()).collect::<Vec<_>>().join("-"))
+            .collect();
+        writeln!(
+            text,
+            "{:>6}  {:>6}  {:>4}  {:>13}  {:>13}  {}",
+            pair.client,
+            pair.server,
+            optional(pair.hops),
+            pair.edge_disjoint_paths.len(),
+            pair.node_disjoint_paths.len(),
+            paths.join(", ")
+        )
+        .unwrap();
+    }
+
+    writeln!(text, "\nDegree  Drones").unwrap();
+    for (degree, drones) in &report.degree_distribution {
+        writeln!(text, "{:>6}  {:>6}", degree, drones).unwrap();
+    }
+
+    // The most central drones first: they deserve the most reliable implementations
+    let mut drones: Vec<&DroneReport> = report.drone_metrics.iter().collect();
+    drones.sort_by(|a, b| b.betweenness.total_cmp(&a.betweenness).then(a.id.cmp(&b.id)));
+    writeln!(text, "\n Drone  Degree    PDR  Betweenness").unwrap();
+    for drone in drones {
+        writeln!(text, "{:>6}  {:>6}  {:>5}  {:>11.3}", drone.id, drone.degree, drone.pdr, drone.betweenness).unwrap();
+    }
+
+    text
+}
+
+// Matrix of the failures against the client-server pairs: "X" for a lost connection,
+// "+n" for a path n hops longer, "." for no change
+fn format_impact(failures: &[FailureImpact], scope: FailureScope) -> String {
+    let mut text = String::new();
+    let Some(first) = failures.first() else {
+        return text;
+    };
+
+    writeln!(text, "\nFailure impact, from the most harmful crash:").unwrap();
+    write!(text, "{:>9}  {:>7}  {:>4}  {:>5}", "Crashed", "Allowed", "Lost", "+Hops").unwrap();
+    for pair in &first.pairs {
+        write!(text, "  {:>7}", format!("{}>{}", pair.client, pair.server)).unwrap();
+    }
+    writeln!(text).unwrap();
+
+    let shown = if scope == FailureScope::DronePairs { PRINTED_FAILURES } else { failures.len() };
+    for failure in failures.iter().take(shown) {
+        let crashed: Vec<String> = failure.crashed.iter().map(|id| id.to_string()).collect();
+        write!(
+            text,
+            "{:>9}  {:>7}  {:>4}  {:>5}",
+            crashed.join("+"),
+            if failure.allowed { "yes" } else { "no" },
+            failure.disconnected,
+            failure.added_hops
+        )
+        .unwrap();
+        for pair in &failure.pairs {
+            let cell = match (pair.hops_before, pair.hops_after) {
+                (Some(_), None) => "X".to_string(),
+                (Some(before), Some(after)) if after > before => format!("+{}", after - before),
+                _ => ".".to_string(),
+            };
+            write!(text, "  {:>7}", cell).unwrap();
+        }
+        writeln!(text).unwrap();
+    }
+    if failures.len() > shown {
+        writeln!(text, "... {} more failures, see the JSON report", failures.len() - shown).unwrap();
+    }
+
+    text
+}
+
+fn format_delivery(pairs: &[PairDelivery], options: &DeliveryOptions) -> String {
+    let mut text = String::new();
+
+    writeln!(
+        text,
+        "\nDelivery model ({} retries, messages of {} bytes), routes ranked by expected transmissions:",
+        options.retries, options.message_size
+    )
+    .unwrap();
+    writeln!(text, "Client  Server  Rank  Fragment  With retries  Message  Transmissions  Route").unwrap();
+    for pair in pairs {
+        for (rank, route) in pair.routes.iter().enumerate() {
+            let hops: Vec<String> = route.route.iter().map(|id| id.to_string()).collect();
+            writeln!(
+                text,
+                "{:>6}  {:>6}  {:>4}  {:>8.4}  {:>12.4}  {:>7.4}  {:>13.2}  {}",
+                pair.client,
+                pair.server,
+                rank + 1,
+                route.fragment_delivery,
+                route.fragment_delivery_with_retries,
+                route.message_delivery,
+                route.expected_transmissions,
+                hops.join("-")
+            )
+            .unwrap();
+        }
+    }
+    text
+}
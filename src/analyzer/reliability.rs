@@ -0,0 +1,276 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use wg_2024::config::Config;
+use wg_2024::network::NodeId;
+use wg_2024::packet::FRAGMENT_DSIZE;
+
+use crate::utils::topology;
+
+// Added to the cost of each hop, so that among equally reliable routes the shortest wins
+const HOP_COST: f64 = 1e-6;
+
+/// Parameters of the delivery model
+#[derive(Debug, Clone, Copy)]
+pub struct DeliveryOptions {
+    /// Routes computed for each client-server pair
+    pub routes: usize,
+    /// Times a dropped fragment is sent again
+    pub retries: usize,
+    /// Size of a message, in bytes, to compute the number of its fragments
+    pub message_size: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PairDelivery {
+    pub client: NodeId,
+    pub server: NodeId,
+    /// The most reliable routes, ranked by expected transmissions: the first is the best one
+    pub routes: Vec<RouteDelivery>,
+}
+
+/// Expected behaviour of a fragment sent along a route.
+/// Drones only drop `MsgFragment`s, so the Acks and Nacks always come back:
+/// they do not change the delivery probabilities, only the transmissions.
+#[derive(Debug, Clone, Serialize)]
+pub struct RouteDelivery {
+    pub route: Vec<NodeId>,
+    /// Probability that a single attempt reaches the server
+    pub fragment_delivery: f64,
+    /// Probability that the fragment reaches the server within the retries
+    pub fragment_delivery_with_retries: f64,
+    /// Probability that every fragment of a message reaches the server within the retries
+    pub message_delivery: f64,
+    /// Packets sent over the links, Nacks and Acks included, until a fragment is delivered.
+    /// Infinite (null in JSON) when the route drops everything.
+    pub expected_transmissions: f64,
+}
+
+/// Computes the delivery model of the k most reliable routes between every client and server
+pub fn delivery(config: &Config, options: &DeliveryOptions) -> Vec<PairDelivery> {
+    let pdrs = drone_pdrs(config);
+    let fragments = options.message_size.div_ceil(FRAGMENT_DSIZE).max(1);
+
+    let mut pairs = Vec::new();
+    for client in &config.client {
+        for server in &config.server {
+            let mut routes: Vec<RouteDelivery> = k_best_routes(config, &pdrs, client.id, server.id, options.routes)
+                .into_iter()
+                .map(|route| route_delivery(route, &pdrs, options.retries, fragments))
+                .collect();
+            routes.sort_by(|a, b| a.expected_transmissions.total_cmp(&b.expected_transmissions));
+            pairs.push(PairDelivery { client: client.id, server: server.id, routes });
+        }
+    }
+    pairs
+}
+
+/// The route most likely to deliver a fragment in a single attempt
+pub fn most_reliable_route(config: &Config, from: NodeId, to: NodeId) -> Option<Vec<NodeId>> {
+    cheapest_route(config, &drone_pdrs(config), from, to, &HashSet::new(), &HashSet::new())
+}
+
+/// Probability that a single attempt crosses every drone of the route.
+/// Nodes missing from the topology count as drones that drop everything,
+/// routes without drones (less than 3 nodes) always deliver.
+pub fn fragment_delivery(config: &Config, route: &[NodeId]) -> f64 {
+    if route.len() < 3 {
+        return 1.;
+    }
+    let pdrs = drone_pdrs(config);
+    route[1..route.len() - 1]
+        .iter()
+        .map(|id| 1. - pdrs.get(id).copied().unwrap_or(1.))
+        .product()
+}
+
+fn drone_pdrs(config: &Config) -> HashMap<NodeId, f64> {
+    config.drone.iter().map(|d| (d.id, d.pdr as f64)).collect()
+}
+
+fn route_delivery(route: Vec<NodeId>, pdrs: &HashMap<NodeId, f64>, retries: usize, fragments: usize) -> RouteDelivery {
+    let drones: Vec<f64> = route[1..route.len() - 1].iter().map(|id| pdrs[id]).collect();
+    let links = route.len() - 1;
+
+    // The fragment crosses the first link, then each drone forwards it with probability 1 - pdr.
+    // A drop at the i-th drone sends a Nack back over i links, a delivery an Ack over every link.
+    let mut reached = 1.;
+    let mut transmissions = 1.;
+    for (i, pdr) in drones.iter().enumerate() {
+        transmissions += reached * pdr * (i + 1) as f64;
+        reached *= 1. - pdr;
+        transmissions += reached;
+    }
+    transmissions += reached * links as f64;
+
+    let with_retries = 1. - (1. - reached).powi(retries as i32 + 1);
+    RouteDelivery {
+        route,
+        fragment_delivery: reached,
+        fragment_delivery_with_retries: with_retries,
+        message_delivery: with_retries.powi(fragments as i32),
+        // Attempts are geometric, 1 / reached of them are needed on average
+        expected_transmissions: if reached > 0. { transmissions / reached } else { f64::INFINITY },
+    }
+}
+
+/// Yen's algorithm: the `k` loopless routes with the lowest cost, where crossing a drone costs
+/// -ln(1 - pdr), so the cheapest route is the one most likely to deliver a fragment.
+fn k_best_routes(config: &Config, pdrs: &HashMap<NodeId, f64>, from: NodeId, to: NodeId, k: usize) -> Vec<Vec<NodeId>> {
+    let cost = |route: &[NodeId]| -> f64 {
+        route.iter().filter_map(|id| pdrs.get(id)).map(|pdr| -(1. - pdr).ln()).sum::<f64>() + HOP_COST * route.len() as f64
+    };
+
+    let mut best: Vec<Vec<NodeId>> = Vec::new();
+    let Some(first) = cheapest_route(config, pdrs, from, to, &HashSet::new(), &HashSet::new()) else {
+        return best;
+    };
+    best.push(first);
+    let mut candidates: Vec<Vec<NodeId>> = Vec::new();
+
+    while best.len() < k {
+        let previous = best.last().unwrap().clone();
+        for j in 0..previous.len() - 1 {
+            let root = &previous[..=j];
+            let removed_links: HashSet<(NodeId, NodeId)> = best
+                .iter()
+                .filter(|route| route.len() > j + 1 && route[..=j] == *root)
+                .map(|route| (route[j], route[j + 1]))
+                .collect();
+            let removed_nodes: HashSet<NodeId> = root[..j].iter().copied().collect();
+
+            if let Some(spur) = cheapest_route(config, pdrs, root[j], to, &removed_nodes, &removed_links) {
+                let route: Vec<NodeId> = root[..j].iter().chain(spur.iter()).copied().collect();
+                if !best.contains(&route) && !candidates.contains(&route) {
+                    candidates.push(route);
+                }
+            }
+        }
+
+        let Some(next) = (0..candidates.len()).min_by(|a, b| cost(&candidates[*a]).total_cmp(&cost(&candidates[*b]))) else {
+            break;
+        };
+        best.push(candidates.swap_remove(next));
+    }
+    best
+}
+
+// Dijkstra through the drones only, avoiding some nodes and links
+fn cheapest_route(
+    config: &Config,
+    pdrs: &HashMap<NodeId, f64>,
+    from: NodeId,
+    to: NodeId,
+    removed_nodes: &HashSet<NodeId>,
+    removed_links: &HashSet<(NodeId, NodeId)>,
+) -> Option<Vec<NodeId>> {
+    let mut costs: HashMap<NodeId, f64> = HashMap::from([(from, 0.)]);
+    let mut previous: HashMap<NodeId, NodeId> = HashMap::new();
+    let mut done: HashSet<NodeId> = HashSet::new();
+
+    while let Some((id, cost)) = costs
+        .iter()
+        .filter(|(id, _)| !done.contains(*id))
+        .min_by(|a, b| a.1.total_cmp(b.1))
+        .map(|(id, cost)| (*id, *cost))
+    {
+        done.insert(id);
+        if id == to {
+            let mut route = vec![to];
+            while let Some(node) = previous.get(route.last().unwrap()) {
+                route.push(*node);
+            }
+            route.reverse();
+            return Some(route);
+        }
+        if id != from && !topology::is_drone(config, id) {
+            continue;
+        }
+
+        for neighbour in topology::neighbours(config, id).into_iter().flatten() {
+            if removed_nodes.contains(neighbour) || removed_links.contains(&(id, *neighbour)) || done.contains(neighbour) {
+                continue;
+            }
+            let crossing = pdrs.get(neighbour).map_or(0., |pdr| -(1. - pdr).ln());
+            let new_cost = cost + crossing + HOP_COST;
+            if costs.get(neighbour).is_none_or(|c| new_cost < *c) {
+                costs.insert(*neighbour, new_cost);
+                previous.insert(*neighbour, id);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::topology::tests::config_with_links;
+
+    // Client 100 reaches drones 1 and 2, server 200 drones 3 and 4, with every drone-drone link between them
+    fn crossed() -> Config {
+        config_with_links(
+            &[(1, 0.5), (2, 0.1), (3, 0.1), (4, 0.2)],
+            &[100],
+            &[200],
+            &[(100, 1), (100, 2), (1, 3), (1, 4), (2, 3), (2, 4), (3, 200), (4, 200)],
+        )
+    }
+
+    #[test]
+    fn ranks_the_routes_by_reliability() {
+        let config = crossed();
+        let routes = k_best_routes(&config, &drone_pdrs(&config), 100, 200, 4);
+        assert_eq!(routes, [vec![100, 2, 3, 200], vec![100, 2, 4, 200], vec![100, 1, 3, 200], vec![100, 1, 4, 200]]);
+        assert_eq!(most_reliable_route(&config, 100, 200), Some(vec![100, 2, 3, 200]));
+    }
+
+    #[test]
+    fn finds_every_loopless_route() {
+        let config = crossed();
+        let routes = k_best_routes(&config, &drone_pdrs(&config), 100, 200, 20);
+
+        // The 4 routes through two drones, and the 4 that cross every drone
+        assert_eq!(routes.len(), 8);
+        for route in &routes {
+            let unique: HashSet<&NodeId> = route.iter().collect();
+            assert_eq!(unique.len(), route.len(), "{:?}", route);
+        }
+        assert!(routes[4..].iter().all(|route| route.len() == 6));
+    }
+
+    #[test]
+    fn finds_no_route_between_disconnected_hosts() {
+        let mut config = crossed();
+        topology::remove_link(&mut config, 1, 3).unwrap();
+        topology::remove_link(&mut config, 1, 4).unwrap();
+        topology::remove_link(&mut config, 2, 3).unwrap();
+        topology::remove_link(&mut config, 2, 4).unwrap();
+
+        assert_eq!(most_reliable_route(&config, 100, 200), None);
+        assert!(k_best_routes(&config, &drone_pdrs(&config), 100, 200, 3).is_empty());
+    }
+
+    #[test]
+    fn models_the_retries_and_the_acks() {
+        let config = config_with_links(&[(1, 0.5)], &[100], &[200], &[(100, 1), (1, 200)]);
+        let options = DeliveryOptions { routes: 1, retries: 1, message_size: 2 * FRAGMENT_DSIZE };
+        let route = &delivery(&config, &options)[0].routes[0];
+
+        assert_eq!(route.route, [100, 1, 200]);
+        assert_eq!(route.fragment_delivery, 0.5);
+        assert_eq!(route.fragment_delivery_with_retries, 0.75);
+        assert_eq!(route.message_delivery, 0.5625);
+        // An attempt sends the fragment, the Nack half of the times, else the forward and 2 Acks
+        assert_eq!(route.expected_transmissions, 6.);
+    }
+
+    #[test]
+    fn computes_the_delivery_of_any_route() {
+        let config = crossed();
+        assert!((fragment_delivery(&config, &[100, 2, 4, 200]) - 0.72).abs() < 1e-6);
+        assert_eq!(fragment_delivery(&config, &[100, 9, 200]), 0.);
+        assert_eq!(fragment_delivery(&config, &[100, 200]), 1.);
+        assert_eq!(fragment_delivery(&config, &[100]), 1.);
+        assert_eq!(fragment_delivery(&config, &[]), 1.);
+    }
+}
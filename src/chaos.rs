@@ -0,0 +1,147 @@
+use log::{debug, info};
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::thread;
+use std::time::Duration;
+use wg_2024::config::Config;
+use wg_2024::network::NodeId;
+
+use crate::network_control::NetworkControl;
+use crate::utils::topology::{self, Mutation};
+
+// Upper bound of the packet drop rates picked by the chaos engine,
+// higher values make the network valid but useless
+const MAX_CHAOS_PDR: f32 = 0.5;
+
+/// Chaos engine: periodically crashes drones, changes PDRs and removes and re-adds links.
+/// Every action is checked with `input_validator::validate_mutation`
+/// and skipped if it would break the validator rules. Given the same seed, the same actions are chosen.
+pub struct ChaosEngine {
+    seed: u64,
+    rng: ChaCha8Rng,
+    interval: Duration,
+    removed_links: Vec<(NodeId, NodeId)>,
+}
+
+impl ChaosEngine {
+    pub fn new(seed: u64, interval: Duration) -> Self {
+        Self {
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            interval,
+            removed_links: Vec::new(),
+        }
+    }
+
+    pub fn spawn(mut self, network: NetworkControl) -> thread::JoinHandle<()> {
+        info!("Chaos engine started with seed {}", self.seed);
+        thread::spawn(move || {
+            let mut step: u64 = 0;
+            loop {
+                thread::sleep(self.interval);
+                step += 1;
+
+                let current = network.topology();
+                let Some(mutation) = self.pick_action(&current) else {
+                    debug!("[chaos seed {}] #{}: no action available", self.seed, step);
+                    continue;
+                };
+
+                match network.apply_validated(&mutation) {
+                    Ok(()) => {
+                        info!("[chaos seed {}] #{}: {}", self.seed, step, mutation);
+                        self.applied(&mutation);
+                    }
+                    Err(reason) => info!("[chaos seed {}] #{}: skipped \"{}\": {}", self.seed, step, mutation, reason),
+                }
+            }
+        })
+    }
+
+    // Removed links are remembered so they can be added back later
+    fn applied(&mut self, mutation: &Mutation) {
+        match *mutation {
+            Mutation::RemoveLink(a, b) => self.removed_links.push((a, b)),
+            Mutation::AddLink(a, b) => self.removed_links.retain(|l| *l != (a, b)),
+            _ => {}
+        }
+    }
+
+    fn pick_action(&mut self, current: &Config) -> Option<Mutation> {
+        let drones: Vec<NodeId> = current.drone.iter().map(|d| d.id).collect();
+        let links: Vec<(NodeId, NodeId)> = current
+            .drone
+            .iter()
+            .flat_map(|d| d.connected_node_ids.iter().map(move |n| (d.id, *n)))
+            .filter(|(a, b)| a < b || !topology::is_drone(current, *b))
+            .collect();
+        // Links whose endpoints are both still alive
+        let removed_links: Vec<(NodeId, NodeId)> = self
+            .removed_links
+            .iter()
+            .copied()
+            .filter(|(a, b)| topology::neighbours(current, *a).is_some() && topology::neighbours(current, *b).is_some())
+            .collect();
+
+        match self.rng.gen_range(0..4) {
+            0 => drones.choose(&mut self.rng).map(|id| Mutation::Crash(*id)),
+            1 => {
+                let id = *drones.choose(&mut self.rng)?;
+                Some(Mutation::SetPdr(id, self.rng.gen_range(0.0..=MAX_CHAOS_PDR)))
+            }
+            2 => links.choose(&mut self.rng).map(|(a, b)| Mutation::RemoveLink(*a, *b)),
+            _ => removed_links.choose(&mut self.rng).map(|(a, b)| Mutation::AddLink(*a, *b)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::input_validator;
+    use crate::utils::topology::tests::config_with_links;
+
+    // Runs the engine on a topology without a network, applying the actions the validator accepts
+    fn actions(seed: u64, steps: usize) -> Vec<Option<Mutation>> {
+        let mut current = config_with_links(
+            &[(1, 0.1), (2, 0.1), (3, 0.1), (4, 0.1), (5, 0.1)],
+            &[100, 101],
+            &[200],
+            &[(100, 1), (101, 2), (1, 2), (1, 3), (2, 3), (2, 4), (3, 4), (3, 5), (4, 5), (4, 200), (5, 200)],
+        );
+        let mut engine = ChaosEngine::new(seed, Duration::ZERO);
+
+        (0..steps)
+            .map(|_| {
+                let mutation = engine.pick_action(&current)?;
+                if input_validator::validate_mutation(&current, &mutation).is_ok() {
+                    topology::apply_mutation(&mut current, &mutation).unwrap();
+                    engine.applied(&mutation);
+                }
+                Some(mutation)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_actions() {
+        let first = actions(42, 50);
+        assert!(first.iter().flatten().count() > 10);
+        assert_eq!(first, actions(42, 50));
+    }
+
+    #[test]
+    fn different_seeds_give_different_actions() {
+        assert_ne!(actions(42, 50), actions(43, 50));
+    }
+
+    #[test]
+    fn pdrs_stay_below_the_chaos_bound() {
+        for mutation in actions(7, 100).into_iter().flatten() {
+            if let Mutation::SetPdr(_, pdr) = mutation {
+                assert!((0.0..=MAX_CHAOS_PDR).contains(&pdr));
+            }
+        }
+    }
+}
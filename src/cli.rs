@@ -0,0 +1,314 @@
+use crate::analyzer::{DeliveryOptions, FailureScope};
+use crate::generator::{GeneratorOptions, PdrDistribution, RandomOptions, Shape};
+use crate::importer::ImportFormat;
+
+/// What the initializer has been asked to do on the command line
+pub enum Command {
+    /// Launch the network
+    Run { config_path: String, watch: bool },
+    /// Export the topology as a graph
+    Plan {
+        config_path: String,
+        format: PlanFormat,
+        output: Option<String>,
+    },
+    /// Convert a graph file into a topology file
+    Import {
+        path: String,
+        format: Option<ImportFormat>,
+        default_pdr: f32,
+        output: Option<String>,
+    },
+    /// Print the graph metrics of the topology
+    Analyze {
+        config_path: String,
+        failures: FailureScope,
+        delivery: Option<DeliveryOptions>,
+        json: Option<String>,
+    },
+    /// Check the topology file, optionally fixing its mechanical mistakes
+    Validate {
+        config_path: String,
+        fix: bool,
+        output: Option<String>,
+    },
+    /// Write a topology file with a standard shape
+    Generate {
+        shape: Shape,
+        options: GeneratorOptions,
+        output: Option<String>,
+    },
+    /// Write random topology files
+    GenerateRandom {
+        options: RandomOptions,
+        count: u64,
+        output: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlanFormat {
+    Dot,
+    Mermaid,
+    /// The plain `wg_2024` format, with the links expanded
+    Toml,
+}
+
+pub const USAGE: &str = "Usage:
+  network-initializer [--config <path>] [--watch]
+  network-initializer plan [--config <path>] [--format dot|mermaid|toml] [--output <path>]
+  network-initializer validate [--config <path>] [--fix] [--output <path>]
+  network-initializer analyze [--config <path>] [--impact] [--pairs] [--json <path>]
+                      [--routes <k>] [--retries <n>] [--message-size <bytes>]
+  network-initializer import <file> [--format dot|graphml|csv] [--pdr <value>] [--output <path>]
+  network-initializer generate <shape> [--drones <n>] [--width <n>] [--clients <n>] [--servers <n>]
+                      [--client-links <n>] [--server-links <n>] [--pdr <value>] [--output <path>]
+  network-initializer generate random [--seed <n>] [--count <n>] [--drones <n>] [--clients <n>] [--servers <n>]
+                      [--degree <value>] [--server-links <n>] [--pdr <value>|uniform(<min>, <max>)] [--output <path>]
+
+Options:
+  --config <path>   topology file to load (default: input.toml)
+  --watch           apply the changes of the topology file to the running network
+  --fix             correct one-way links, repeated neighbours, self-loops, links to missing nodes
+                    and PDRs out of range, writing the topology over the file unless --output is given
+                    (required for files with includes, ranges or links)
+  --impact          simulate the crash of each drone
+  --pairs           simulate the crash of each pair of drones too
+  --routes <k>      compute the delivery probabilities of the k most reliable routes of each pair
+  --retries <n>     times a dropped fragment is sent again, for --routes (default: 3)
+  --message-size <bytes> size of a message, for --routes (default: 1024)
+  --json <path>     also write the analysis as JSON
+  --format <format> format of the exported topology (default: dot),
+                    or of the imported file (default: guessed from the extension)
+  --pdr <value>     PDR of the imported drones without a \"pdr\" attribute (default: 0),
+                    or of the generated drones (default: 0)
+  <shape>           chain, double-chain, ring, star, grid, tree, butterfly or full
+  --drones <n>      drones of the generated topology (default: 10)
+  --width <n>       drones in each row of a grid (default: square root of the drones)
+  --clients <n>     clients of the generated topology (default: 1)
+  --servers <n>     servers of the generated topology (default: 1)
+  --client-links <n> drones each generated client is connected to (default: 1)
+  --server-links <n> drones each generated server is connected to (default: 2)
+  --seed <n>        seed of the random topology (default: a random one)
+  --count <n>       random topologies to generate with consecutive seeds, one file each (default: 1)
+  --degree <value>  average number of drone neighbours of each random drone (default: 3)
+  --output <path>   write the result to a file instead of the standard output";
+
+pub fn parse_args(args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut args = args.peekable();
+
+    match args.peek().map(String::as_str) {
+        Some("plan") => {
+            args.next();
+            parse_plan(args)
+        }
+        Some("validate") => {
+            args.next();
+            parse_validate(args)
+        }
+        Some("analyze") => {
+            args.next();
+            parse_analyze(args)
+        }
+        Some("import") => {
+            args.next();
+            parse_import(args)
+        }
+        Some("generate") => {
+            args.next();
+            if args.peek().map(String::as_str) == Some("random") {
+                args.next();
+                parse_generate_random(args)
+            } else {
+                parse_generate(args)
+            }
+        }
+        _ => parse_run(args),
+    }
+}
+
+fn parse_run(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut config_path = "input.toml".to_string();
+    let mut watch = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => config_path = next_value(&mut args, &arg)?,
+            "--watch" => watch = true,
+            _ => return Err(format!("Unknown argument \"{}\"", arg)),
+        }
+    }
+
+    Ok(Command::Run { config_path, watch })
+}
+
+fn parse_plan(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut config_path = "input.toml".to_string();
+    let mut format = PlanFormat::Dot;
+    let mut output = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => config_path = next_value(&mut args, &arg)?,
+            "--format" => {
+                format = match next_value(&mut args, &arg)?.as_str() {
+                    "dot" => PlanFormat::Dot,
+                    "mermaid" => PlanFormat::Mermaid,
+                    "toml" => PlanFormat::Toml,
+                    other => return Err(format!("Unknown format \"{}\"! Valid formats: dot, mermaid, toml", other)),
+                }
+            }
+            "--output" => output = Some(next_value(&mut args, &arg)?),
+            _ => return Err(format!("Unknown argument \"{}\"", arg)),
+        }
+    }
+
+    Ok(Command::Plan { config_path, format, output })
+}
+
+fn parse_validate(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut config_path = "input.toml".to_string();
+    let mut fix = false;
+    let mut output = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => config_path = next_value(&mut args, &arg)?,
+            "--fix" => fix = true,
+            "--output" => output = Some(next_value(&mut args, &arg)?),
+            _ => return Err(format!("Unknown argument \"{}\"", arg)),
+        }
+    }
+
+    Ok(Command::Validate { config_path, fix, output })
+}
+
+fn parse_analyze(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut config_path = "input.toml".to_string();
+    let mut failures = FailureScope::None;
+    let mut routes = None;
+    let mut delivery = DeliveryOptions {
+        routes: 0,
+        retries: 3,
+        message_size: 1024,
+    };
+    let mut json = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => config_path = next_value(&mut args, &arg)?,
+            "--impact" => failures = failures.max(FailureScope::Drones),
+            "--pairs" => failures = FailureScope::DronePairs,
+            "--routes" => routes = Some(next_number(&mut args, &arg)?),
+            "--retries" => delivery.retries = next_number(&mut args, &arg)?,
+            "--message-size" => delivery.message_size = next_number(&mut args, &arg)?,
+            "--json" => json = Some(next_value(&mut args, &arg)?),
+            _ => return Err(format!("Unknown argument \"{}\"", arg)),
+        }
+    }
+
+    let delivery = routes.map(|routes| DeliveryOptions { routes, ..delivery });
+    Ok(Command::Analyze { config_path, failures, delivery, json })
+}
+
+fn parse_import(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut path = None;
+    let mut format = None;
+    let mut default_pdr = 0.;
+    let mut output = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => format = Some(ImportFormat::parse(&next_value(&mut args, &arg)?)?),
+            "--pdr" => {
+                let value = next_value(&mut args, &arg)?;
+                default_pdr = value.parse().map_err(|_| format!("Invalid PDR \"{}\"", value))?;
+            }
+            "--output" => output = Some(next_value(&mut args, &arg)?),
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg),
+            _ => return Err(format!("Unknown argument \"{}\"", arg)),
+        }
+    }
+
+    let path = path.ok_or("Missing the file to import".to_string())?;
+    Ok(Command::Import { path, format, default_pdr, output })
+}
+
+fn parse_generate(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut shape = None;
+    let mut options = GeneratorOptions {
+        drones: 10,
+        width: None,
+        clients: 1,
+        servers: 1,
+        client_links: 1,
+        server_links: 2,
+        pdr: 0.,
+    };
+    let mut output = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--drones" => options.drones = next_number(&mut args, &arg)?,
+            "--width" => options.width = Some(next_number(&mut args, &arg)?),
+            "--clients" => options.clients = next_number(&mut args, &arg)?,
+            "--servers" => options.servers = next_number(&mut args, &arg)?,
+            "--client-links" => options.client_links = next_number(&mut args, &arg)?,
+            "--server-links" => options.server_links = next_number(&mut args, &arg)?,
+            "--pdr" => {
+                let value = next_value(&mut args, &arg)?;
+                options.pdr = value.parse().map_err(|_| format!("Invalid PDR \"{}\"", value))?;
+            }
+            "--output" => output = Some(next_value(&mut args, &arg)?),
+            _ if shape.is_none() && !arg.starts_with("--") => shape = Some(Shape::parse(&arg)?),
+            _ => return Err(format!("Unknown argument \"{}\"", arg)),
+        }
+    }
+
+    let shape = shape.ok_or("Missing the shape to generate".to_string())?;
+    Ok(Command::Generate { shape, options, output })
+}
+
+fn parse_generate_random(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut options = RandomOptions {
+        seed: rand::random(),
+        drones: 10,
+        clients: 1,
+        servers: 1,
+        degree: 3.,
+        server_links: 2,
+        pdr: PdrDistribution::Constant(0.),
+    };
+    let mut count = 1;
+    let mut output = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => options.seed = next_number(&mut args, &arg)? as u64,
+            "--count" => count = next_number(&mut args, &arg)? as u64,
+            "--drones" => options.drones = next_number(&mut args, &arg)?,
+            "--clients" => options.clients = next_number(&mut args, &arg)?,
+            "--servers" => options.servers = next_number(&mut args, &arg)?,
+            "--server-links" => options.server_links = next_number(&mut args, &arg)?,
+            "--degree" => {
+                let value = next_value(&mut args, &arg)?;
+                options.degree = value.parse().map_err(|_| format!("Invalid degree \"{}\"", value))?;
+            }
+            "--pdr" => options.pdr = PdrDistribution::parse(&next_value(&mut args, &arg)?)?,
+            "--output" => output = Some(next_value(&mut args, &arg)?),
+            _ => return Err(format!("Unknown argument \"{}\"", arg)),
+        }
+    }
+
+    Ok(Command::GenerateRandom { options, count, output })
+}
+
+// Returns the value that follows an option
+fn next_value(args: &mut impl Iterator<Item = String>, option: &str) -> Result<String, String> {
+    args.next().ok_or(format!("Missing value after {}", option))
+}
+
+fn next_number(args: &mut impl Iterator<Item = String>, option: &str) -> Result<usize, String> {
+    let value = next_value(args, option)?;
+    value.parse().map_err(|_| format!("Invalid number \"{}\" after {}", value, option))
+}
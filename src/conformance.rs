@@ -0,0 +1,344 @@
+use crossbeam_channel::{unbounded, Receiver, RecvTimeoutError, Sender};
+use log::{debug, info, warn};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use wg_2024::controller::{DroneCommand, DroneEvent};
+use wg_2024::network::NodeId;
+use wg_2024::packet::{Packet, PacketType};
+
+use crate::event_tap::EventObserver;
+use crate::link_layer::{packet_type_name, PacketObserver};
+
+// How often the drop rates are checked
+const REPORT_INTERVAL: Duration = Duration::from_secs(30);
+// Fragments needed before the drop rate of a drone is checked
+const MIN_FRAGMENTS: u64 = 30;
+// 99% confidence: with many drones, a lower confidence flags conforming drones too often
+const Z: f64 = 2.576;
+// The events of different taps reach the monitor in any order, so they are handled in the order
+// they were seen only after this delay
+const REORDER_DELAY: Duration = Duration::from_millis(50);
+// After a PDR change, the drone may still handle the fragments queued before the command for this long,
+// so the fragments of this window are not counted for either PDR
+const SETTLE_WINDOW: Duration = Duration::from_millis(200);
+
+/// What a drone did with a packet, as seen by the event tap or by the link layer
+pub enum DropEvent {
+    Forwarded(NodeId, &'static str),
+    Dropped(NodeId, &'static str),
+    /// A drone dropped a packet that is not a fragment
+    ForbiddenDrop(NodeId, &'static str, &'static str),
+    /// A fragment arrived on the link of a drone
+    Received(NodeId),
+    /// A drone was commanded to use a new PDR
+    PdrSet(NodeId, f32),
+}
+
+/// Collects the fragments each drone forwards and drops, from its `DroneEvent`s,
+/// the PDRs it is commanded to use, from its `DroneCommand`s, and the fragments that reach it, from the link layer.
+/// Each event is timestamped when the tap sees it.
+pub struct ConformanceMonitor {
+    events: Sender<(Instant, DropEvent)>,
+}
+
+impl ConformanceMonitor {
+    /// Returns the observer to register on the event tap and on the link layer,
+    /// and the receiver of the events it sees
+    pub fn new() -> (Arc<Self>, Receiver<(Instant, DropEvent)>) {
+        let (sender, receiver) = unbounded();
+        (Arc::new(Self { events: sender }), receiver)
+    }
+
+    fn send(&self, event: DropEvent) {
+        let _ = self.events.send((Instant::now(), event));
+    }
+}
+
+impl EventObserver for ConformanceMonitor {
+    fn on_drone_event(&self, drone_id: NodeId, drone_type: &'static str, event: &DroneEvent) {
+        let event = match event {
+            DroneEvent::PacketSent(packet) if is_fragment(packet) => DropEvent::Forwarded(drone_id, drone_type),
+            DroneEvent::PacketDropped(packet) if is_fragment(packet) => DropEvent::Dropped(drone_id, drone_type),
+            DroneEvent::PacketDropped(packet) => {
+                DropEvent::ForbiddenDrop(drone_id, drone_type, packet_type_name(&packet.pack_type))
+            }
+            _ => return,
+        };
+        self.send(event);
+    }
+
+    // Seen before the drone gets the command: the events the drone sent before this time were for the old PDR,
+    // but the drone may still have fragments to handle before the command, see `SETTLE_WINDOW`
+    fn on_drone_command(&self, drone_id: NodeId, command: &DroneCommand) {
+        if let DroneCommand::SetPacketDropRate(pdr) = command {
+            self.send(DropEvent::PdrSet(drone_id, *pdr));
+        }
+    }
+}
+
+impl PacketObserver for ConformanceMonitor {
+    fn on_packet(&self, to: NodeId, packet: &Packet) {
+        if is_fragment(packet) {
+            self.send(DropEvent::Received(to));
+        }
+    }
+}
+
+fn is_fragment(packet: &Packet) -> bool {
+    matches!(packet.pack_type, PacketType::MsgFragment(_))
+}
+
+/// Fragments handled by a drone while it had a given PDR
+#[derive(Debug, Default)]
+struct Trials {
+    forwarded: u64,
+    dropped: u64,
+}
+
+/// What the monitor knows about the drones, updated with the events in the order they were seen
+#[derive(Default)]
+struct Tally {
+    /// The PDR each drone has, as last commanded
+    pdrs: HashMap<NodeId, f32>,
+    /// When the PDR of each drone last changed
+    changed_at: HashMap<NodeId, Instant>,
+    // Keyed by drone and PDR (as bits, to be hashable)
+    trials: BTreeMap<(NodeId, u32), Trials>,
+    implementations: HashMap<NodeId, &'static str>,
+    received: HashMap<NodeId, u64>,
+    forbidden: HashSet<(NodeId, &'static str)>,
+}
+
+impl Tally {
+    fn handle(&mut self, time: Instant, event: DropEvent) {
+        match event {
+            DropEvent::Received(id) => *self.received.entry(id).or_default() += 1,
+            DropEvent::PdrSet(id, pdr) => {
+                self.pdrs.insert(id, pdr);
+                self.changed_at.insert(id, time);
+            }
+            DropEvent::Forwarded(id, drone_type) => {
+                self.implementations.insert(id, drone_type);
+                if let Some(trials) = self.current_trials(id, time) {
+                    trials.forwarded += 1;
+                }
+            }
+            DropEvent::Dropped(id, drone_type) => {
+                self.implementations.insert(id, drone_type);
+                if let Some(trials) = self.current_trials(id, time) {
+                    trials.dropped += 1;
+                }
+            }
+            DropEvent::ForbiddenDrop(id, drone_type, packet_type) => {
+                if self.forbidden.insert((id, packet_type)) {
+                    warn!(
+                        "PDR conformance: drone {} ({}) dropped a {}, only fragments can be dropped",
+                        id, drone_type, packet_type
+                    );
+                }
+            }
+        }
+    }
+
+    // The trials of the PDR the drone had at `time`, None in the window that follows a PDR change
+    fn current_trials(&mut self, id: NodeId, time: Instant) -> Option<&mut Trials> {
+        if self.changed_at.get(&id).is_some_and(|changed_at| time.saturating_duration_since(*changed_at) < SETTLE_WINDOW) {
+            return None;
+        }
+        let pdr = self.pdrs.get(&id).copied().unwrap_or_default();
+        Some(self.trials.entry((id, pdr.to_bits())).or_default())
+    }
+}
+
+/// Checks that each drone drops fragments at its PDR: the drop rate observed while the drone had a PDR
+/// gives a Wilson score interval, and the drone is flagged when its PDR falls outside of it.
+/// `pdrs` holds the PDR each drone starts with, and is kept up to date with the `SetPacketDropRate`
+/// commands the drones receive, from the simulation controller or from the initializer.
+/// The command and event taps are different threads, so their events are sorted by the time they were seen,
+/// and the fragments of the `SETTLE_WINDOW` that follows a change are not counted.
+/// Drones dropping packets that are not fragments are flagged at once, as the protocol forbids it.
+pub fn spawn(events: Receiver<(Instant, DropEvent)>, pdrs: HashMap<NodeId, f32>) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let mut tally = Tally { pdrs, ..Tally::default() };
+        // Sorted by the time they were seen, then by arrival
+        let mut pending: BTreeMap<(Instant, u64), DropEvent> = BTreeMap::new();
+        let mut arrivals = 0;
+        let mut next_report = Instant::now() + REPORT_INTERVAL;
+
+        loop {
+            let timeout = next_report.saturating_duration_since(Instant::now()).min(REORDER_DELAY);
+            match events.recv_timeout(timeout) {
+                Ok((time, event)) => {
+                    pending.insert((time, arrivals), event);
+                    arrivals += 1;
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+
+            while let Some(entry) = pending.first_entry() {
+                if entry.key().0.elapsed() < REORDER_DELAY {
+                    break;
+                }
+                let ((time, _), event) = entry.remove_entry();
+                tally.handle(time, event);
+            }
+
+            if Instant::now() >= next_report {
+                report(&tally);
+                next_report = Instant::now() + REPORT_INTERVAL;
+            }
+        }
+    })
+}
+
+fn report(tally: &Tally) {
+    let (mut conforming, mut non_conforming, mut too_few) = (0, 0, 0);
+
+    for ((id, pdr), trial) in &tally.trials {
+        let pdr = f32::from_bits(*pdr) as f64;
+        let drone_type = tally.implementations.get(id).copied().unwrap_or("Unknown");
+        let total = trial.forwarded + trial.dropped;
+        if total < MIN_FRAGMENTS {
+            too_few += 1;
+            continue;
+        }
+
+        let (low, high) = wilson_interval(trial.dropped, total);
+        let observed = trial.dropped as f64 / total as f64;
+        let verdict = if pdr < low {
+            Some("drops too much")
+        } else if pdr > high {
+            Some("drops too little")
+        } else {
+            None
+        };
+
+        match verdict {
+            Some(verdict) => {
+                non_conforming += 1;
+                warn!(
+                    "PDR conformance: drone {} ({}) {}: PDR {}, dropped {} of {} fragments ({:.3}, 99% interval {:.3}-{:.3})",
+                    id, drone_type, verdict, pdr, trial.dropped, total, observed, low, high
+                );
+            }
+            None => {
+                conforming += 1;
+                debug!(
+                    "PDR conformance: drone {} ({}) conforms: PDR {}, dropped {} of {} fragments ({:.3}, 99% interval {:.3}-{:.3})",
+                    id, drone_type, pdr, trial.dropped, total, observed, low, high
+                );
+            }
+        }
+    }
+
+    // Without events the drop rate of a drone cannot be checked
+    let reporting: HashSet<NodeId> = tally.trials.keys().map(|(id, _)| *id).collect();
+    for (id, count) in &tally.received {
+        if *count >= MIN_FRAGMENTS && !reporting.contains(id) && tally.pdrs.contains_key(id) {
+            warn!("PDR conformance: drone {} received {} fragments but reported none of them", id, count);
+        }
+    }
+
+    info!(
+        "PDR conformance: {} conforming, {} not conforming, {} with fewer than {} fragments",
+        conforming, non_conforming, too_few, MIN_FRAGMENTS
+    );
+}
+
+// Wilson score interval of the probability of `successes` in `trials`
+fn wilson_interval(successes: u64, trials: u64) -> (f64, f64) {
+    let n = trials as f64;
+    let p = successes as f64 / n;
+    let z2 = Z * Z;
+    let center = (p + z2 / (2. * n)) / (1. + z2 / n);
+    let half_width = Z / (1. + z2 / n) * (p * (1. - p) / n + z2 / (4. * n * n)).sqrt();
+    ((center - half_width).max(0.), (center + half_width).min(1.))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wilson_interval_is_clamped_when_nothing_is_dropped() {
+        let (low, high) = wilson_interval(0, 100);
+        assert_eq!(low, 0.);
+        assert!(high > 0. && high < 0.1);
+    }
+
+    #[test]
+    fn wilson_interval_reaches_one_when_everything_is_dropped() {
+        let (low, high) = wilson_interval(100, 100);
+        assert!(low > 0.9 && low < 1.);
+        assert!((high - 1.).abs() < 1e-12);
+    }
+
+    #[test]
+    fn wilson_interval_contains_the_observed_rate() {
+        for (successes, trials) in [(1, 30), (15, 30), (29, 30), (300, 1000)] {
+            let (low, high) = wilson_interval(successes, trials);
+            let observed = successes as f64 / trials as f64;
+            assert!(low < observed && observed < high, "{} of {}: {}-{}", successes, trials, low, high);
+        }
+    }
+
+    #[test]
+    fn wilson_interval_narrows_with_more_fragments() {
+        let (low, high) = wilson_interval(30, 100);
+        let (more_low, more_high) = wilson_interval(300, 1000);
+        assert!(more_high - more_low < high - low);
+    }
+
+    #[test]
+    fn commanded_pdr_is_reported() {
+        let (monitor, events) = ConformanceMonitor::new();
+        monitor.on_drone_command(1, &DroneCommand::SetPacketDropRate(0.5));
+        monitor.on_drone_command(1, &DroneCommand::Crash);
+        assert!(matches!(events.try_recv(), Ok((_, DropEvent::PdrSet(1, pdr))) if pdr == 0.5));
+        assert!(events.try_recv().is_err());
+    }
+
+    fn counts(tally: &Tally) -> Vec<(NodeId, f32, u64, u64)> {
+        tally.trials.iter().map(|((id, pdr), trials)| (*id, f32::from_bits(*pdr), trials.forwarded, trials.dropped)).collect()
+    }
+
+    #[test]
+    fn fragments_around_a_pdr_change_are_not_counted() {
+        let mut tally = Tally { pdrs: HashMap::from([(1, 0.)]), ..Tally::default() };
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+
+        tally.handle(at(0), DropEvent::Forwarded(1, "drone"));
+        tally.handle(at(10), DropEvent::PdrSet(1, 0.5));
+        tally.handle(at(20), DropEvent::Forwarded(1, "drone"));
+        tally.handle(at(150), DropEvent::Forwarded(1, "drone"));
+        tally.handle(at(300), DropEvent::Dropped(1, "drone"));
+        tally.handle(at(310), DropEvent::Forwarded(1, "drone"));
+
+        assert_eq!(counts(&tally), [(1, 0., 1, 0), (1, 0.5, 1, 1)]);
+    }
+
+    #[test]
+    fn the_settle_window_is_per_drone() {
+        let mut tally = Tally { pdrs: HashMap::from([(1, 0.), (2, 0.)]), ..Tally::default() };
+        let start = Instant::now();
+
+        tally.handle(start, DropEvent::PdrSet(1, 0.5));
+        tally.handle(start + Duration::from_millis(10), DropEvent::Dropped(1, "drone"));
+        tally.handle(start + Duration::from_millis(10), DropEvent::Dropped(2, "drone"));
+
+        assert_eq!(counts(&tally), [(2, 0., 0, 1)]);
+    }
+
+    #[test]
+    fn events_are_timestamped_by_the_taps() {
+        let (monitor, events) = ConformanceMonitor::new();
+        let before = Instant::now();
+        monitor.on_drone_command(1, &DroneCommand::SetPacketDropRate(0.5));
+        let (time, _) = events.try_recv().unwrap();
+        assert!(before <= time && time <= Instant::now());
+    }
+}
@@ -0,0 +1,367 @@
+use common_utils::HostEvent;
+use crossbeam_channel::{unbounded, Receiver, RecvTimeoutError, Sender};
+use log::{info, warn};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use wg_2024::config::Config;
+use wg_2024::controller::DroneEvent;
+use wg_2024::network::NodeId;
+use wg_2024::packet::{Packet, PacketType};
+
+use crate::event_tap::EventObserver;
+use crate::link_layer::PacketObserver;
+use crate::network_control::NetworkControl;
+use crate::utils::topology;
+
+// How often the topology is checked for changes and the hosts for convergence
+const TICK: Duration = Duration::from_millis(100);
+// Flooding is considered finished when no flood packet is seen for this long
+const QUIET_PERIOD: Duration = Duration::from_millis(500);
+// After this long without converging, the hosts without a route are reported
+const CONVERGENCE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A flood packet seen by the link layer or in the controller events, with the time it was seen
+pub enum FloodEvent {
+    /// A request, or a response still on its way back
+    Traffic(Instant),
+    /// A response that reached the host that started the flood, with the ids of its path trace
+    Response(Instant, Vec<NodeId>),
+    /// A host that sent a flood request of its own
+    Flooded(Instant, NodeId),
+}
+
+/// Taps the links and the host events, and forwards the flood traffic to the thread started by `spawn`
+pub struct ConvergenceMonitor {
+    events: Sender<FloodEvent>,
+}
+
+impl ConvergenceMonitor {
+    /// Returns the observer to register on the link layer and on the event tap,
+    /// and the receiver of the flood events it sees
+    pub fn new() -> (Arc<Self>, Receiver<FloodEvent>) {
+        let (sender, receiver) = unbounded();
+        (Arc::new(Self { events: sender }), receiver)
+    }
+}
+
+impl PacketObserver for ConvergenceMonitor {
+    fn on_packet(&self, to: NodeId, packet: &Packet) {
+        let event = match &packet.pack_type {
+            PacketType::FloodResponse(response) if packet.routing_header.hops.last() == Some(&to) => {
+                FloodEvent::Response(Instant::now(), response.path_trace.iter().map(|(id, _)| *id).collect())
+            }
+            PacketType::FloodRequest(_) | PacketType::FloodResponse(_) => FloodEvent::Traffic(Instant::now()),
+            _ => return,
+        };
+        let _ = self.events.send(event);
+    }
+}
+
+impl EventObserver for ConvergenceMonitor {
+    // A response sent through the simulation controller never crosses a link
+    fn on_drone_event(&self, _drone_id: NodeId, _drone_type: &'static str, event: &DroneEvent) {
+        if let DroneEvent::ControllerShortcut(packet) = event {
+            self.on_shortcut(packet);
+        }
+    }
+
+    fn on_host_event(&self, host_id: NodeId, event: &HostEvent) {
+        match event {
+            HostEvent::PacketSent(packet) if matches!(packet.pack_type, PacketType::FloodRequest(_)) => {
+                let _ = self.events.send(FloodEvent::Flooded(Instant::now(), host_id));
+            }
+            HostEvent::ControllerShortcut(packet) => self.on_shortcut(packet),
+            _ => {}
+        }
+    }
+}
+
+impl ConvergenceMonitor {
+    fn on_shortcut(&self, packet: &Packet) {
+        if let PacketType::FloodResponse(response) = &packet.pack_type {
+            let path_trace = response.path_trace.iter().map(|(id, _)| *id).collect();
+            let _ = self.events.send(FloodEvent::Response(Instant::now(), path_trace));
+        }
+    }
+}
+
+/// The discovery that follows the launch or a topology change
+struct Epoch {
+    cause: String,
+    start: Instant,
+    flood_packets: usize,
+    converged: bool,
+    timed_out: bool,
+}
+
+impl Epoch {
+    fn new(cause: String, start: Instant) -> Self {
+        Self { cause, start, flood_packets: 0, converged: false, timed_out: false }
+    }
+}
+
+/// Measures how long discovery takes to converge after the launch (`launched_at`) and after each
+/// change of the topology: the network has converged when flood packets have been seen since the
+/// change, none has been seen for `QUIET_PERIOD` and every host that can reach another one has sent
+/// a flood request since the change, as told by the host events, and has learned from the responses
+/// to its floods a route to every other host it can reach. The time is measured up to the last flood packet.
+/// Changes are seen by polling the topology, so a change is timed from the last poll that still
+/// saw the previous topology, up to `TICK` before it actually happened.
+pub fn spawn(events: Receiver<FloodEvent>, network: NetworkControl, launched_at: Instant) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        // The links each host learned in the current epoch, with the time they were last learned
+        let mut learned: HashMap<NodeId, HashMap<(NodeId, NodeId), Instant>> = HashMap::new();
+        // When each host last sent a flood request
+        let mut flooded: HashMap<NodeId, Instant> = HashMap::new();
+        let mut epoch = Epoch::new("launch".to_string(), launched_at);
+        let mut last_flood = launched_at;
+        let mut previous = network.topology();
+        let mut last_poll = launched_at;
+        let mut packets_since_poll = 0;
+
+        loop {
+            match events.recv_timeout(TICK) {
+                // Packets seen before the current epoch are about the previous topology
+                Ok(FloodEvent::Traffic(time) | FloodEvent::Response(time, _) | FloodEvent::Flooded(time, _))
+                    if time < epoch.start => {}
+                Ok(FloodEvent::Traffic(time)) => {
+                    packets_since_poll += 1;
+                    last_flood = last_flood.max(time);
+                }
+                Ok(FloodEvent::Response(time, path_trace)) => {
+                    packets_since_poll += 1;
+                    last_flood = last_flood.max(time);
+                    if let Some(host) = path_trace.first() {
+                        let links = learned.entry(*host).or_default();
+                        for pair in path_trace.windows(2) {
+                            links.insert((pair[0].min(pair[1]), pair[0].max(pair[1])), time);
+                        }
+                    }
+                }
+                // The request itself is counted by the link layer
+                Ok(FloodEvent::Flooded(time, host)) => {
+                    flooded.insert(host, time);
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+
+            let current = network.topology();
+            let changes = describe_changes(&previous, &current);
+            if !changes.is_empty() {
+                // The change happened after the last poll: what was learned before it is outdated,
+                // the flood packets seen since then may already answer it
+                epoch = Epoch::new(changes.join(", "), last_poll);
+                for links in learned.values_mut() {
+                    links.retain(|_, time| *time >= last_poll);
+                }
+                previous = current;
+            }
+            epoch.flood_packets += packets_since_poll;
+            packets_since_poll = 0;
+            last_poll = Instant::now();
+
+            if epoch.converged {
+                continue;
+            }
+            if epoch.flood_packets == 0 {
+                if !epoch.timed_out && epoch.start.elapsed() >= CONVERGENCE_TIMEOUT {
+                    epoch.timed_out = true;
+                    warn!("Convergence: no flood was seen within {}s after {}", CONVERGENCE_TIMEOUT.as_secs(), epoch.cause);
+                }
+                continue;
+            }
+            if last_flood.elapsed() < QUIET_PERIOD {
+                continue;
+            }
+
+            let not_flooded = hosts_not_flooded(&previous, &flooded, epoch.start);
+            let without_route = hosts_without_route(&previous, &learned);
+            if not_flooded.is_empty() && without_route.is_empty() {
+                epoch.converged = true;
+                let elapsed = last_flood.saturating_duration_since(epoch.start);
+                info!(
+                    "Convergence: the network converged {:.3}s after {} ({} flood packets)",
+                    elapsed.as_secs_f64(),
+                    epoch.cause,
+                    epoch.flood_packets
+                );
+            } else if !epoch.timed_out && epoch.start.elapsed() >= CONVERGENCE_TIMEOUT {
+                epoch.timed_out = true;
+                let mut reasons = Vec::new();
+                if !not_flooded.is_empty() {
+                    let ids: Vec<String> = not_flooded.iter().map(|id| id.to_string()).collect();
+                    reasons.push(format!("hosts that did not flood: {}", ids.join(", ")));
+                }
+                if !without_route.is_empty() {
+                    reasons.push(format!("hosts without a route: {}", without_route.join(", ")));
+                }
+                warn!(
+                    "Convergence: the network did not converge within {}s after {}, {}",
+                    CONVERGENCE_TIMEOUT.as_secs(),
+                    epoch.cause,
+                    reasons.join("; ")
+                );
+            }
+        }
+    })
+}
+
+// The changes of nodes and links, PDR changes do not need a new discovery
+fn describe_changes(previous: &Config, current: &Config) -> Vec<String> {
+    let (previous_nodes, current_nodes) = (topology::node_ids(previous), topology::node_ids(current));
+    let (previous_links, current_links) = (topology::links(previous), topology::links(current));
+
+    let mut changes = Vec::new();
+    changes.extend(previous_nodes.difference(&current_nodes).map(|id| format!("the crash of {}", id)));
+
+    // The links of crashed nodes are implied
+    let changed_nodes: BTreeSet<NodeId> = previous_nodes.difference(&current_nodes).copied().collect();
+    let unchanged_ends = |(a, b): &&(NodeId, NodeId)| !changed_nodes.contains(a) && !changed_nodes.contains(b);
+    for (a, b) in previous_links.difference(&current_links).filter(unchanged_ends) {
+        changes.push(format!("the removal of link {}-{}", a, b));
+    }
+    for (a, b) in current_links.difference(&previous_links).filter(unchanged_ends) {
+        changes.push(format!("the addition of link {}-{}", a, b));
+    }
+    changes
+}
+
+// The hosts that can reach another host but sent no flood request since `since`
+fn hosts_not_flooded(current: &Config, flooded: &HashMap<NodeId, Instant>, since: Instant) -> Vec<NodeId> {
+    let hosts: BTreeSet<NodeId> = topology::node_ids(current).into_iter().filter(|id| !topology::is_drone(current, *id)).collect();
+    hosts
+        .iter()
+        .copied()
+        .filter(|host| flooded.get(host).is_none_or(|time| *time < since))
+        .filter(|host| topology::hop_distances(current, *host).keys().any(|id| id != host && hosts.contains(id)))
+        .collect()
+}
+
+// Describes each pair of hosts that can reach each other but whose first one has not learned a route yet
+fn hosts_without_route(current: &Config, learned: &HashMap<NodeId, HashMap<(NodeId, NodeId), Instant>>) -> Vec<String> {
+    let hosts: Vec<NodeId> = topology::node_ids(current).into_iter().filter(|id| !topology::is_drone(current, *id)).collect();
+    let mut without_route = Vec::new();
+
+    for host in &hosts {
+        let reachable = topology::hop_distances(current, *host);
+        // Only the learned links that still exist make a route
+        let known: HashSet<(NodeId, NodeId)> =
+            learned.get(host).map(|learned| learned.keys().copied().collect()).unwrap_or_default();
+        let mut known_topology = current.clone();
+        for id in topology::node_ids(current) {
+            if let Some(neighbours) = topology::neighbours_mut(&mut known_topology, id) {
+                neighbours.retain(|neighbour| known.contains(&(id.min(*neighbour), id.max(*neighbour))));
+            }
+        }
+        let known_reachable = topology::hop_distances(&known_topology, *host);
+
+        for other in hosts.iter().filter(|other| *other != host && reachable.contains_key(other)) {
+            if !known_reachable.contains_key(other) {
+                without_route.push(format!("{}→{}", host, other));
+            }
+        }
+    }
+    without_route
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::topology::tests::config_with_links;
+    use wg_2024::network::SourceRoutingHeader;
+    use wg_2024::packet::{FloodRequest, FloodResponse, NodeType};
+
+    // 100 - 1 - 2 - 200, with client 101 linked to 2 and 3, and 3 linked to 1
+    fn network() -> Config {
+        config_with_links(
+            &[(1, 0.1), (2, 0.1), (3, 0.1)],
+            &[100, 101],
+            &[200],
+            &[(100, 1), (1, 2), (2, 200), (101, 2), (101, 3), (1, 3)],
+        )
+    }
+
+    fn learned(hosts: &[(NodeId, &[(NodeId, NodeId)])]) -> HashMap<NodeId, HashMap<(NodeId, NodeId), Instant>> {
+        let now = Instant::now();
+        hosts
+            .iter()
+            .map(|(host, links)| (*host, links.iter().map(|(a, b)| ((*a.min(b), *a.max(b)), now)).collect()))
+            .collect()
+    }
+
+    fn flood_packet(pack_type: PacketType) -> Packet {
+        Packet { routing_header: SourceRoutingHeader { hop_index: 1, hops: vec![2, 1, 100] }, session_id: 0, pack_type }
+    }
+
+    #[test]
+    fn describes_the_changes_of_nodes_and_links() {
+        let previous = network();
+
+        let mut current = previous.clone();
+        topology::remove_drone(&mut current, 3).unwrap();
+        assert_eq!(describe_changes(&previous, &current), ["the crash of 3"]);
+
+        let mut current = previous.clone();
+        topology::remove_link(&mut current, 1, 2).unwrap();
+        topology::add_link(&mut current, 101, 1).unwrap();
+        assert_eq!(describe_changes(&previous, &current), ["the removal of link 1-2", "the addition of link 1-101"]);
+
+        let mut current = previous.clone();
+        topology::set_pdr(&mut current, 1, 0.9).unwrap();
+        assert!(describe_changes(&previous, &current).is_empty());
+    }
+
+    #[test]
+    fn finds_the_hosts_without_a_route() {
+        let learned = learned(&[
+            (100, &[(100, 1), (1, 2), (2, 200)]),
+            (101, &[(101, 2), (1, 2), (1, 100), (2, 200)]),
+            // The only learned way to 100 passes through client 101
+            (200, &[(200, 2), (2, 101), (101, 3), (3, 1), (1, 100)]),
+        ]);
+        assert_eq!(hosts_without_route(&network(), &learned), ["100→101", "200→100"]);
+    }
+
+    #[test]
+    fn ignores_the_learned_links_that_went_away() {
+        let learned = learned(&[(100, &[(100, 1), (1, 2), (2, 200)]), (101, &[(101, 2), (1, 2), (1, 100), (2, 200)])]);
+        let mut current = network();
+        topology::remove_link(&mut current, 1, 2).unwrap();
+        // 100 and 200 cannot reach each other anymore, and 200 learned nothing
+        assert_eq!(hosts_without_route(&current, &learned), ["100→101", "101→100", "200→101"]);
+    }
+
+    #[test]
+    fn finds_the_hosts_that_did_not_flood() {
+        let config = config_with_links(
+            &[(1, 0.1), (2, 0.1)],
+            &[100, 101, 102],
+            &[200],
+            &[(100, 1), (1, 2), (2, 200), (101, 2)],
+        );
+        let before = Instant::now();
+        let since = before + Duration::from_millis(10);
+        let flooded = HashMap::from([(100, since + Duration::from_millis(10)), (101, before)]);
+        // 102 reaches no other host, so it does not need to flood
+        assert_eq!(hosts_not_flooded(&config, &flooded, since), [101, 200]);
+    }
+
+    #[test]
+    fn turns_the_host_events_into_flood_events() {
+        let (monitor, events) = ConvergenceMonitor::new();
+        let request = FloodRequest { flood_id: 1, initiator_id: 100, path_trace: vec![(100, NodeType::Client)] };
+        let response =
+            FloodResponse { flood_id: 1, path_trace: vec![(100, NodeType::Client), (1, NodeType::Drone), (2, NodeType::Drone)] };
+
+        monitor.on_host_event(100, &HostEvent::PacketSent(flood_packet(PacketType::FloodRequest(request))));
+        monitor.on_drone_event(2, "drone", &DroneEvent::ControllerShortcut(flood_packet(PacketType::FloodResponse(response))));
+        monitor.on_host_event(100, &HostEvent::HostMessageSent(200));
+
+        assert!(matches!(events.try_recv(), Ok(FloodEvent::Flooded(_, 100))));
+        assert!(matches!(events.try_recv(), Ok(FloodEvent::Response(_, path_trace)) if path_trace == [100, 1, 2]));
+        assert!(events.try_recv().is_err());
+    }
+}
@@ -0,0 +1,337 @@
+use crate::link_layer::{packet_destination, packet_source, packet_type_name, LinkLayer, PacketObserver};
+use log::{info, warn};
+use std::io::BufRead;
+use std::sync::{Arc, Condvar, Mutex};
+use std::{fmt, io, thread};
+use wg_2024::network::NodeId;
+use wg_2024::packet::Packet;
+
+/// A condition that freezes the network when a matching packet is about to be delivered
+#[derive(Debug, Clone, PartialEq)]
+pub enum Breakpoint {
+    PacketType(String),
+    Source(NodeId),
+    Destination(NodeId),
+    Session(u64),
+}
+
+impl Breakpoint {
+    fn matches(&self, packet: &Packet) -> bool {
+        match self {
+            Breakpoint::PacketType(name) => packet_type_name(&packet.pack_type).eq_ignore_ascii_case(name),
+            Breakpoint::Source(id) => packet_source(packet) == Some(*id),
+            Breakpoint::Destination(id) => packet_destination(packet) == Some(*id),
+            Breakpoint::Session(session_id) => packet.session_id == *session_id,
+        }
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Breakpoint::PacketType(name) => write!(f, "type {}", name),
+            Breakpoint::Source(id) => write!(f, "src {}", id),
+            Breakpoint::Destination(id) => write!(f, "dst {}", id),
+            Breakpoint::Session(session_id) => write!(f, "session {}", session_id),
+        }
+    }
+}
+
+struct DebuggerState {
+    paused: bool,
+    // Number of packets that can still be released while paused
+    steps: usize,
+    breakpoints: Vec<Breakpoint>,
+}
+
+/// Step-through packet debugger.
+/// It is registered as an observer of the link layer: while the network is paused
+/// every link thread blocks before delivering its packet, until a step releases it.
+pub struct PacketDebugger {
+    state: Mutex<DebuggerState>,
+    released: Condvar,
+}
+
+impl PacketDebugger {
+    pub fn new(start_paused: bool) -> Self {
+        Self {
+            state: Mutex::new(DebuggerState {
+                paused: start_paused,
+                steps: 0,
+                breakpoints: Vec::new(),
+            }),
+            released: Condvar::new(),
+        }
+    }
+
+    pub fn pause(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.paused = true;
+        state.steps = 0;
+    }
+
+    pub fn resume(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.paused = false;
+        state.steps = 0;
+        self.released.notify_all();
+    }
+
+    /// Releases `count` packets while the network is paused
+    pub fn step(&self, count: usize) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        if !state.paused {
+            return Err("The network is running, pause it before stepping".to_string());
+        }
+        state.steps += count;
+        self.released.notify_all();
+        Ok(())
+    }
+
+    pub fn add_breakpoint(&self, breakpoint: Breakpoint) {
+        self.state.lock().unwrap().breakpoints.push(breakpoint);
+    }
+
+    pub fn remove_breakpoint(&self, index: usize) -> Option<Breakpoint> {
+        let mut state = self.state.lock().unwrap();
+        if index < state.breakpoints.len() {
+            Some(state.breakpoints.remove(index))
+        } else {
+            None
+        }
+    }
+
+    pub fn breakpoints(&self) -> Vec<Breakpoint> {
+        self.state.lock().unwrap().breakpoints.clone()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.lock().unwrap().paused
+    }
+}
+
+impl PacketObserver for PacketDebugger {
+    fn on_packet(&self, to: NodeId, packet: &Packet) {
+        let mut state = self.state.lock().unwrap();
+
+        if !state.paused {
+            if let Some(breakpoint) = state.breakpoints.iter().find(|b| b.matches(packet)) {
+                println!("[debugger] Breakpoint ({}) hit, network paused", breakpoint);
+                state.paused = true;
+                state.steps = 0;
+            }
+        }
+
+        if !state.paused {
+            return;
+        }
+
+        println!("[debugger] Holding {} -> {}", describe_packet(packet), to);
+        while state.paused && state.steps == 0 {
+            state = self.released.wait(state).unwrap();
+        }
+
+        if state.paused {
+            state.steps -= 1;
+            println!("[debugger] Released {} -> {}", describe_packet(packet), to);
+        }
+    }
+}
+
+fn describe_packet(packet: &Packet) -> String {
+    let endpoint = |id: Option<NodeId>| id.map_or("-".to_string(), |id| id.to_string());
+    format!(
+        "{} (session {}, {} => {}, hops {:?})",
+        packet_type_name(&packet.pack_type),
+        packet.session_id,
+        endpoint(packet_source(packet)),
+        endpoint(packet_destination(packet)),
+        packet.routing_header.hops
+    )
+}
+
+/// Spawns the thread that reads debugger commands from stdin
+pub fn spawn_console(debugger: Arc<PacketDebugger>, link_layer: LinkLayer) {
+    thread::spawn(move || {
+        print_help();
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            let words: Vec<&str> = line.split_whitespace().collect();
+
+            match words.as_slice() {
+                [] => {}
+                ["pause" | "p"] => {
+                    debugger.pause();
+                    println!("[debugger] Network paused");
+                }
+                ["continue" | "c"] => {
+                    debugger.resume();
+                    println!("[debugger] Network resumed");
+                }
+                ["step" | "s"] => {
+                    if let Err(error_message) = debugger.step(1) {
+                        println!("[debugger] {}", error_message);
+                    }
+                }
+                ["step" | "s", count] => match count.parse::<usize>() {
+                    Ok(count) => {
+                        if let Err(error_message) = debugger.step(count) {
+                            println!("[debugger] {}", error_message);
+                        }
+                    }
+                    Err(_) => println!("[debugger] Invalid step count \"{}\"", count),
+                },
+                ["break" | "b", kind, value] => match parse_breakpoint(kind, value) {
+                    Ok(breakpoint) => {
+                        println!("[debugger] Breakpoint {} set", breakpoint);
+                        debugger.add_breakpoint(breakpoint);
+                    }
+                    Err(error_message) => println!("[debugger] {}", error_message),
+                },
+                ["breakpoints" | "bl"] => {
+                    for (index, breakpoint) in debugger.breakpoints().iter().enumerate() {
+                        println!("[debugger] #{}: {}", index, breakpoint);
+                    }
+                }
+                ["delete" | "d", index] => match index.parse::<usize>().ok().and_then(|i| debugger.remove_breakpoint(i)) {
+                    Some(breakpoint) => println!("[debugger] Breakpoint {} removed", breakpoint),
+                    None => println!("[debugger] No breakpoint #{}", index),
+                },
+                ["queues" | "q"] => {
+                    println!("[debugger] Network {}", if debugger.is_paused() { "paused" } else { "running" });
+                    for (id, on_link, in_inbox) in link_layer.queue_depths() {
+                        println!("[debugger] Node {:>3}: {} held on link, {} in inbox", id, on_link, in_inbox);
+                    }
+                }
+                ["help" | "h"] => print_help(),
+                _ => println!("[debugger] Unknown command \"{}\", type \"help\"", line.trim()),
+            }
+        }
+        warn!("Debugger console closed, resuming the network");
+        debugger.resume();
+    });
+    info!("Packet debugger console started");
+}
+
+fn parse_breakpoint(kind: &str, value: &str) -> Result<Breakpoint, String> {
+    match kind {
+        "type" => {
+            const TYPES: [&str; 5] = ["MsgFragment", "Ack", "Nack", "FloodRequest", "FloodResponse"];
+            match TYPES.iter().find(|t| t.eq_ignore_ascii_case(value)) {
+                Some(name) => Ok(Breakpoint::PacketType(name.to_string())),
+                None => Err(format!("Unknown packet type \"{}\"! Valid types: {}", value, TYPES.join(", "))),
+            }
+        }
+        "src" => value.parse().map(Breakpoint::Source).map_err(|_| format!("Invalid node id \"{}\"", value)),
+        "dst" => value.parse().map(Breakpoint::Destination).map_err(|_| format!("Invalid node id \"{}\"", value)),
+        "session" => value.parse().map(Breakpoint::Session).map_err(|_| format!("Invalid session id \"{}\"", value)),
+        _ => Err(format!("Unknown breakpoint kind \"{}\"! Valid kinds: type, src, dst, session", kind)),
+    }
+}
+
+fn print_help() {
+    println!("[debugger] Commands:");
+    println!("  pause | p                       pause the delivery of all packets");
+    println!("  continue | c                    resume the delivery of all packets");
+    println!("  step | s [n]                    release n packets (default 1) while paused");
+    println!("  break | b <kind> <value>        kind: type (FloodRequest, Nack, ...), src, dst, session");
+    println!("  breakpoints | bl                list the breakpoints");
+    println!("  delete | d <index>              remove a breakpoint");
+    println!("  queues | q                      show the packets waiting for each node");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wg_2024::network::SourceRoutingHeader;
+    use wg_2024::packet::{Ack, PacketType};
+
+    fn ack(hops: Vec<NodeId>, session_id: u64) -> Packet {
+        Packet {
+            routing_header: SourceRoutingHeader { hop_index: 1, hops },
+            session_id,
+            pack_type: PacketType::Ack(Ack { fragment_index: 0 }),
+        }
+    }
+
+    #[test]
+    fn breakpoints_are_parsed() {
+        assert_eq!(parse_breakpoint("type", "nack"), Ok(Breakpoint::PacketType("Nack".to_string())));
+        assert_eq!(parse_breakpoint("src", "3"), Ok(Breakpoint::Source(3)));
+        assert_eq!(parse_breakpoint("dst", "101"), Ok(Breakpoint::Destination(101)));
+        assert_eq!(parse_breakpoint("session", "42"), Ok(Breakpoint::Session(42)));
+    }
+
+    #[test]
+    fn bad_breakpoints_are_rejected() {
+        assert!(parse_breakpoint("type", "Fragment").unwrap_err().contains("Unknown packet type \"Fragment\""));
+        assert_eq!(parse_breakpoint("src", "300"), Err("Invalid node id \"300\"".to_string()));
+        assert_eq!(parse_breakpoint("dst", "-1"), Err("Invalid node id \"-1\"".to_string()));
+        assert_eq!(parse_breakpoint("session", "abc"), Err("Invalid session id \"abc\"".to_string()));
+        assert!(parse_breakpoint("node", "1").unwrap_err().contains("Unknown breakpoint kind \"node\""));
+    }
+
+    #[test]
+    fn breakpoints_match_nodes_and_packet_types() {
+        let packet = ack(vec![101, 1, 2, 201], 7);
+
+        assert!(Breakpoint::PacketType("ack".to_string()).matches(&packet));
+        assert!(!Breakpoint::PacketType("Nack".to_string()).matches(&packet));
+        assert!(Breakpoint::Source(101).matches(&packet));
+        assert!(!Breakpoint::Source(1).matches(&packet));
+        assert!(Breakpoint::Destination(201).matches(&packet));
+        assert!(!Breakpoint::Destination(2).matches(&packet));
+        assert!(Breakpoint::Session(7).matches(&packet));
+        assert!(!Breakpoint::Session(8).matches(&packet));
+    }
+
+    #[test]
+    fn stepping_needs_a_paused_network() {
+        let debugger = PacketDebugger::new(false);
+        assert!(debugger.step(1).is_err());
+
+        debugger.pause();
+        assert!(debugger.is_paused());
+        assert_eq!(debugger.step(2), Ok(()));
+        assert_eq!(debugger.state.lock().unwrap().steps, 2);
+    }
+
+    #[test]
+    fn steps_release_packets_one_at_a_time() {
+        let debugger = PacketDebugger::new(true);
+        debugger.step(2).unwrap();
+
+        debugger.on_packet(1, &ack(vec![101, 1], 0));
+        assert_eq!(debugger.state.lock().unwrap().steps, 1);
+        debugger.on_packet(1, &ack(vec![101, 1], 1));
+        assert_eq!(debugger.state.lock().unwrap().steps, 0);
+        assert!(debugger.is_paused());
+    }
+
+    #[test]
+    fn pausing_and_resuming_forget_pending_steps() {
+        let debugger = PacketDebugger::new(true);
+        debugger.step(3).unwrap();
+        debugger.pause();
+        assert_eq!(debugger.state.lock().unwrap().steps, 0);
+
+        debugger.step(3).unwrap();
+        debugger.resume();
+        assert!(!debugger.is_paused());
+        assert_eq!(debugger.state.lock().unwrap().steps, 0);
+
+        // A running network does not hold packets
+        debugger.on_packet(1, &ack(vec![101, 1], 0));
+    }
+
+    #[test]
+    fn breakpoints_can_be_removed() {
+        let debugger = PacketDebugger::new(false);
+        debugger.add_breakpoint(Breakpoint::Source(1));
+        debugger.add_breakpoint(Breakpoint::Session(2));
+
+        assert_eq!(debugger.remove_breakpoint(0), Some(Breakpoint::Source(1)));
+        assert_eq!(debugger.remove_breakpoint(1), None);
+        assert_eq!(debugger.breakpoints(), vec![Breakpoint::Session(2)]);
+    }
+}
@@ -0,0 +1,226 @@
+use crossbeam_channel::{unbounded, Receiver, RecvTimeoutError, Sender};
+use log::{info, warn};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use wg_2024::config::Config;
+use wg_2024::network::NodeId;
+use wg_2024::packet::{FloodResponse, NodeType, Packet, PacketType};
+
+use crate::link_layer::PacketObserver;
+use crate::network_control::NetworkControl;
+use crate::utils::topology;
+
+// How often the learned topologies are compared with the current one
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Taps the links and collects the `FloodResponse`s when they reach the host that started the flood.
+/// The responses are turned into learned topologies on another thread by `spawn`.
+pub struct DiscoveryAuditor {
+    responses: Sender<(NodeId, FloodResponse)>,
+}
+
+impl DiscoveryAuditor {
+    /// Returns the observer to register on the link layer and the receiver of the responses it sees
+    pub fn new() -> (Arc<Self>, Receiver<(NodeId, FloodResponse)>) {
+        let (sender, receiver) = unbounded();
+        (Arc::new(Self { responses: sender }), receiver)
+    }
+}
+
+impl PacketObserver for DiscoveryAuditor {
+    fn on_packet(&self, to: NodeId, packet: &Packet) {
+        if let PacketType::FloodResponse(response) = &packet.pack_type {
+            if packet.routing_header.hops.last() == Some(&to) {
+                let _ = self.responses.send((to, response.clone()));
+            }
+        }
+    }
+}
+
+/// The graph a host could have learned from the responses to its last flood.
+/// Each entry remembers whether it matched the topology when it was learned.
+#[derive(Debug, Default)]
+struct LearnedTopology {
+    flood_id: u64,
+    nodes: BTreeMap<NodeId, bool>,
+    links: BTreeMap<(NodeId, NodeId), bool>,
+}
+
+impl LearnedTopology {
+    /// Merges the path trace of a response to the flood `flood_id`: a newer flood starts the discovery
+    /// from scratch, while the late responses to an older flood are ignored
+    fn learn(&mut self, current: &Config, flood_id: u64, path_trace: &[(NodeId, NodeType)]) {
+        if flood_id < self.flood_id {
+            return;
+        }
+        if flood_id > self.flood_id {
+            *self = LearnedTopology { flood_id, ..Default::default() };
+        }
+        for (id, node_type) in path_trace {
+            let real = topology::node_type_of(current, *id) == Some(*node_type);
+            self.nodes.entry(*id).or_insert(real);
+        }
+        for pair in path_trace.windows(2) {
+            let (a, b) = (pair[0].0, pair[1].0);
+            let real = topology::linked(current, a, b);
+            self.links.entry((a.min(b), a.max(b))).or_insert(real);
+        }
+    }
+
+    // Compares the learned graph with the current topology, as seen from `host`
+    fn diff(&self, current: &Config, host: NodeId) -> Vec<String> {
+        let reachable = topology::hop_distances(current, host);
+        let missing_nodes = reachable.keys().copied().filter(|id| *id != host && !self.nodes.contains_key(id));
+        let phantom_nodes = self.nodes.iter().filter(|(_, real)| !**real).map(|(id, _)| *id);
+        let phantom_links = self.links.iter().filter(|(_, real)| !**real).map(|(link, _)| *link);
+        let stale_nodes = self
+            .nodes
+            .iter()
+            .filter(|(id, real)| **real && topology::neighbours(current, **id).is_none())
+            .map(|(id, _)| *id);
+        let stale_links = self
+            .links
+            .iter()
+            .filter(|((a, b), real)| **real && !topology::linked(current, *a, *b))
+            .map(|(link, _)| *link);
+
+        let mut differences = Vec::new();
+        push_nodes(&mut differences, "missing nodes", missing_nodes.collect());
+        push_nodes(&mut differences, "phantom nodes", phantom_nodes.collect());
+        push_links(&mut differences, "phantom links", phantom_links.collect());
+        push_nodes(&mut differences, "stale nodes", stale_nodes.collect());
+        push_links(&mut differences, "stale links", stale_links.collect());
+        differences
+    }
+}
+
+/// Rebuilds the topology each host learned from its last flood and compares it with the current one,
+/// reporting the reachable nodes it does not know (missing), the nodes and links that did not exist
+/// when they were learned (phantom) and the ones that existed but went away since (stale).
+/// A host is reported again only when its differences change.
+pub fn spawn(responses: Receiver<(NodeId, FloodResponse)>, network: NetworkControl) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let mut learned: BTreeMap<NodeId, LearnedTopology> = BTreeMap::new();
+        let mut reported: BTreeMap<NodeId, Vec<String>> = BTreeMap::new();
+        let mut next_check = Instant::now() + CHECK_INTERVAL;
+
+        loop {
+            match responses.recv_timeout(next_check.saturating_duration_since(Instant::now())) {
+                Ok((host, response)) => {
+                    let host_topology = learned.entry(host).or_default();
+                    host_topology.learn(&network.topology(), response.flood_id, &response.path_trace);
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    let current = network.topology();
+                    for (host, host_topology) in &learned {
+                        let differences = host_topology.diff(&current, *host);
+                        if reported.get(host) == Some(&differences) {
+                            continue;
+                        }
+
+                        let name = format!("{} {} (flood {})", topology::host_kind(&current, *host), host, host_topology.flood_id);
+                        if differences.is_empty() {
+                            info!(
+                                "Discovery audit: {} learned the topology correctly: {} nodes, {} links",
+                                name,
+                                host_topology.nodes.len(),
+                                host_topology.links.len()
+                            );
+                        } else {
+                            warn!("Discovery audit: {}: {}", name, differences.join("; "));
+                        }
+                        reported.insert(*host, differences);
+                    }
+                    next_check = Instant::now() + CHECK_INTERVAL;
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+    })
+}
+
+fn push_nodes(differences: &mut Vec<String>, label: &str, ids: BTreeSet<NodeId>) {
+    if !ids.is_empty() {
+        let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+        differences.push(format!("{} {}", label, ids.join(", ")));
+    }
+}
+
+fn push_links(differences: &mut Vec<String>, label: &str, links: Vec<(NodeId, NodeId)>) {
+    if !links.is_empty() {
+        let links: Vec<String> = links.iter().map(|(a, b)| format!("{}-{}", a, b)).collect();
+        differences.push(format!("{} {}", label, links.join(", ")));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::topology::tests::config_with_links;
+
+    // 100 - 1 - 2 - 200, with drone 3 linked to 1 and 2
+    fn triangle() -> Config {
+        config_with_links(
+            &[(1, 0.1), (2, 0.1), (3, 0.1)],
+            &[100],
+            &[200],
+            &[(100, 1), (1, 2), (2, 200), (1, 3), (2, 3)],
+        )
+    }
+
+    fn trace(ids: &[NodeId]) -> Vec<(NodeId, NodeType)> {
+        ids.iter()
+            .map(|id| match id {
+                100 => (*id, NodeType::Client),
+                200 => (*id, NodeType::Server),
+                _ => (*id, NodeType::Drone),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reports_what_a_partial_discovery_misses() {
+        let config = triangle();
+        let mut learned = LearnedTopology::default();
+        learned.learn(&config, 1, &trace(&[100, 1, 2, 200]));
+        assert_eq!(learned.diff(&config, 100), ["missing nodes 3"]);
+
+        learned.learn(&config, 1, &trace(&[100, 1, 3]));
+        assert!(learned.diff(&config, 100).is_empty());
+    }
+
+    #[test]
+    fn reports_phantom_and_stale_entries() {
+        let mut config = triangle();
+        let mut learned = LearnedTopology::default();
+        learned.learn(&config, 1, &trace(&[100, 1, 3, 2, 200]));
+        learned.learn(&config, 1, &trace(&[100, 1, 2, 200]));
+        learned.learn(&config, 1, &trace(&[100, 3, 200]));
+        assert_eq!(learned.diff(&config, 100), ["phantom links 3-100, 3-200"]);
+
+        topology::remove_drone(&mut config, 3).unwrap();
+        assert_eq!(learned.diff(&config, 100), ["phantom links 3-100, 3-200", "stale nodes 3", "stale links 1-3, 2-3"]);
+    }
+
+    #[test]
+    fn ignores_the_late_responses_to_an_older_flood() {
+        let mut config = triangle();
+        let mut learned = LearnedTopology::default();
+        learned.learn(&config, 1, &trace(&[100, 1, 3, 2, 200]));
+
+        topology::remove_drone(&mut config, 3).unwrap();
+        learned.learn(&config, 2, &trace(&[100, 1, 2, 200]));
+        // A response to flood 1 arriving after the ones of flood 2 must not bring drone 3 back
+        learned.learn(&config, 1, &trace(&[100, 1, 3]));
+        assert_eq!(learned.flood_id, 2);
+        assert!(learned.diff(&config, 100).is_empty());
+
+        // Out of order responses to the same flood are all merged
+        learned.learn(&config, 4, &trace(&[100, 1]));
+        learned.learn(&config, 3, &trace(&[100, 1, 2, 200]));
+        learned.learn(&config, 4, &trace(&[100, 1, 2]));
+        assert_eq!((learned.flood_id, learned.diff(&config, 100)), (4, vec!["missing nodes 200".to_string()]));
+    }
+}
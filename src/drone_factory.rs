@@ -25,10 +25,7 @@ impl<T: Drone + Send + 'static> DroneRunnable for T {
 
     /// Returns the type name of the drone implementation.
     fn drone_type(&self) -> &'static str {
-        std::any::type_name::<T>()
-            .split("::")
-            .last()
-            .unwrap_or("Unknown")
+        drone_type_name::<T>()
     }
 
     /// Returns a reference to the `Any` trait object for this drone.
@@ -42,6 +39,14 @@ impl<T: Drone + Send + 'static> DroneRunnable for T {
     }
 }
 
+/// Returns the type name of a drone implementation, without its path.
+pub fn drone_type_name<T>() -> &'static str {
+    std::any::type_name::<T>()
+        .split("::")
+        .last()
+        .unwrap_or("Unknown")
+}
+
 /// Type alias for a factory function that produces a `Box<dyn DroneRunnable + Send>`.
 pub type DroneFactory = Box<
     dyn Fn(
@@ -81,3 +86,16 @@ macro_rules! drone_factories {
         ]
     }};
 }
+
+/// Macro that produces a vector with the type names of the given drone implementations,
+/// in the same order as `drone_factories!`.
+#[macro_export]
+macro_rules! drone_type_names {
+    ($($type_name:ty),* $(,)?) => {{
+        vec![
+            $(
+                $crate::drone_factory::drone_type_name::<$type_name>()
+            ),*
+        ]
+    }};
+}
@@ -0,0 +1,102 @@
+use common_utils::HostEvent;
+use crossbeam_channel::{unbounded, Receiver};
+use log::debug;
+use std::sync::Arc;
+use std::thread;
+use wg_2024::controller::{DroneCommand, DroneEvent};
+use wg_2024::network::NodeId;
+
+/// Something that wants to look at the events the nodes send to the simulation controller,
+/// and at the commands the drones receive.
+/// The callbacks are called on the tap thread of the node, before the event or command is forwarded.
+pub trait EventObserver: Send + Sync {
+    fn on_drone_event(&self, _drone_id: NodeId, _drone_type: &'static str, _event: &DroneEvent) {}
+    fn on_drone_command(&self, _drone_id: NodeId, _command: &DroneCommand) {}
+    fn on_host_event(&self, _host_id: NodeId, _event: &HostEvent) {}
+}
+
+/// Fans out the controller events: every event channel can be tapped by a thread that shows
+/// each event to the observers and then forwards it, so the simulation controller still gets all of them.
+/// The command channels of the drones are tapped the same way, on their way to the drones.
+#[derive(Clone, Default)]
+pub struct EventTap {
+    observers: Vec<Arc<dyn EventObserver>>,
+}
+
+impl EventTap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers an observer. Only channels tapped after this call will notify it.
+    pub fn add_observer(&mut self, observer: Arc<dyn EventObserver>) {
+        self.observers.push(observer);
+    }
+
+    /// Returns the receiver the simulation controller must listen on instead of `events`
+    pub fn tap_drone(
+        &self,
+        drone_id: NodeId,
+        drone_type: &'static str,
+        events: Receiver<DroneEvent>,
+    ) -> Receiver<DroneEvent> {
+        let observers = self.observers.clone();
+        self.tap(drone_id, events, move |event| {
+            for observer in &observers {
+                observer.on_drone_event(drone_id, drone_type, event);
+            }
+        })
+    }
+
+    /// Returns the receiver the drone must listen on instead of `commands`, so the commands sent
+    /// by the simulation controller and by the initializer are both seen
+    pub fn tap_drone_commands(&self, drone_id: NodeId, commands: Receiver<DroneCommand>) -> Receiver<DroneCommand> {
+        let observers = self.observers.clone();
+        self.tap(drone_id, commands, move |command| {
+            for observer in &observers {
+                observer.on_drone_command(drone_id, command);
+            }
+        })
+    }
+
+    /// Returns the receiver the simulation controller must listen on instead of `events`
+    pub fn tap_host(&self, host_id: NodeId, events: Receiver<HostEvent>) -> Receiver<HostEvent> {
+        let observers = self.observers.clone();
+        self.tap(host_id, events, move |event| {
+            for observer in &observers {
+                observer.on_host_event(host_id, event);
+            }
+        })
+    }
+
+    // Without observers the channel is left as it is
+    fn tap<E: Send + 'static>(
+        &self,
+        node_id: NodeId,
+        events: Receiver<E>,
+        notify: impl Fn(&E) + Send + 'static,
+    ) -> Receiver<E> {
+        if self.observers.is_empty() {
+            return events;
+        }
+
+        let (sender, receiver) = unbounded();
+        thread::spawn(move || {
+            for event in events.iter() {
+                notify(&event);
+                if sender.send(event).is_err() {
+                    break;
+                }
+            }
+            debug!("Event tap of node {} terminated", node_id);
+        });
+        receiver
+    }
+}
+
+/// Returns the name of the variant of a host event (e.g. "HostMessageSent").
+/// The host events are defined by the hosts, so the name is taken from their `Debug` output.
+pub fn host_event_name(event: &HostEvent) -> String {
+    let debug = format!("{:?}", event);
+    debug.split(|c: char| !c.is_alphanumeric() && c != '_').next().unwrap_or_default().to_string()
+}
@@ -0,0 +1,206 @@
+pub mod pdr;
+pub mod random;
+pub mod shapes;
+
+use std::fs;
+use std::path::Path;
+use wg_2024::config::{Client, Config, Drone, Server};
+use wg_2024::network::NodeId;
+
+use crate::utils::{config_writer, input_validator};
+pub use pdr::PdrDistribution;
+pub use random::RandomOptions;
+pub use shapes::{Shape, ShapeLayout};
+
+/// Size of the generated topology and how its hosts are attached
+#[derive(Debug, Clone)]
+pub struct GeneratorOptions {
+    pub drones: usize,
+    /// Drones in each row of a grid
+    pub width: Option<usize>,
+    pub clients: usize,
+    pub servers: usize,
+    /// Drones each client is connected to (1 or 2)
+    pub client_links: usize,
+    /// Drones each server is connected to (at least 2)
+    pub server_links: usize,
+    pub pdr: f32,
+}
+
+/// Generates a topology with the given shape, validates it and writes it as TOML
+pub fn generate(shape: Shape, options: &GeneratorOptions, output: Option<&str>) -> Result<(), String> {
+    if options.drones > NodeId::MAX as usize {
+        return Err(format!("Too many drones, at most {} are allowed", NodeId::MAX));
+    }
+    let layout = shape.layout(options.drones, options.width)?;
+    let config = build(&layout, options)?;
+    check(&config)?;
+
+    write(&config_writer::to_toml(&config), output)
+}
+
+/// Generates `count` random topologies, with consecutive seeds starting from `options.seed`.
+/// With more than one topology, each file is named after its seed, e.g. "net.toml" becomes "net-42.toml".
+pub fn generate_random(options: &RandomOptions, count: u64, output: Option<&str>) -> Result<(), String> {
+    if count > 1 && output.is_none() {
+        return Err("Multiple random topologies need an --output path".to_string());
+    }
+
+    for seed in options.seed..options.seed.saturating_add(count) {
+        let options = RandomOptions { seed, ..options.clone() };
+        let config = random::random_topology(&options)?;
+        check(&config)?;
+
+        let toml = format!("# Random topology, seed {}\n\n{}", seed, config_writer::to_toml(&config));
+        let path = match output {
+            Some(path) if count > 1 => Some(seeded_path(path, seed)),
+            _ => output.map(str::to_string),
+        };
+        write(&toml, path.as_deref())?;
+    }
+    Ok(())
+}
+
+// The generators are valid by construction, this only guards against bugs in them
+fn check(config: &Config) -> Result<(), String> {
+    input_validator::validate_config(config)
+        .and_then(|_| input_validator::check_reachability(config))
+        .map_err(|error_message| format!("The generated topology is not valid: {}", error_message))
+}
+
+fn write(toml: &str, output: Option<&str>) -> Result<(), String> {
+    match output {
+        Some(path) => fs::write(path, toml).map_err(|e| format!("Unable to write \"{}\": {}", path, e)),
+        None => {
+            print!("{}", toml);
+            Ok(())
+        }
+    }
+}
+
+fn seeded_path(path: &str, seed: u64) -> String {
+    let path = Path::new(path);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("topology");
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("toml");
+    path.with_file_name(format!("{}-{}.{}", stem, seed, extension)).to_string_lossy().into_owned()
+}
+
+/// Turns a layout into a `Config`: drones get the ids 1..=drones and the same PDR,
+/// clients and servers are attached to consecutive drones of their side of the layout.
+pub fn build(layout: &ShapeLayout, options: &GeneratorOptions) -> Result<Config, String> {
+    if !(1..=2).contains(&options.client_links) {
+        return Err("Clients must be connected to 1 or 2 drones".to_string());
+    }
+    if options.server_links < 2 {
+        return Err("Servers must be connected to at least 2 drones".to_string());
+    }
+    if options.clients > 0 && layout.client_side.len() < options.client_links {
+        return Err(format!("Not enough drones to connect each client to {} of them", options.client_links));
+    }
+    if options.servers > 0 && layout.server_side.len() < options.server_links {
+        return Err(format!("Not enough drones to connect each server to {} of them", options.server_links));
+    }
+
+    let (client_ids, server_ids) = host_ids(options.drones, options.clients, options.servers)?;
+
+    let mut config = Config {
+        drone: (1..=options.drones as NodeId)
+            .map(|id| Drone { id, connected_node_ids: Vec::new(), pdr: options.pdr })
+            .collect(),
+        client: Vec::new(),
+        server: Vec::new(),
+    };
+    for (a, b) in &layout.links {
+        connect_drone(&mut config, *a, *b);
+        connect_drone(&mut config, *b, *a);
+    }
+
+    for (i, id) in client_ids.into_iter().enumerate() {
+        let drones = attachment(&layout.client_side, i, options.client_links);
+        for drone in &drones {
+            connect_drone(&mut config, *drone, id);
+        }
+        config.client.push(Client { id, connected_drone_ids: drones });
+    }
+    for (i, id) in server_ids.into_iter().enumerate() {
+        let drones = attachment(&layout.server_side, i, options.server_links);
+        for drone in &drones {
+            connect_drone(&mut config, *drone, id);
+        }
+        config.server.push(Server { id, connected_drone_ids: drones });
+    }
+
+    Ok(config)
+}
+
+// The i-th host takes the next `links` drones of its side, wrapping around
+fn attachment(side: &[NodeId], i: usize, links: usize) -> Vec<NodeId> {
+    (0..links).map(|j| side[(i * links + j) % side.len()]).collect()
+}
+
+pub(super) fn connect_drone(config: &mut Config, drone: NodeId, neighbour: NodeId) {
+    if let Some(drone) = config.drone.iter_mut().find(|d| d.id == drone) {
+        if !drone.connected_node_ids.contains(&neighbour) {
+            drone.connected_node_ids.push(neighbour);
+        }
+    }
+}
+
+// Clients start from 100 and servers from 200, like in input.toml,
+// unless there are too many nodes: then the ids simply follow the drones
+pub(super) fn host_ids(drones: usize, clients: usize, servers: usize) -> Result<(Vec<NodeId>, Vec<NodeId>), String> {
+    let max_id = NodeId::MAX as usize;
+    let (client_start, server_start) = if drones < 100 && clients <= 100 && 200 + servers <= max_id + 1 {
+        (100, 200)
+    } else {
+        (drones + 1, drones + 1 + clients)
+    };
+    if server_start + servers > max_id + 1 {
+        return Err(format!("Too many nodes, at most {} ids are available", max_id));
+    }
+
+    let client_ids = (client_start..client_start + clients).map(|id| id as NodeId).collect();
+    let server_ids = (server_start..server_start + servers).map(|id| id as NodeId).collect();
+    Ok((client_ids, server_ids))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_shape_builds_a_valid_topology() {
+        let options = GeneratorOptions {
+            drones: 12,
+            width: Some(4),
+            clients: 3,
+            servers: 2,
+            client_links: 2,
+            server_links: 2,
+            pdr: 0.05,
+        };
+        let shapes = [
+            Shape::Chain,
+            Shape::DoubleChain,
+            Shape::Ring,
+            Shape::Star,
+            Shape::Grid,
+            Shape::Tree,
+            Shape::Butterfly,
+            Shape::Full,
+        ];
+
+        for shape in shapes {
+            let config = build(&shape.layout(options.drones, options.width).unwrap(), &options).unwrap();
+            assert!(check(&config).is_ok(), "{:?}: {:?}", shape, check(&config));
+            assert_eq!(config.client.iter().map(|c| c.id).collect::<Vec<_>>(), [100, 101, 102]);
+            assert_eq!(config.server.iter().map(|s| s.id).collect::<Vec<_>>(), [200, 201]);
+        }
+    }
+
+    #[test]
+    fn hosts_follow_the_drones_when_ids_run_out() {
+        assert_eq!(host_ids(150, 2, 1), Ok((vec![151, 152], vec![153])));
+        assert!(host_ids(250, 5, 5).is_err());
+    }
+}
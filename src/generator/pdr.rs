@@ -0,0 +1,77 @@
+use rand::Rng;
+
+/// How the PDRs of generated drones are chosen
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PdrDistribution {
+    Constant(f32),
+    /// Uniform in [min, max]
+    Uniform(f32, f32),
+}
+
+impl PdrDistribution {
+    /// Parses either a value, e.g. "0.05", or "uniform(min, max)"
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let value = value.trim();
+        let distribution = if let Some(arguments) = value.strip_prefix("uniform(").and_then(|v| v.strip_suffix(')')) {
+            let bounds: Vec<f32> = arguments
+                .split(',')
+                .map(|bound| bound.trim().parse::<f32>())
+                .collect::<Result<_, _>>()
+                .map_err(|_| format!("Invalid PDR distribution \"{}\"", value))?;
+            match bounds[..] {
+                [min, max] if min <= max => PdrDistribution::Uniform(min, max),
+                _ => return Err(format!("Invalid PDR distribution \"{}\", expected uniform(min, max)", value)),
+            }
+        } else {
+            PdrDistribution::Constant(value.parse().map_err(|_| format!("Invalid PDR \"{}\"", value))?)
+        };
+
+        let in_range = |pdr: f32| (0. ..=1.).contains(&pdr);
+        match distribution {
+            PdrDistribution::Constant(pdr) if in_range(pdr) => Ok(distribution),
+            PdrDistribution::Uniform(min, max) if in_range(min) && in_range(max) => Ok(distribution),
+            _ => Err(format!("The PDR \"{}\" is not in the right range! Correct range: [0 - 1]", value)),
+        }
+    }
+
+    /// Samples a PDR, rounded to 3 decimals to keep the topology files readable
+    pub fn sample(&self, rng: &mut impl Rng) -> f32 {
+        match *self {
+            PdrDistribution::Constant(pdr) => pdr,
+            PdrDistribution::Uniform(min, max) => (rng.gen_range(min..=max) * 1000.).round() / 1000.,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn parses_constants_and_uniform_ranges() {
+        assert_eq!(PdrDistribution::parse(" 0.05 "), Ok(PdrDistribution::Constant(0.05)));
+        assert_eq!(PdrDistribution::parse("uniform(0.1, 0.3)"), Ok(PdrDistribution::Uniform(0.1, 0.3)));
+        assert_eq!(PdrDistribution::parse("uniform(0.2,0.2)"), Ok(PdrDistribution::Uniform(0.2, 0.2)));
+    }
+
+    #[test]
+    fn rejects_invalid_distributions() {
+        for value in ["", "abc", "1.5", "-0.1", "uniform(0.3, 0.1)", "uniform(0.1)", "uniform(0.1, 2)", "uniform(0.1, 0.2"] {
+            assert!(PdrDistribution::parse(value).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn samples_inside_the_range() {
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+        let distribution = PdrDistribution::Uniform(0.1, 0.2);
+        for _ in 0..1000 {
+            let pdr = distribution.sample(&mut rng);
+            assert!((0.1..=0.2).contains(&pdr));
+            assert_eq!((pdr * 1000.).round() / 1000., pdr);
+        }
+        assert_eq!(PdrDistribution::Constant(0.4).sample(&mut rng), 0.4);
+    }
+}
@@ -0,0 +1,186 @@
+use rand::seq::{index, SliceRandom};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use wg_2024::config::{Client, Config, Drone, Server};
+use wg_2024::network::NodeId;
+
+use super::{connect_drone, host_ids, PdrDistribution};
+
+/// Parameters of a random topology
+#[derive(Debug, Clone)]
+pub struct RandomOptions {
+    pub seed: u64,
+    pub drones: usize,
+    pub clients: usize,
+    pub servers: usize,
+    /// Average number of drone neighbours of each drone
+    pub degree: f32,
+    /// Drones each server is connected to (at least 2)
+    pub server_links: usize,
+    pub pdr: PdrDistribution,
+}
+
+/// Generates a random topology that respects the WGL rules without any rejection:
+/// 1. the drones are linked by a spanning tree, drawn uniformly among all the labelled trees
+///    by decoding a random Prüfer sequence, so every drone reaches every other drone;
+/// 2. extra links are drawn uniformly among the missing ones, until the average degree is reached;
+/// 3. each client is connected to 1 or 2 random drones, each server to `server_links` random drones.
+///
+/// The same seed and options always give the same topology.
+pub fn random_topology(options: &RandomOptions) -> Result<Config, String> {
+    let n = options.drones;
+    if n == 0 || n > NodeId::MAX as usize {
+        return Err(format!("The number of drones must be between 1 and {}", NodeId::MAX));
+    }
+    if options.server_links < 2 {
+        return Err("Servers must be connected to at least 2 drones".to_string());
+    }
+    if options.servers > 0 && options.server_links > n {
+        return Err(format!("Not enough drones to connect each server to {} of them", options.server_links));
+    }
+
+    let max_links = n * (n - 1) / 2;
+    let links = (options.degree * n as f32 / 2.).round() as usize;
+    if links < n - 1 || links > max_links {
+        return Err(format!(
+            "The average degree of {} drones must be between {:.2} and {}",
+            n,
+            2. * (n - 1) as f32 / n as f32,
+            n - 1
+        ));
+    }
+
+    let mut rng = ChaCha8Rng::seed_from_u64(options.seed);
+    let (client_ids, server_ids) = host_ids(n, options.clients, options.servers)?;
+
+    let mut config = Config {
+        drone: (1..=n as NodeId)
+            .map(|id| Drone { id, connected_node_ids: Vec::new(), pdr: options.pdr.sample(&mut rng) })
+            .collect(),
+        client: Vec::new(),
+        server: Vec::new(),
+    };
+
+    let mut tree = random_tree(n, &mut rng);
+    let mut missing: Vec<(NodeId, NodeId)> = (1..=n as NodeId)
+        .flat_map(|a| (a + 1..=n as NodeId).map(move |b| (a, b)))
+        .filter(|link| !tree.contains(link))
+        .collect();
+    missing.shuffle(&mut rng);
+    tree.extend(missing.into_iter().take(links - (n - 1)));
+    for (a, b) in tree {
+        connect_drone(&mut config, a, b);
+        connect_drone(&mut config, b, a);
+    }
+
+    for id in client_ids {
+        let links = if n >= 2 { rng.gen_range(1..=2) } else { 1 };
+        let drones = random_drones(n, links, &mut rng);
+        for drone in &drones {
+            connect_drone(&mut config, *drone, id);
+        }
+        config.client.push(Client { id, connected_drone_ids: drones });
+    }
+    for id in server_ids {
+        let drones = random_drones(n, options.server_links, &mut rng);
+        for drone in &drones {
+            connect_drone(&mut config, *drone, id);
+        }
+        config.server.push(Server { id, connected_drone_ids: drones });
+    }
+
+    Ok(config)
+}
+
+// Uniform random labelled tree over the drones 1..=n, from a random Prüfer sequence
+fn random_tree(n: usize, rng: &mut ChaCha8Rng) -> Vec<(NodeId, NodeId)> {
+    if n < 2 {
+        return Vec::new();
+    }
+    let sequence: Vec<usize> = (0..n - 2).map(|_| rng.gen_range(0..n)).collect();
+    let mut degree = vec![1; n];
+    for node in &sequence {
+        degree[*node] += 1;
+    }
+
+    let mut links = Vec::with_capacity(n - 1);
+    for node in sequence {
+        let leaf = (0..n).find(|i| degree[*i] == 1).unwrap();
+        links.push(link(leaf, node));
+        degree[leaf] -= 1;
+        degree[node] -= 1;
+    }
+    let last: Vec<usize> = (0..n).filter(|i| degree[*i] == 1).collect();
+    links.push(link(last[0], last[1]));
+    links
+}
+
+fn link(a: usize, b: usize) -> (NodeId, NodeId) {
+    ((a.min(b) + 1) as NodeId, (a.max(b) + 1) as NodeId)
+}
+
+fn random_drones(n: usize, amount: usize, rng: &mut ChaCha8Rng) -> Vec<NodeId> {
+    index::sample(rng, n, amount).into_iter().map(|i| (i + 1) as NodeId).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{input_validator, topology};
+
+    fn options(seed: u64) -> RandomOptions {
+        RandomOptions {
+            seed,
+            drones: 10,
+            clients: 3,
+            servers: 2,
+            degree: 3.,
+            server_links: 2,
+            pdr: PdrDistribution::Uniform(0., 0.3),
+        }
+    }
+
+    #[test]
+    fn decodes_a_spanning_tree() {
+        for seed in 0..50 {
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            let tree = random_tree(8, &mut rng);
+            assert_eq!(tree.len(), 7);
+
+            // n - 1 links that connect every node form a tree
+            let mut reached = vec![1];
+            while let Some(&(a, b)) = tree.iter().find(|(a, b)| reached.contains(a) != reached.contains(b)) {
+                reached.push(if reached.contains(&a) { b } else { a });
+            }
+            assert_eq!(reached.len(), 8, "seed {}: {:?}", seed, tree);
+        }
+        assert!(random_tree(1, &mut ChaCha8Rng::seed_from_u64(0)).is_empty());
+    }
+
+    #[test]
+    fn generates_valid_topologies_with_the_requested_degree() {
+        for seed in 0..50 {
+            let config = random_topology(&options(seed)).unwrap();
+            input_validator::validate_config(&config).unwrap();
+            input_validator::check_reachability(&config).unwrap();
+
+            let drone_links = topology::links(&config).into_iter().filter(|(_, b)| *b <= 10).count();
+            assert_eq!(drone_links, 15);
+        }
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_topology() {
+        let toml = |seed| crate::utils::config_writer::to_toml(&random_topology(&options(seed)).unwrap());
+        assert_eq!(toml(42), toml(42));
+        assert_ne!(toml(42), toml(43));
+    }
+
+    #[test]
+    fn rejects_impossible_options() {
+        assert!(random_topology(&RandomOptions { drones: 0, ..options(0) }).is_err());
+        assert!(random_topology(&RandomOptions { degree: 1., ..options(0) }).is_err());
+        assert!(random_topology(&RandomOptions { degree: 10., ..options(0) }).is_err());
+        assert!(random_topology(&RandomOptions { server_links: 1, ..options(0) }).is_err());
+    }
+}
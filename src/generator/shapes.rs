@@ -0,0 +1,222 @@
+use wg_2024::network::NodeId;
+
+/// The classic WGL test topologies
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shape {
+    Chain,
+    DoubleChain,
+    Ring,
+    Star,
+    Grid,
+    Tree,
+    Butterfly,
+    Full,
+}
+
+/// The drones of a shape, ready to be turned into a topology
+pub struct ShapeLayout {
+    pub links: Vec<(NodeId, NodeId)>,
+    /// Drones the clients attach to, in order
+    pub client_side: Vec<NodeId>,
+    /// Drones the servers attach to, in order
+    pub server_side: Vec<NodeId>,
+}
+
+impl Shape {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "chain" => Ok(Shape::Chain),
+            "double-chain" => Ok(Shape::DoubleChain),
+            "ring" => Ok(Shape::Ring),
+            "star" => Ok(Shape::Star),
+            "grid" | "mesh" => Ok(Shape::Grid),
+            "tree" => Ok(Shape::Tree),
+            "butterfly" => Ok(Shape::Butterfly),
+            "full" => Ok(Shape::Full),
+            _ => Err(format!(
+                "Unknown shape \"{}\"! Valid shapes: chain, double-chain, ring, star, grid, tree, butterfly, full",
+                name
+            )),
+        }
+    }
+
+    /// Lays out `drones` drones (with ids 1..=drones) in this shape.
+    /// `width` is only used by the grid, and defaults to the square root of the drones.
+    pub fn layout(&self, drones: usize, width: Option<usize>) -> Result<ShapeLayout, String> {
+        if drones == 0 {
+            return Err("At least one drone is needed".to_string());
+        }
+        let n = drones as NodeId;
+        let ids: Vec<NodeId> = (1..=n).collect();
+        let reversed: Vec<NodeId> = ids.iter().rev().copied().collect();
+
+        let layout = match self {
+            // Clients at one end, servers at the other end
+            Shape::Chain => ShapeLayout {
+                links: (1..n).map(|i| (i, i + 1)).collect(),
+                client_side: ids,
+                server_side: reversed,
+            },
+            // Two parallel chains with a rung between each pair of drones
+            Shape::DoubleChain => {
+                if drones < 4 || !drones.is_multiple_of(2) {
+                    return Err("A double chain needs an even number of drones, at least 4".to_string());
+                }
+                let k = n / 2;
+                let mut links: Vec<(NodeId, NodeId)> = Vec::new();
+                for i in 1..k {
+                    links.push((i, i + 1));
+                    links.push((k + i, k + i + 1));
+                }
+                for i in 1..=k {
+                    links.push((i, k + i));
+                }
+                ShapeLayout {
+                    links,
+                    client_side: (1..=k).flat_map(|i| [i, k + i]).collect(),
+                    server_side: (1..=k).rev().flat_map(|i| [i, k + i]).collect(),
+                }
+            }
+            // Clients and servers on opposite sides of the ring
+            Shape::Ring => {
+                if drones < 3 {
+                    return Err("A ring needs at least 3 drones".to_string());
+                }
+                let mut links: Vec<(NodeId, NodeId)> = (1..n).map(|i| (i, i + 1)).collect();
+                links.push((n, 1));
+                let half = drones / 2;
+                ShapeLayout {
+                    links,
+                    client_side: ids.clone(),
+                    server_side: ids[half..].iter().chain(ids[..half].iter()).copied().collect(),
+                }
+            }
+            // Drone 1 is the hub, hosts attach to the spokes
+            Shape::Star => {
+                if drones < 2 {
+                    return Err("A star needs at least 2 drones".to_string());
+                }
+                ShapeLayout {
+                    links: (2..=n).map(|i| (1, i)).collect(),
+                    client_side: ids[1..].to_vec(),
+                    server_side: reversed[..drones - 1].to_vec(),
+                }
+            }
+            // Clients from the top left corner, servers from the bottom right corner
+            Shape::Grid => {
+                let width = width.unwrap_or((drones as f64).sqrt().round().max(1.) as usize);
+                if width == 0 || !drones.is_multiple_of(width) {
+                    return Err(format!("{} drones cannot fill a grid with width {}", drones, width));
+                }
+                let mut links = Vec::new();
+                for i in 0..drones {
+                    let id = (i + 1) as NodeId;
+                    if (i + 1) % width != 0 {
+                        links.push((id, id + 1));
+                    }
+                    if i + width < drones {
+                        links.push((id, id + width as NodeId));
+                    }
+                }
+                ShapeLayout {
+                    links,
+                    client_side: ids,
+                    server_side: reversed,
+                }
+            }
+            // Binary tree rooted in drone 1: clients at the leaves, servers near the root
+            Shape::Tree => {
+                let links = (2..=n).map(|i| (i / 2, i)).collect();
+                let leaves: Vec<NodeId> = ids.iter().copied().filter(|i| 2 * (*i as usize) > drones).collect();
+                ShapeLayout {
+                    links,
+                    client_side: leaves,
+                    server_side: ids,
+                }
+            }
+            // Butterfly network of dimension d: d + 1 stages of 2^d drones
+            Shape::Butterfly => {
+                let dimension = (1..=5).find(|d| (d + 1) * (1 << d) == drones).ok_or(format!(
+                    "A butterfly needs (d + 1) * 2^d drones, valid values: {}",
+                    (1..=5).map(|d: usize| ((d + 1) * (1 << d)).to_string()).collect::<Vec<_>>().join(", ")
+                ))?;
+                let rows = 1 << dimension;
+                let id = |stage: usize, row: usize| (stage * rows + row + 1) as NodeId;
+
+                let mut links = Vec::new();
+                for stage in 0..dimension {
+                    for row in 0..rows {
+                        links.push((id(stage, row), id(stage + 1, row)));
+                        links.push((id(stage, row), id(stage + 1, row ^ (1 << stage))));
+                    }
+                }
+                ShapeLayout {
+                    links,
+                    client_side: (0..rows).map(|row| id(0, row)).collect(),
+                    server_side: (0..rows).map(|row| id(dimension, row)).collect(),
+                }
+            }
+            // Every drone is linked to every other drone
+            Shape::Full => ShapeLayout {
+                links: (1..=n).flat_map(|a| (a + 1..=n).map(move |b| (a, b))).collect(),
+                client_side: ids,
+                server_side: reversed,
+            },
+        };
+
+        Ok(layout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lays_out_the_expected_links() {
+        let chain = Shape::Chain.layout(4, None).unwrap();
+        assert_eq!(chain.links, [(1, 2), (2, 3), (3, 4)]);
+        assert_eq!(chain.server_side, [4, 3, 2, 1]);
+
+        let ring = Shape::Ring.layout(4, None).unwrap();
+        assert_eq!(ring.links, [(1, 2), (2, 3), (3, 4), (4, 1)]);
+        assert_eq!(ring.server_side, [3, 4, 1, 2]);
+
+        let star = Shape::Star.layout(4, None).unwrap();
+        assert_eq!(star.links, [(1, 2), (1, 3), (1, 4)]);
+        assert!(!star.client_side.contains(&1) && !star.server_side.contains(&1));
+
+        let grid = Shape::Grid.layout(6, Some(3)).unwrap();
+        assert_eq!(grid.links, [(1, 2), (1, 4), (2, 3), (2, 5), (3, 6), (4, 5), (5, 6)]);
+
+        let tree = Shape::Tree.layout(5, None).unwrap();
+        assert_eq!(tree.links, [(1, 2), (1, 3), (2, 4), (2, 5)]);
+        assert_eq!(tree.client_side, [3, 4, 5]);
+
+        assert_eq!(Shape::DoubleChain.layout(6, None).unwrap().links.len(), 7);
+        assert_eq!(Shape::Full.layout(5, None).unwrap().links.len(), 10);
+    }
+
+    #[test]
+    fn butterfly_connects_each_stage_to_the_next_one() {
+        // Dimension 2: 3 stages of 4 drones, 2 links from each drone of the first 2 stages
+        let butterfly = Shape::Butterfly.layout(12, None).unwrap();
+        assert_eq!(butterfly.links.len(), 16);
+        assert_eq!(butterfly.client_side, [1, 2, 3, 4]);
+        assert_eq!(butterfly.server_side, [9, 10, 11, 12]);
+        assert!(butterfly.links.contains(&(1, 6)) && butterfly.links.contains(&(5, 11)));
+
+        assert!(Shape::Butterfly.layout(10, None).is_err());
+    }
+
+    #[test]
+    fn rejects_impossible_sizes() {
+        assert!(Shape::Chain.layout(0, None).is_err());
+        assert!(Shape::DoubleChain.layout(5, None).is_err());
+        assert!(Shape::Ring.layout(2, None).is_err());
+        assert!(Shape::Star.layout(1, None).is_err());
+        assert!(Shape::Grid.layout(7, Some(3)).is_err());
+        assert!(Shape::parse("hypercube").is_err());
+        assert_eq!(Shape::parse("mesh"), Ok(Shape::Grid));
+    }
+}
@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+
+use super::ImportedGraph;
+
+/// Parses an adjacency matrix. The first row holds the node names (its first cell is ignored)
+/// and every other row starts with a node name followed by one cell for each column:
+/// empty cells and "0" mean no link, anything else means a link.
+pub fn parse(data: &str) -> Result<ImportedGraph, String> {
+    let mut graph = ImportedGraph::default();
+    let mut lines = data.lines().filter(|line| !line.trim().is_empty());
+
+    let header = lines.next().ok_or("The adjacency matrix is empty".to_string())?;
+    let columns: Vec<String> = split_row(header).into_iter().skip(1).collect();
+    for column in &columns {
+        graph.add_node(column, HashMap::new());
+    }
+
+    for (row_index, line) in lines.enumerate() {
+        let cells = split_row(line);
+        let (name, values) = cells
+            .split_first()
+            .ok_or(format!("Row {} of the adjacency matrix is empty", row_index + 2))?;
+        if values.len() > columns.len() {
+            return Err(format!("Row \"{}\" has more cells than the header", name));
+        }
+
+        graph.add_node(name, HashMap::new());
+        for (column, value) in columns.iter().zip(values) {
+            if !value.is_empty() && value != "0" && column != name {
+                graph.add_edge(name, column);
+            }
+        }
+    }
+
+    Ok(graph)
+}
+
+fn split_row(line: &str) -> Vec<String> {
+    line.split([',', ';'])
+        .map(|cell| cell.trim().trim_matches('"').to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_an_adjacency_matrix() {
+        let graph = parse(
+            ";c1;d2;d3;s4
+             c1;0;1;;0
+             d2;1;1;x;
+             d3;;1;0;1
+             \"s4\";0;;1;0",
+        )
+        .unwrap();
+
+        assert_eq!(graph.nodes.keys().collect::<Vec<_>>(), ["c1", "d2", "d3", "s4"]);
+        // Links are read from both rows, the diagonal is ignored
+        assert_eq!(
+            graph.edges,
+            [("c1", "d2"), ("d2", "c1"), ("d2", "d3"), ("d3", "d2"), ("d3", "s4"), ("s4", "d3")]
+                .map(|(a, b)| (a.to_string(), b.to_string()))
+        );
+    }
+
+    #[test]
+    fn rejects_rows_longer_than_the_header() {
+        assert!(parse(",a,b\na,0,1,1").is_err());
+        assert!(parse("\n\n").is_err());
+    }
+}
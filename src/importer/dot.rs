@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+use std::iter::Peekable;
+use std::vec::IntoIter;
+
+use super::ImportedGraph;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Id(String),
+    EdgeOp,
+    Symbol(char),
+}
+
+/// Parses the subset of the DOT language that describes a plain graph:
+/// node and edge statements (edge chains included), with their attributes, and subgraphs,
+/// whose content is merged into the graph. Default attribute statements are ignored.
+pub fn parse(data: &str) -> Result<ImportedGraph, String> {
+    let mut tokens = tokenize(data)?.into_iter().peekable();
+    let mut graph = ImportedGraph::default();
+
+    // Header: [strict] (graph | digraph) [name] {
+    let mut keyword = next_id(&mut tokens)?;
+    if keyword.eq_ignore_ascii_case("strict") {
+        keyword = next_id(&mut tokens)?;
+    }
+    if !keyword.eq_ignore_ascii_case("graph") && !keyword.eq_ignore_ascii_case("digraph") {
+        return Err(format!("Expected \"graph\" or \"digraph\", found \"{}\"", keyword));
+    }
+    if let Some(Token::Id(_)) = tokens.peek() {
+        tokens.next();
+    }
+    expect(&mut tokens, '{')?;
+
+    parse_statements(&mut tokens, &mut graph)?;
+    Ok(graph)
+}
+
+// Parses statements until the closing brace of the current block
+fn parse_statements(tokens: &mut Peekable<IntoIter<Token>>, graph: &mut ImportedGraph) -> Result<(), String> {
+    loop {
+        match tokens.next() {
+            None => return Err("Unexpected end of file, missing \"}\"".to_string()),
+            Some(Token::Symbol('}')) => return Ok(()),
+            Some(Token::Symbol(';')) => {}
+            Some(Token::Symbol('{')) => parse_statements(tokens, graph)?,
+            Some(Token::Id(id)) if id.eq_ignore_ascii_case("subgraph") => {
+                if let Some(Token::Id(_)) = tokens.peek() {
+                    tokens.next();
+                }
+                expect(tokens, '{')?;
+                parse_statements(tokens, graph)?;
+            }
+            Some(Token::Id(id)) if ["graph", "node", "edge"].iter().any(|k| id.eq_ignore_ascii_case(k)) => {
+                parse_attributes(tokens)?;
+            }
+            Some(Token::Id(id)) => parse_node_or_edge(id, tokens, graph)?,
+            Some(token) => return Err(format!("Unexpected token {:?}", token)),
+        }
+    }
+}
+
+fn parse_node_or_edge(
+    first: String,
+    tokens: &mut Peekable<IntoIter<Token>>,
+    graph: &mut ImportedGraph,
+) -> Result<(), String> {
+    skip_port(tokens);
+
+    // Graph attribute, e.g. rankdir = LR
+    if tokens.peek() == Some(&Token::Symbol('=')) {
+        tokens.next();
+        next_id(tokens)?;
+        return Ok(());
+    }
+
+    let mut chain = vec![first];
+    while tokens.peek() == Some(&Token::EdgeOp) {
+        tokens.next();
+        chain.push(next_id(tokens)?);
+        skip_port(tokens);
+    }
+    let attributes = parse_attributes(tokens)?;
+
+    if chain.len() == 1 {
+        graph.add_node(&chain[0], attributes);
+    } else {
+        for pair in chain.windows(2) {
+            graph.add_edge(&pair[0], &pair[1]);
+        }
+    }
+    Ok(())
+}
+
+// Parses any number of attribute lists: [a=1, b=2][c=3]
+fn parse_attributes(tokens: &mut Peekable<IntoIter<Token>>) -> Result<HashMap<String, String>, String> {
+    let mut attributes = HashMap::new();
+
+    while tokens.peek() == Some(&Token::Symbol('[')) {
+        tokens.next();
+        loop {
+            match tokens.next() {
+                Some(Token::Symbol(']')) => break,
+                Some(Token::Symbol(',' | ';')) => {}
+                Some(Token::Id(key)) => {
+                    expect(tokens, '=')?;
+                    attributes.insert(key.to_lowercase(), next_id(tokens)?);
+                }
+                Some(token) => return Err(format!("Unexpected token {:?} in attribute list", token)),
+                None => return Err("Unexpected end of file, missing \"]\"".to_string()),
+            }
+        }
+    }
+    Ok(attributes)
+}
+
+// Ports (node:port:compass) do not matter for the topology
+fn skip_port(tokens: &mut Peekable<IntoIter<Token>>) {
+    while tokens.peek() == Some(&Token::Symbol(':')) {
+        tokens.next();
+        tokens.next();
+    }
+}
+
+fn next_id(tokens: &mut Peekable<IntoIter<Token>>) -> Result<String, String> {
+    match tokens.next() {
+        Some(Token::Id(id)) => Ok(id),
+        Some(token) => Err(format!("Expected an identifier, found {:?}", token)),
+        None => Err("Unexpected end of file".to_string()),
+    }
+}
+
+fn expect(tokens: &mut Peekable<IntoIter<Token>>, symbol: char) -> Result<(), String> {
+    match tokens.next() {
+        Some(Token::Symbol(c)) if c == symbol => Ok(()),
+        Some(token) => Err(format!("Expected \"{}\", found {:?}", symbol, token)),
+        None => Err(format!("Unexpected end of file, missing \"{}\"", symbol)),
+    }
+}
+
+fn tokenize(data: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = data.chars().peekable();
+    let mut line_start = true;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => {
+                line_start = true;
+                continue;
+            }
+            c if c.is_whitespace() => continue,
+            // Preprocessor lines
+            '#' if line_start => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = ' ';
+                loop {
+                    match chars.next() {
+                        Some('/') if previous == '*' => break,
+                        Some(c) => previous = c,
+                        None => return Err("Unterminated comment".to_string()),
+                    }
+                }
+            }
+            '-' if matches!(chars.peek(), Some('-') | Some('>')) => {
+                chars.next();
+                tokens.push(Token::EdgeOp);
+            }
+            '"' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('\\') => {
+                            if let Some(escaped) = chars.next() {
+                                if escaped != '"' {
+                                    value.push('\\');
+                                }
+                                value.push(escaped);
+                            }
+                        }
+                        Some('"') => break,
+                        Some(c) => value.push(c),
+                        None => return Err("Unterminated string".to_string()),
+                    }
+                }
+                tokens.push(Token::Id(value));
+            }
+            '{' | '}' | '[' | ']' | '=' | ';' | ',' | ':' => tokens.push(Token::Symbol(c)),
+            c if c.is_alphanumeric() || c == '_' || c == '.' || c == '-' => {
+                let mut value = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if next.is_alphanumeric() || next == '_' || next == '.' {
+                        value.push(next);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push(Token::Id(value));
+            }
+            '<' => return Err("HTML labels are not supported".to_string()),
+            c => return Err(format!("Unexpected character '{}'", c)),
+        }
+        line_start = false;
+    }
+
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenizes_comments_strings_and_edges() {
+        let tokens = tokenize("# preprocessor\na -- \"b \\\"c\\\"\" // comment\n/* block */ d -> e;").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Id("a".to_string()),
+                Token::EdgeOp,
+                Token::Id("b \"c\"".to_string()),
+                Token::Id("d".to_string()),
+                Token::EdgeOp,
+                Token::Id("e".to_string()),
+                Token::Symbol(';'),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_unterminated_input() {
+        assert!(tokenize("a -- \"b").is_err());
+        assert!(tokenize("a /* b").is_err());
+        assert!(parse("graph { a -- b").is_err());
+        assert!(parse("tree { a }").is_err());
+    }
+
+    #[test]
+    fn parses_chains_subgraphs_and_attributes() {
+        let graph = parse(
+            "strict graph g {
+                rankdir = LR;
+                node [shape=box];
+                c1 [type=client] [pdr=\"0.5\"];
+                c1 -- d1:port -- s1;
+                subgraph cluster { d1 -- d2 }
+            }",
+        )
+        .unwrap();
+
+        assert_eq!(graph.nodes.keys().collect::<Vec<_>>(), ["c1", "d1", "d2", "s1"]);
+        assert_eq!(graph.nodes["c1"]["type"], "client");
+        assert_eq!(graph.nodes["c1"]["pdr"], "0.5");
+        assert!(!graph.nodes["d1"].contains_key("shape"));
+        assert_eq!(
+            graph.edges,
+            [("c1", "d1"), ("d1", "s1"), ("d1", "d2")].map(|(a, b)| (a.to_string(), b.to_string()))
+        );
+    }
+}
@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+
+use super::ImportedGraph;
+
+/// Parses a GraphML document. The `<data>` of each node is read using the `attr.name`
+/// of its `<key>` (e.g. "type" or "pdr"), edges are read from their source and target.
+pub fn parse(data: &str) -> Result<ImportedGraph, String> {
+    let document = roxmltree::Document::parse(data).map_err(|e| format!("Invalid GraphML: {}", e))?;
+    let mut graph = ImportedGraph::default();
+
+    // Key id -> attribute name
+    let keys: HashMap<&str, &str> = document
+        .descendants()
+        .filter(|n| n.has_tag_name("key"))
+        .filter_map(|key| Some((key.attribute("id")?, key.attribute("attr.name").or(key.attribute("id"))?)))
+        .collect();
+
+    for node in document.descendants().filter(|n| n.has_tag_name("node")) {
+        let name = node.attribute("id").ok_or("A GraphML node has no id".to_string())?;
+        let attributes: HashMap<String, String> = node
+            .children()
+            .filter(|c| c.has_tag_name("data"))
+            .filter_map(|data| {
+                let key = data.attribute("key")?;
+                let attribute = keys.get(key).copied().unwrap_or(key);
+                Some((attribute.to_lowercase(), data.text()?.trim().to_string()))
+            })
+            .collect();
+        graph.add_node(name, attributes);
+    }
+
+    for edge in document.descendants().filter(|n| n.has_tag_name("edge")) {
+        match (edge.attribute("source"), edge.attribute("target")) {
+            (Some(source), Some(target)) => graph.add_edge(source, target),
+            _ => return Err("A GraphML edge has no source or target".to_string()),
+        }
+    }
+
+    Ok(graph)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_node_data_through_the_keys() {
+        let graph = parse(
+            r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+                <key id="k0" for="node" attr.name="Type"/>
+                <key id="pdr" for="node"/>
+                <graph edgedefault="undirected">
+                    <node id="n1"><data key="k0">client</data></node>
+                    <node id="n2"><data key="k0">drone</data><data key="pdr"> 0.3 </data></node>
+                    <edge source="n1" target="n2"/>
+                </graph>
+            </graphml>"#,
+        )
+        .unwrap();
+
+        assert_eq!(graph.nodes["n1"]["type"], "client");
+        assert_eq!(graph.nodes["n2"]["type"], "drone");
+        assert_eq!(graph.nodes["n2"]["pdr"], "0.3");
+        assert_eq!(graph.edges, vec![("n1".to_string(), "n2".to_string())]);
+    }
+
+    #[test]
+    fn rejects_edges_without_endpoints() {
+        assert!(parse(r#"<graphml><graph><edge source="a"/></graph></graphml>"#).is_err());
+        assert!(parse("<graphml>").is_err());
+    }
+}
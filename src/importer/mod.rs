@@ -0,0 +1,229 @@
+pub mod csv;
+pub mod dot;
+pub mod graphml;
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use wg_2024::config::{Client, Config, Drone, Server};
+use wg_2024::network::NodeId;
+
+use crate::utils::{config_writer, input_validator};
+
+/// A graph read from an external format, before it is turned into a `Config`
+#[derive(Debug, Default)]
+pub struct ImportedGraph {
+    /// Node names, with their attributes (e.g. "type" and "pdr")
+    pub nodes: BTreeMap<String, HashMap<String, String>>,
+    pub edges: Vec<(String, String)>,
+}
+
+impl ImportedGraph {
+    /// Adds a node, merging its attributes with the ones it already has
+    pub fn add_node(&mut self, name: &str, attributes: HashMap<String, String>) {
+        self.nodes.entry(name.to_string()).or_default().extend(attributes);
+    }
+
+    pub fn add_edge(&mut self, a: &str, b: &str) {
+        self.add_node(a, HashMap::new());
+        self.add_node(b, HashMap::new());
+        self.edges.push((a.to_string(), b.to_string()));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportFormat {
+    Dot,
+    GraphMl,
+    Csv,
+}
+
+impl ImportFormat {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.to_lowercase().as_str() {
+            "dot" | "gv" => Ok(ImportFormat::Dot),
+            "graphml" | "xml" => Ok(ImportFormat::GraphMl),
+            "csv" => Ok(ImportFormat::Csv),
+            _ => Err(format!("Unknown import format \"{}\"! Valid formats: dot, graphml, csv", name)),
+        }
+    }
+
+    /// Guesses the format from the extension of the file
+    pub fn from_path(path: &str) -> Result<Self, String> {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .ok_or(format!("Unable to guess the format of \"{}\", use --format", path))?;
+        Self::parse(extension)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum NodeKind {
+    Drone,
+    Client,
+    Server,
+}
+
+/// Reads a graph file, converts it to a `Config`, validates it and writes it as TOML
+pub fn import(
+    path: &str,
+    format: Option<ImportFormat>,
+    default_pdr: f32,
+    output: Option<&str>,
+) -> Result<(), String> {
+    let format = match format {
+        Some(format) => format,
+        None => ImportFormat::from_path(path)?,
+    };
+    let data = fs::read_to_string(path).map_err(|e| format!("Unable to read \"{}\": {}", path, e))?;
+
+    let graph = match format {
+        ImportFormat::Dot => dot::parse(&data)?,
+        ImportFormat::GraphMl => graphml::parse(&data)?,
+        ImportFormat::Csv => csv::parse(&data)?,
+    };
+    let config = to_config(&graph, default_pdr)?;
+    input_validator::validate_config(&config)
+        .map_err(|error_message| format!("The imported topology is not valid: {}", error_message))?;
+
+    let toml = config_writer::to_toml(&config);
+    match output {
+        Some(path) => fs::write(path, toml).map_err(|e| format!("Unable to write \"{}\": {}", path, e)),
+        None => {
+            print!("{}", toml);
+            Ok(())
+        }
+    }
+}
+
+/// Converts an imported graph into a `Config`.
+/// The kind of each node comes from its "type" (or "kind") attribute, or else from the prefix
+/// of its name: "d"/"drone", "c"/"client", "s"/"server"; names without a prefix are drones.
+/// The id comes from the "id" attribute or else from the digits of the name, the PDR of the
+/// drones from the "pdr" attribute or else from `default_pdr`.
+pub fn to_config(graph: &ImportedGraph, default_pdr: f32) -> Result<Config, String> {
+    let mut nodes: HashMap<&str, (NodeId, NodeKind, f32)> = HashMap::new();
+
+    for (name, attributes) in &graph.nodes {
+        let kind = match attributes.get("type").or(attributes.get("kind")) {
+            Some(kind) => parse_kind(kind).ok_or(format!("Node \"{}\" has an unknown type \"{}\"", name, kind))?,
+            None => kind_from_prefix(name),
+        };
+        let id = match attributes.get("id") {
+            Some(id) => id.parse::<NodeId>().map_err(|_| format!("Node \"{}\" has an invalid id \"{}\"", name, id))?,
+            None => id_from_name(name)?,
+        };
+        let pdr = match attributes.get("pdr") {
+            Some(pdr) => pdr.parse::<f32>().map_err(|_| format!("Node \"{}\" has an invalid pdr \"{}\"", name, pdr))?,
+            None => default_pdr,
+        };
+        nodes.insert(name, (id, kind, pdr));
+    }
+
+    let mut neighbours: BTreeMap<NodeId, Vec<NodeId>> = nodes.values().map(|(id, _, _)| (*id, Vec::new())).collect();
+    for (a, b) in &graph.edges {
+        let (id_a, kind_a, _) = nodes[a.as_str()];
+        let (id_b, kind_b, _) = nodes[b.as_str()];
+        if kind_a != NodeKind::Drone && kind_b != NodeKind::Drone {
+            return Err(format!("Link {}-{} connects two hosts without a drone", a, b));
+        }
+
+        // Duplicated edges (e.g. both directions of a digraph) are merged
+        if !neighbours[&id_a].contains(&id_b) {
+            neighbours.get_mut(&id_a).unwrap().push(id_b);
+        }
+        if !neighbours[&id_b].contains(&id_a) {
+            neighbours.get_mut(&id_b).unwrap().push(id_a);
+        }
+    }
+
+    let mut sorted_nodes: Vec<&(NodeId, NodeKind, f32)> = nodes.values().collect();
+    sorted_nodes.sort_by_key(|(id, _, _)| *id);
+
+    let mut config = Config {
+        drone: Vec::new(),
+        client: Vec::new(),
+        server: Vec::new(),
+    };
+    for (id, kind, pdr) in sorted_nodes {
+        let connected_ids = neighbours[id].clone();
+        match kind {
+            NodeKind::Drone => config.drone.push(Drone { id: *id, connected_node_ids: connected_ids, pdr: *pdr }),
+            NodeKind::Client => config.client.push(Client { id: *id, connected_drone_ids: connected_ids }),
+            NodeKind::Server => config.server.push(Server { id: *id, connected_drone_ids: connected_ids }),
+        }
+    }
+
+    Ok(config)
+}
+
+fn parse_kind(kind: &str) -> Option<NodeKind> {
+    match kind.to_lowercase().as_str() {
+        "drone" | "d" => Some(NodeKind::Drone),
+        "client" | "c" => Some(NodeKind::Client),
+        "server" | "s" => Some(NodeKind::Server),
+        _ => None,
+    }
+}
+
+fn kind_from_prefix(name: &str) -> NodeKind {
+    let prefix: String = name.chars().take_while(|c| c.is_alphabetic()).collect();
+    parse_kind(&prefix).unwrap_or(NodeKind::Drone)
+}
+
+fn id_from_name(name: &str) -> Result<NodeId, String> {
+    let digits: String = name.chars().skip_while(|c| !c.is_ascii_digit()).take_while(|c| c.is_ascii_digit()).collect();
+    digits
+        .parse::<NodeId>()
+        .map_err(|_| format!("Unable to get a valid id from the node name \"{}\"", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan;
+    use crate::utils::topology;
+
+    // c1 - d2 - s4, c1 - d3 - s4, d2 - d3
+    fn sample_config() -> Config {
+        Config {
+            drone: vec![
+                Drone { id: 2, connected_node_ids: vec![1, 3, 4], pdr: 0.1 },
+                Drone { id: 3, connected_node_ids: vec![1, 2, 4], pdr: 0.25 },
+            ],
+            client: vec![Client { id: 1, connected_drone_ids: vec![2, 3] }],
+            server: vec![Server { id: 4, connected_drone_ids: vec![2, 3] }],
+        }
+    }
+
+    #[test]
+    fn infers_kinds_and_ids_from_the_names() {
+        let graph = csv::parse(",client1,d2,drone3,s4\nclient1,0,1,1,0\nd2,1,0,1,1\ndrone3,1,1,0,1\ns4,0,1,1,0").unwrap();
+        let config = to_config(&graph, 0.1).unwrap();
+
+        assert_eq!(config.client.iter().map(|c| c.id).collect::<Vec<_>>(), [1]);
+        assert_eq!(config.drone.iter().map(|d| d.id).collect::<Vec<_>>(), [2, 3]);
+        assert_eq!(config.server.iter().map(|s| s.id).collect::<Vec<_>>(), [4]);
+        assert!(config.drone.iter().all(|d| d.pdr == 0.1));
+        assert_eq!(topology::links(&config), topology::links(&sample_config()));
+    }
+
+    #[test]
+    fn rejects_links_between_hosts() {
+        let graph = dot::parse("graph { c1 -- s2 }").unwrap();
+        assert!(to_config(&graph, 0.0).is_err());
+
+        let graph = dot::parse("graph { x [type=router] }").unwrap();
+        assert!(to_config(&graph, 0.0).is_err());
+    }
+
+    #[test]
+    fn planned_dot_imports_back_to_the_same_topology() {
+        let config = sample_config();
+        let dot = plan::to_dot(&config, &HashMap::new());
+        let imported = to_config(&dot::parse(&dot).unwrap(), 0.9).unwrap();
+
+        assert_eq!(config_writer::to_toml(&imported), config_writer::to_toml(&config));
+    }
+}
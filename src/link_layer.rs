@@ -0,0 +1,143 @@
+use crossbeam_channel::{bounded, unbounded, Receiver, Sender, TrySendError};
+use log::{debug, warn};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use wg_2024::network::NodeId;
+use wg_2024::packet::{Packet, PacketType};
+
+use crate::utils::buffers::{BufferSettings, OverflowPolicy};
+
+/// Something that wants to look at every packet crossing a link.
+/// `on_packet` is called on the link thread of the receiving node *before*
+/// the packet is delivered, so an observer may also hold the packet back
+/// by blocking (this is how the packet debugger freezes the network).
+pub trait PacketObserver: Send + Sync {
+    fn on_packet(&self, to: NodeId, packet: &Packet);
+
+    /// Called when the packet is dropped because the buffer of `to` is full
+    /// (only with the `drop` overflow policy)
+    fn on_overflow(&self, _to: NodeId, _packet: &Packet) {}
+}
+
+// Packets waiting on the link of a node and packets waiting in the node inbox
+type LinkQueues = (Receiver<Packet>, Receiver<Packet>);
+
+/// The link layer sits between the nodes: every node gets an inbound proxy
+/// thread that receives the packets sent by its neighbours (and by the
+/// simulation controller) and forwards them to the node itself.
+#[derive(Clone, Default)]
+pub struct LinkLayer {
+    observers: Vec<Arc<dyn PacketObserver>>,
+    buffers: BufferSettings,
+    queues: Arc<Mutex<HashMap<NodeId, LinkQueues>>>,
+}
+
+impl LinkLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers an observer. Only links opened after this call will notify it.
+    pub fn add_observer(&mut self, observer: Arc<dyn PacketObserver>) {
+        self.observers.push(observer);
+    }
+
+    /// Sets the size of the buffers. Only links opened after this call will use it.
+    pub fn set_buffers(&mut self, buffers: BufferSettings) {
+        self.buffers = buffers;
+    }
+
+    /// Opens the inbound link of a node and spawns its proxy.
+    /// Returns (sender the neighbours must use, receiver the node must listen on).
+    ///
+    /// With a bounded buffer and the `block` policy the link itself is bounded, so the senders wait
+    /// when it is full, and the proxy hands each packet directly to the node. The packet the proxy
+    /// is holding is part of the buffer, so the link takes one packet less than the capacity.
+    /// With the `drop` policy the inbox is bounded, and the proxy drops the packets that do not fit.
+    pub fn open_link(&self, node_id: NodeId) -> (Sender<Packet>, Receiver<Packet>) {
+        let capacity = self.buffers.capacity(node_id);
+        let overflow = self.buffers.overflow(node_id);
+        let ((link_sender, link_receiver), (node_sender, node_receiver)) = match (capacity, overflow) {
+            (Some(capacity), OverflowPolicy::Block) => (bounded::<Packet>(capacity - 1), bounded::<Packet>(0)),
+            (Some(capacity), OverflowPolicy::Drop) => (unbounded::<Packet>(), bounded::<Packet>(capacity)),
+            (None, _) => (unbounded::<Packet>(), unbounded::<Packet>()),
+        };
+
+        self.queues
+            .lock()
+            .unwrap()
+            .insert(node_id, (link_receiver.clone(), node_receiver.clone()));
+
+        let observers = self.observers.clone();
+        thread::spawn(move || {
+            for packet in link_receiver.iter() {
+                for observer in &observers {
+                    observer.on_packet(node_id, &packet);
+                }
+
+                let result = match overflow {
+                    OverflowPolicy::Block => node_sender.send(packet).map_err(|_| ()),
+                    OverflowPolicy::Drop => match node_sender.try_send(packet) {
+                        Err(TrySendError::Full(packet)) => {
+                            warn!("Buffer of node {} is full, {} dropped", node_id, packet_type_name(&packet.pack_type));
+                            for observer in &observers {
+                                observer.on_overflow(node_id, &packet);
+                            }
+                            Ok(())
+                        }
+                        result => result.map_err(|_| ()),
+                    },
+                };
+                if result.is_err() {
+                    warn!("Link to node {} is closed, stopping its proxy", node_id);
+                    break;
+                }
+            }
+            debug!("Proxy of node {} terminated", node_id);
+        });
+
+        (link_sender, node_receiver)
+    }
+
+    /// Returns, for each node, the number of packets waiting on its link
+    /// and the number of packets waiting in its inbox, sorted by node id.
+    pub fn queue_depths(&self) -> Vec<(NodeId, usize, usize)> {
+        let mut depths: Vec<(NodeId, usize, usize)> = self
+            .queues
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, (link, inbox))| (*id, link.len(), inbox.len()))
+            .collect();
+        depths.sort_by_key(|(id, _, _)| *id);
+        depths
+    }
+}
+
+/// Returns the name of the variant of the packet type (e.g. "FloodRequest")
+pub fn packet_type_name(pack_type: &PacketType) -> &'static str {
+    match pack_type {
+        PacketType::MsgFragment(_) => "MsgFragment",
+        PacketType::Ack(_) => "Ack",
+        PacketType::Nack(_) => "Nack",
+        PacketType::FloodRequest(_) => "FloodRequest",
+        PacketType::FloodResponse(_) => "FloodResponse",
+    }
+}
+
+/// Returns the node that originated the packet, if known
+pub fn packet_source(packet: &Packet) -> Option<NodeId> {
+    match &packet.pack_type {
+        PacketType::FloodRequest(flood_request) => Some(flood_request.initiator_id),
+        _ => packet.routing_header.hops.first().copied(),
+    }
+}
+
+/// Returns the final destination of the packet, if it has one
+pub fn packet_destination(packet: &Packet) -> Option<NodeId> {
+    match &packet.pack_type {
+        PacketType::FloodRequest(_) => None,
+        _ => packet.routing_header.hops.last().copied(),
+    }
+}
@@ -1,12 +1,67 @@
+mod analyzer;
+mod chaos;
+mod cli;
+mod conformance;
+mod convergence;
+mod debugger;
+mod discovery_auditor;
 mod drone_factory;
+mod event_tap;
+mod generator;
+mod importer;
+mod link_layer;
+mod metrics;
+mod network_control;
 mod network_initializer;
+mod plan;
+mod queue_monitor;
+mod route_auditor;
+mod scenario;
 mod utils;
+mod validate;
+mod watcher;
 
+use cli::Command;
+use dotenv::dotenv;
 use network_initializer::NetworkInitializer;
+use std::{env, process};
 
 fn main() {
     #![allow(warnings)]
 
-    let mut network_initializer = NetworkInitializer::new();
-    network_initializer.launch();
+    let command = match cli::parse_args(env::args().skip(1)) {
+        Ok(command) => command,
+        Err(error_message) => {
+            eprintln!("ERROR: {}\n\n{}", error_message, cli::USAGE);
+            process::exit(1);
+        }
+    };
+
+    let result = match command {
+        Command::Run { config_path, watch } => {
+            let mut network_initializer = NetworkInitializer::new(config_path, watch);
+            network_initializer.launch();
+            Ok(())
+        }
+        Command::Plan { config_path, format, output } => {
+            dotenv().ok();
+            plan::plan(&config_path, format, output.as_deref())
+        }
+        Command::Analyze { config_path, failures, delivery, json } => {
+            analyzer::analyze(&config_path, failures, delivery, json.as_deref())
+        }
+        Command::Validate { config_path, fix, output } => validate::validate(&config_path, fix, output.as_deref()),
+        Command::Import { path, format, default_pdr, output } => {
+            importer::import(&path, format, default_pdr, output.as_deref())
+        }
+        Command::Generate { shape, options, output } => generator::generate(shape, &options, output.as_deref()),
+        Command::GenerateRandom { options, count, output } => {
+            generator::generate_random(&options, count, output.as_deref())
+        }
+    };
+
+    if let Err(error_message) = result {
+        eprintln!("ERROR: {}", error_message);
+        process::exit(1);
+    }
 }
@@ -0,0 +1,235 @@
+use common_utils::HostEvent;
+use log::{error, info, warn};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use wg_2024::controller::DroneEvent;
+use wg_2024::network::NodeId;
+use wg_2024::packet::Packet;
+
+use crate::event_tap::{host_event_name, EventObserver};
+use crate::link_layer::{packet_type_name, PacketObserver};
+use crate::network_control::NetworkControl;
+
+// How long a client may take to send its request, so an idle connection does not keep its thread forever
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Drone, implementation and packet type
+type PacketKey = (NodeId, &'static str, &'static str);
+
+#[derive(Default)]
+struct Counters {
+    packets_sent: BTreeMap<PacketKey, u64>,
+    packets_dropped: BTreeMap<PacketKey, u64>,
+    controller_shortcuts: BTreeMap<PacketKey, u64>,
+    host_events: BTreeMap<(NodeId, String), u64>,
+    buffer_drops: BTreeMap<(NodeId, &'static str), u64>,
+}
+
+/// Counts the controller events and the packets dropped by full buffers, to be served in the Prometheus text format by `serve`
+#[derive(Default)]
+pub struct Metrics {
+    counters: Mutex<Counters>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Renders the counters, and the gauges of the current topology, in the Prometheus text format
+    pub fn render(&self, network: &NetworkControl) -> String {
+        let counters = self.counters.lock().unwrap();
+        let mut text = String::new();
+
+        let packet_counters = [
+            ("network_packets_sent_total", "Packets forwarded by each drone", &counters.packets_sent),
+            ("network_packets_dropped_total", "Packets dropped by each drone", &counters.packets_dropped),
+            (
+                "network_controller_shortcuts_total",
+                "Packets each drone sent through the controller",
+                &counters.controller_shortcuts,
+            ),
+        ];
+        for (name, help, samples) in packet_counters {
+            writeln!(text, "# HELP {} {}", name, help).unwrap();
+            writeln!(text, "# TYPE {} counter", name).unwrap();
+            for ((drone, implementation, packet_type), value) in samples {
+                writeln!(
+                    text,
+                    "{}{{drone=\"{}\",implementation=\"{}\",packet_type=\"{}\"}} {}",
+                    name, drone, implementation, packet_type, value
+                )
+                .unwrap();
+            }
+        }
+
+        writeln!(text, "# HELP network_host_events_total Events sent by each client and server").unwrap();
+        writeln!(text, "# TYPE network_host_events_total counter").unwrap();
+        for ((host, event), value) in &counters.host_events {
+            writeln!(text, "network_host_events_total{{host=\"{}\",event=\"{}\"}} {}", host, event, value).unwrap();
+        }
+
+        writeln!(text, "# HELP network_buffer_drops_total Packets dropped because the buffer of the node was full").unwrap();
+        writeln!(text, "# TYPE network_buffer_drops_total counter").unwrap();
+        for ((node, packet_type), value) in &counters.buffer_drops {
+            writeln!(text, "network_buffer_drops_total{{node=\"{}\",packet_type=\"{}\"}} {}", node, packet_type, value).unwrap();
+        }
+
+        let topology = network.topology();
+        writeln!(text, "# HELP network_drone_pdr Current packet drop rate of each drone").unwrap();
+        writeln!(text, "# TYPE network_drone_pdr gauge").unwrap();
+        for drone in &topology.drone {
+            writeln!(text, "network_drone_pdr{{drone=\"{}\"}} {}", drone.id, drone.pdr).unwrap();
+        }
+
+        writeln!(text, "# HELP network_nodes Nodes currently in the network").unwrap();
+        writeln!(text, "# TYPE network_nodes gauge").unwrap();
+        writeln!(text, "network_nodes{{kind=\"drone\"}} {}", topology.drone.len()).unwrap();
+        writeln!(text, "network_nodes{{kind=\"client\"}} {}", topology.client.len()).unwrap();
+        writeln!(text, "network_nodes{{kind=\"server\"}} {}", topology.server.len()).unwrap();
+
+        text
+    }
+}
+
+impl EventObserver for Metrics {
+    fn on_drone_event(&self, drone_id: NodeId, drone_type: &'static str, event: &DroneEvent) {
+        let mut counters = self.counters.lock().unwrap();
+        let (samples, packet) = match event {
+            DroneEvent::PacketSent(packet) => (&mut counters.packets_sent, packet),
+            DroneEvent::PacketDropped(packet) => (&mut counters.packets_dropped, packet),
+            DroneEvent::ControllerShortcut(packet) => (&mut counters.controller_shortcuts, packet),
+        };
+        *samples.entry((drone_id, drone_type, packet_type_name(&packet.pack_type))).or_default() += 1;
+    }
+
+    fn on_host_event(&self, host_id: NodeId, event: &HostEvent) {
+        let mut counters = self.counters.lock().unwrap();
+        *counters.host_events.entry((host_id, host_event_name(event))).or_default() += 1;
+    }
+}
+
+impl PacketObserver for Metrics {
+    fn on_packet(&self, _to: NodeId, _packet: &Packet) {}
+
+    fn on_overflow(&self, to: NodeId, packet: &Packet) {
+        let mut counters = self.counters.lock().unwrap();
+        *counters.buffer_drops.entry((to, packet_type_name(&packet.pack_type))).or_default() += 1;
+    }
+}
+
+/// Serves the metrics on `GET /metrics` at the given address, one thread per connection
+pub fn serve(address: &str, metrics: Arc<Metrics>, network: NetworkControl) -> Result<thread::JoinHandle<()>, String> {
+    let listener = TcpListener::bind(address).map_err(|e| format!("Unable to serve the metrics on {}: {}", address, e))?;
+    info!("Serving the metrics on http://{}/metrics", address);
+
+    Ok(thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let metrics = metrics.clone();
+                    let network = network.clone();
+                    thread::spawn(move || {
+                        if let Err(e) = respond(stream, &metrics, &network) {
+                            warn!("Metrics request failed: {}", e);
+                        }
+                    });
+                }
+                Err(e) => error!("Unable to accept a metrics connection: {}", e),
+            }
+        }
+    }))
+}
+
+fn respond(stream: TcpStream, metrics: &Metrics, network: &NetworkControl) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // The headers are not needed, but they must be read before answering
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (status, content_type, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", "text/plain; version=0.0.4", metrics.render(network)),
+        _ => ("404 Not Found", "text/plain", "Not found, the metrics are on /metrics\n".to_string()),
+    };
+
+    let mut stream = reader.into_inner();
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::source_map::SourceMap;
+    use crate::utils::topology::tests::config_with_links;
+    use std::collections::HashMap;
+    use wg_2024::network::SourceRoutingHeader;
+    use wg_2024::packet::{Ack, PacketType};
+
+    fn ack() -> Packet {
+        Packet {
+            routing_header: SourceRoutingHeader { hop_index: 1, hops: vec![100, 1, 200] },
+            session_id: 0,
+            pack_type: PacketType::Ack(Ack { fragment_index: 0 }),
+        }
+    }
+
+    #[test]
+    fn renders_the_prometheus_text_format() {
+        let config = config_with_links(&[(1, 0.25), (2, 0.)], &[100], &[200], &[(100, 1), (1, 2), (2, 200)]);
+        let network = NetworkControl::new(config, HashMap::new(), HashMap::new(), HashMap::new(), SourceMap::default());
+        let metrics = Metrics::new();
+        metrics.on_drone_event(1, "rustbusters", &DroneEvent::PacketSent(ack()));
+        metrics.on_drone_event(1, "rustbusters", &DroneEvent::PacketSent(ack()));
+        metrics.on_drone_event(2, "rusty_drones", &DroneEvent::ControllerShortcut(ack()));
+        metrics.on_host_event(100, &HostEvent::HostMessageSent(200));
+        metrics.on_overflow(2, &ack());
+
+        assert_eq!(
+            metrics.render(&network),
+            "\
+# HELP network_packets_sent_total Packets forwarded by each drone
+# TYPE network_packets_sent_total counter
+network_packets_sent_total{drone=\"1\",implementation=\"rustbusters\",packet_type=\"Ack\"} 2
+# HELP network_packets_dropped_total Packets dropped by each drone
+# TYPE network_packets_dropped_total counter
+# HELP network_controller_shortcuts_total Packets each drone sent through the controller
+# TYPE network_controller_shortcuts_total counter
+network_controller_shortcuts_total{drone=\"2\",implementation=\"rusty_drones\",packet_type=\"Ack\"} 1
+# HELP network_host_events_total Events sent by each client and server
+# TYPE network_host_events_total counter
+network_host_events_total{host=\"100\",event=\"HostMessageSent\"} 1
+# HELP network_buffer_drops_total Packets dropped because the buffer of the node was full
+# TYPE network_buffer_drops_total counter
+network_buffer_drops_total{node=\"2\",packet_type=\"Ack\"} 1
+# HELP network_drone_pdr Current packet drop rate of each drone
+# TYPE network_drone_pdr gauge
+network_drone_pdr{drone=\"1\"} 0.25
+network_drone_pdr{drone=\"2\"} 0
+# HELP network_nodes Nodes currently in the network
+# TYPE network_nodes gauge
+network_nodes{kind=\"drone\"} 2
+network_nodes{kind=\"client\"} 1
+network_nodes{kind=\"server\"} 1
+"
+        );
+    }
+}
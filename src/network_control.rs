@@ -0,0 +1,474 @@
+use common_utils::{ClientToServerMessage, HostCommand, HostMessage, MessageBody, MessageContent};
+use crossbeam_channel::{Receiver, Sender};
+use log::{debug, info, warn};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+use wg_2024::config::{Client, Config, Drone, Server};
+use wg_2024::controller::DroneCommand;
+use wg_2024::network::NodeId;
+use wg_2024::packet::Packet;
+
+use crate::drone_factory::DroneFactory;
+use crate::event_tap::EventTap;
+use crate::link_layer::LinkLayer;
+use crate::network_initializer::{spawn_client, spawn_drone, spawn_server};
+use crate::utils::input_validator;
+use crate::utils::source_map::SourceMap;
+use crate::utils::topology::{self, Mutation};
+
+/// Handle used to change the running network without going through the simulation controller.
+/// It keeps track of the current topology, so every change is applied both to the nodes
+/// (through the `DroneCommand` and `HostCommand` channels) and to the tracked `Config`.
+/// After every change the simulation controller is sent the new topology.
+#[derive(Clone)]
+pub struct NetworkControl {
+    topology: Arc<Mutex<Config>>,
+    channels: Arc<Mutex<NodeChannels>>,
+    /// Where the initial topology is written, used to point refused changes at the topology file
+    sources: Arc<SourceMap>,
+    spawner: Option<Arc<Mutex<NodeSpawner>>>,
+    controller_updates: Option<Sender<Config>>,
+}
+
+struct NodeChannels {
+    drone_commands: HashMap<NodeId, Sender<DroneCommand>>,
+    host_commands: HashMap<NodeId, Sender<HostCommand>>,
+    node_senders: HashMap<NodeId, Sender<Packet>>,
+}
+
+/// What is needed to spawn new nodes while the network is running
+pub struct NodeSpawner {
+    pub link_layer: LinkLayer,
+    pub event_tap: EventTap,
+    pub drone_factories: Arc<Vec<DroneFactory>>,
+    pub next_factory: usize,
+    pub server_controller_sender: Option<Sender<HostCommand>>,
+}
+
+impl NetworkControl {
+    pub fn new(
+        config: Config,
+        drone_commands: HashMap<NodeId, Sender<DroneCommand>>,
+        host_commands: HashMap<NodeId, Sender<HostCommand>>,
+        node_senders: HashMap<NodeId, Sender<Packet>>,
+        sources: SourceMap,
+    ) -> Self {
+        Self {
+            topology: Arc::new(Mutex::new(config)),
+            channels: Arc::new(Mutex::new(NodeChannels {
+                drone_commands,
+                host_commands,
+                node_senders,
+            })),
+            sources: Arc::new(sources),
+            spawner: None,
+            controller_updates: None,
+        }
+    }
+
+    /// Allows the network control to spawn new nodes
+    pub fn with_spawner(mut self, spawner: NodeSpawner) -> Self {
+        self.spawner = Some(Arc::new(Mutex::new(spawner)));
+        self
+    }
+
+    /// Sends the topology to the simulation controller after every change
+    pub fn with_controller_updates(mut self, controller_updates: Sender<Config>) -> Self {
+        self.controller_updates = Some(controller_updates);
+        self
+    }
+
+    /// Returns a snapshot of the current topology
+    pub fn topology(&self) -> Config {
+        self.topology.lock().unwrap().clone()
+    }
+
+    /// Applies the mutation to the network, without checking it
+    pub fn apply(&self, mutation: &Mutation) -> Result<(), String> {
+        let mut current = self.topology.lock().unwrap();
+        self.apply_to(&mut current, mutation)
+    }
+
+    /// Applies the mutation to the network only if the resulting topology is still valid
+    /// (see `input_validator::validate_mutation`). A refused change is rendered like the errors
+    /// of the topology file, with a snippet of the nodes it refers to.
+    pub fn apply_validated(&self, mutation: &Mutation) -> Result<(), String> {
+        let mut current = self.topology.lock().unwrap();
+        input_validator::validate_mutation(&current, mutation).map_err(|error| self.sources.render(&error))?;
+        self.apply_to(&mut current, mutation)
+    }
+
+    /// Checks the mutation against the current topology without applying it
+    pub fn check(&self, mutation: &Mutation) -> Result<(), String> {
+        let current = self.topology.lock().unwrap();
+        input_validator::validate_mutation(&current, mutation).map_err(|error| self.sources.render(&error))
+    }
+
+    fn apply_to(&self, current: &mut Config, mutation: &Mutation) -> Result<(), String> {
+        // The neighbours of a crashed drone must be known before it is removed from the topology
+        let neighbours = match mutation {
+            Mutation::Crash(id) => topology::neighbours(current, *id).cloned().unwrap_or_default(),
+            _ => Vec::new(),
+        };
+        topology::apply_mutation(current, mutation)?;
+
+        // Every command is sent even if one fails (e.g. a neighbour that crashed in the meantime),
+        // so the live network stays as close as possible to the tracked topology
+        let results = match *mutation {
+            Mutation::Crash(drone_id) => {
+                // Removes the crashed drone from its neighbours and then crashes it
+                let mut results: Vec<Result<(), String>> =
+                    neighbours.iter().map(|neighbour| self.remove_sender(*neighbour, drone_id)).collect();
+                results.push(self.send_drone_command(drone_id, DroneCommand::Crash));
+                results
+            }
+            Mutation::SetPdr(drone_id, pdr) => {
+                vec![self.send_drone_command(drone_id, DroneCommand::SetPacketDropRate(pdr))]
+            }
+            Mutation::AddLink(a, b) => vec![self.add_sender(a, b), self.add_sender(b, a)],
+            Mutation::RemoveLink(a, b) => vec![self.remove_sender(a, b), self.remove_sender(b, a)],
+        };
+
+        // The tracked topology changed even if some command failed
+        self.notify_controller(current);
+
+        let errors: Vec<String> = results.into_iter().filter_map(Result::err).collect();
+        if !errors.is_empty() {
+            return Err(format!("\"{}\" was only partially applied: {}", mutation, errors.join("; ")));
+        }
+        info!("Network changed: {}", mutation);
+        Ok(())
+    }
+
+    /// Asks a client to send a text message to another client through a server
+    pub fn send_message(&self, from: NodeId, to: NodeId, server: NodeId, text: &str) -> Result<(), String> {
+        let current = self.topology.lock().unwrap();
+        if !topology::is_client(&current, from) || !topology::is_client(&current, to) {
+            return Err(format!("Messages can only be sent between clients! From {} to {}", from, to));
+        }
+        if !topology::is_server(&current, server) {
+            return Err(format!("Node {} is not a server", server));
+        }
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let message = HostMessage::FromClient(ClientToServerMessage::SendPrivateMessage {
+            recipient_id: to,
+            message: MessageBody {
+                sender_id: from,
+                content: MessageContent::Text(text.to_string()),
+                timestamp: timestamp.to_string(),
+            },
+        });
+        self.send_host_command(from, HostCommand::SendMessage(server, message))?;
+        info!("Client {} asked to send \"{}\" to client {} via server {}", from, text, to, server);
+        Ok(())
+    }
+
+    /// Spawns a new drone and links it to its (existing) neighbours
+    pub fn spawn_drone(&self, drone: &Drone) -> Result<(), String> {
+        let spawner = self.spawner()?;
+        self.check_new_id(drone.id)?;
+
+        let (command_sender, event_receiver, drone_type) = {
+            let mut spawner = spawner.lock().unwrap();
+            if spawner.drone_factories.is_empty() {
+                return Err("There are no drone implementations to spawn".to_string());
+            }
+
+            let (packet_sender, packet_recv) = spawner.link_layer.open_link(drone.id);
+            self.channels.lock().unwrap().node_senders.insert(drone.id, packet_sender);
+
+            let create_drone = &spawner.drone_factories[spawner.next_factory];
+            let unlinked_drone = Drone {
+                id: drone.id,
+                connected_node_ids: Vec::new(),
+                pdr: drone.pdr,
+            };
+            let (command_sender, event_receiver, drone_type, _) =
+                spawn_drone(create_drone, &unlinked_drone, packet_recv, HashMap::new(), &spawner.event_tap);
+            spawner.next_factory = (spawner.next_factory + 1) % spawner.drone_factories.len();
+            let event_receiver = spawner.event_tap.tap_drone(drone.id, drone_type, event_receiver);
+
+            (command_sender, event_receiver, drone_type)
+        };
+
+        self.channels.lock().unwrap().drone_commands.insert(drone.id, command_sender);
+        drain_events(drone.id, event_receiver);
+        // The drone already has its PDR, the command lets the observers of the command channels know it
+        self.send_drone_command(drone.id, DroneCommand::SetPacketDropRate(drone.pdr))?;
+        self.add_node(|current| current.drone.push(Drone { id: drone.id, connected_node_ids: Vec::new(), pdr: drone.pdr }));
+        info!("Drone {} ({}) spawned", drone.id, drone_type);
+
+        self.link_new_node(drone.id, &drone.connected_node_ids)
+    }
+
+    /// Spawns a new client and links it to its (existing) drones
+    pub fn spawn_client(&self, client: &Client) -> Result<(), String> {
+        let spawner = self.spawner()?;
+        self.check_new_id(client.id)?;
+
+        let (packet_sender, packet_recv, event_tap) = {
+            let spawner = spawner.lock().unwrap();
+            let (packet_sender, packet_recv) = spawner.link_layer.open_link(client.id);
+            (packet_sender, packet_recv, spawner.event_tap.clone())
+        };
+        let (command_sender, event_receiver, _) = spawn_client(client.id, packet_recv, HashMap::new());
+        let event_receiver = event_tap.tap_host(client.id, event_receiver);
+        self.register_host(client.id, packet_sender, command_sender);
+        drain_events(client.id, event_receiver);
+
+        self.add_node(|current| current.client.push(Client { id: client.id, connected_drone_ids: Vec::new() }));
+        info!("Client {} spawned", client.id);
+
+        self.link_new_node(client.id, &client.connected_drone_ids)
+    }
+
+    /// Spawns a new server and links it to its (existing) drones
+    pub fn spawn_server(&self, server: &Server) -> Result<(), String> {
+        let spawner = self.spawner()?;
+        self.check_new_id(server.id)?;
+
+        let (packet_sender, packet_recv, server_controller_sender, event_tap) = {
+            let spawner = spawner.lock().unwrap();
+            let server_controller_sender = spawner
+                .server_controller_sender
+                .clone()
+                .ok_or("The server controller is not running".to_string())?;
+            let (packet_sender, packet_recv) = spawner.link_layer.open_link(server.id);
+            (packet_sender, packet_recv, server_controller_sender, spawner.event_tap.clone())
+        };
+        let (command_sender, event_receiver, _) =
+            spawn_server(server.id, packet_recv, HashMap::new(), server_controller_sender);
+        let event_receiver = event_tap.tap_host(server.id, event_receiver);
+        self.register_host(server.id, packet_sender, command_sender);
+        drain_events(server.id, event_receiver);
+
+        self.add_node(|current| current.server.push(Server { id: server.id, connected_drone_ids: Vec::new() }));
+        info!("Server {} spawned", server.id);
+
+        self.link_new_node(server.id, &server.connected_drone_ids)
+    }
+
+    /// Unlinks a client or a server from its drones and stops tracking it.
+    /// The host keeps running, but no packet can reach it anymore.
+    pub fn remove_host(&self, host_id: NodeId) -> Result<(), String> {
+        let neighbours = {
+            let current = self.topology.lock().unwrap();
+            if topology::is_drone(&current, host_id) {
+                return Err(format!("Node {} is a drone, crash it instead", host_id));
+            }
+            topology::neighbours(&current, host_id)
+                .cloned()
+                .ok_or(format!("Host {} does not exist", host_id))?
+        };
+
+        for neighbour in neighbours {
+            self.apply(&Mutation::RemoveLink(host_id, neighbour))?;
+        }
+
+        let mut current = self.topology.lock().unwrap();
+        current.client.retain(|c| c.id != host_id);
+        current.server.retain(|s| s.id != host_id);
+        let mut channels = self.channels.lock().unwrap();
+        channels.host_commands.remove(&host_id);
+        channels.node_senders.remove(&host_id);
+        drop(channels);
+        self.notify_controller(&current);
+        info!("Host {} removed", host_id);
+        Ok(())
+    }
+
+    fn spawner(&self) -> Result<Arc<Mutex<NodeSpawner>>, String> {
+        self.spawner
+            .clone()
+            .ok_or("This network control cannot spawn new nodes".to_string())
+    }
+
+    fn check_new_id(&self, id: NodeId) -> Result<(), String> {
+        if topology::neighbours(&self.topology.lock().unwrap(), id).is_some() {
+            Err(format!("There is already a node with Id {}", id))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn register_host(&self, host_id: NodeId, packet_sender: Sender<Packet>, command_sender: Sender<HostCommand>) {
+        let mut channels = self.channels.lock().unwrap();
+        channels.node_senders.insert(host_id, packet_sender);
+        channels.host_commands.insert(host_id, command_sender);
+    }
+
+    // Adds a freshly spawned (and still unlinked) node to the tracked topology
+    fn add_node(&self, add: impl FnOnce(&mut Config)) {
+        let mut current = self.topology.lock().unwrap();
+        add(&mut current);
+        self.notify_controller(&current);
+    }
+
+    // Links a freshly spawned node to the neighbours that are already in the network
+    fn link_new_node(&self, id: NodeId, neighbours: &[NodeId]) -> Result<(), String> {
+        for neighbour in neighbours {
+            if topology::neighbours(&self.topology.lock().unwrap(), *neighbour).is_some() {
+                self.apply(&Mutation::AddLink(id, *neighbour))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn notify_controller(&self, current: &Config) {
+        if let Some(controller_updates) = &self.controller_updates {
+            if controller_updates.send(current.clone()).is_err() {
+                debug!("The simulation controller is not listening for topology updates anymore");
+            }
+        }
+    }
+
+    fn add_sender(&self, node_id: NodeId, neighbour: NodeId) -> Result<(), String> {
+        let channels = self.channels.lock().unwrap();
+        let sender = channels
+            .node_senders
+            .get(&neighbour)
+            .ok_or(format!("There is no channel for node {}", neighbour))?
+            .clone();
+        let is_drone = channels.drone_commands.contains_key(&node_id);
+        drop(channels);
+
+        if is_drone {
+            self.send_drone_command(node_id, DroneCommand::AddSender(neighbour, sender))
+        } else {
+            self.send_host_command(node_id, HostCommand::AddSender(neighbour, sender))
+        }
+    }
+
+    fn remove_sender(&self, node_id: NodeId, neighbour: NodeId) -> Result<(), String> {
+        let is_drone = self.channels.lock().unwrap().drone_commands.contains_key(&node_id);
+        if is_drone {
+            self.send_drone_command(node_id, DroneCommand::RemoveSender(neighbour))
+        } else {
+            self.send_host_command(node_id, HostCommand::RemoveSender(neighbour))
+        }
+    }
+
+    fn send_drone_command(&self, drone_id: NodeId, command: DroneCommand) -> Result<(), String> {
+        match self.channels.lock().unwrap().drone_commands.get(&drone_id) {
+            Some(sender) => sender.send(command).map_err(|_| {
+                warn!("Drone {} is not listening for commands anymore", drone_id);
+                format!("Unable to send a command to drone {}", drone_id)
+            }),
+            None => Err(format!("Drone {} does not exist", drone_id)),
+        }
+    }
+
+    fn send_host_command(&self, host_id: NodeId, command: HostCommand) -> Result<(), String> {
+        match self.channels.lock().unwrap().host_commands.get(&host_id) {
+            Some(sender) => sender.send(command).map_err(|_| {
+                warn!("Host {} is not listening for commands anymore", host_id);
+                format!("Unable to send a command to host {}", host_id)
+            }),
+            None => Err(format!("Host {} does not exist", host_id)),
+        }
+    }
+}
+
+// Nodes spawned at runtime have no event channel in the simulation controller,
+// so their events are only consumed here (after the event tap) to keep their channels from growing
+fn drain_events<E: Send + 'static>(node_id: NodeId, event_receiver: Receiver<E>) {
+    thread::spawn(move || {
+        let count = event_receiver.iter().count();
+        debug!("Node {} stopped sending events after {} events", node_id, count);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::topology::tests::config_with_links;
+    use crossbeam_channel::unbounded;
+
+    struct TestNetwork {
+        network: NetworkControl,
+        updates: Receiver<Config>,
+        drones: Vec<Receiver<DroneCommand>>,
+        // Bound by the tests to leave the command channels of the hosts open
+        _hosts: Vec<Receiver<HostCommand>>,
+    }
+
+    // Client 100 and server 200 joined by drones 1 and 2
+    fn network() -> TestNetwork {
+        let config = config_with_links(&[(1, 0.), (2, 0.)], &[100], &[200], &[(100, 1), (100, 2), (1, 200), (2, 200)]);
+        let (drone_commands, drone_receivers): (HashMap<_, _>, Vec<_>) = [1, 2]
+            .into_iter()
+            .map(|id| {
+                let (sender, receiver) = unbounded();
+                ((id, sender), receiver)
+            })
+            .unzip();
+        let (host_commands, host_receivers): (HashMap<_, _>, Vec<_>) = [100, 200]
+            .into_iter()
+            .map(|id| {
+                let (sender, receiver) = unbounded();
+                ((id, sender), receiver)
+            })
+            .unzip();
+        let node_senders = [1, 2, 100, 200].into_iter().map(|id| (id, unbounded().0)).collect();
+        let (updates_sender, updates) = unbounded();
+
+        let network = NetworkControl::new(config, drone_commands, host_commands, node_senders, SourceMap::default())
+            .with_controller_updates(updates_sender);
+        TestNetwork { network, updates, drones: drone_receivers, _hosts: host_receivers }
+    }
+
+    #[test]
+    fn the_controller_gets_the_topology_after_each_change() {
+        let TestNetwork { network, updates, drones: _drones, _hosts } = network();
+
+        network.apply(&Mutation::SetPdr(1, 0.4)).unwrap();
+        network.apply(&Mutation::RemoveLink(100, 2)).unwrap();
+
+        let first = updates.try_recv().unwrap();
+        assert_eq!(first.drone[0].pdr, 0.4);
+        assert!(topology::linked(&first, 100, 2));
+        assert!(!topology::linked(&updates.try_recv().unwrap(), 100, 2));
+        assert!(updates.try_recv().is_err());
+    }
+
+    #[test]
+    fn refused_changes_are_not_sent_to_the_controller() {
+        let TestNetwork { network, updates, drones: _drones, _hosts } = network();
+
+        assert!(network.apply_validated(&Mutation::SetPdr(1, 2.)).is_err());
+        assert!(updates.try_recv().is_err());
+    }
+
+    #[test]
+    fn removed_hosts_are_unlinked_and_forgotten() {
+        let TestNetwork { network, updates, drones, _hosts } = network();
+
+        network.remove_host(100).unwrap();
+
+        let current = network.topology();
+        assert!(current.client.is_empty());
+        assert!(!topology::linked(&current, 100, 1) && !topology::linked(&current, 100, 2));
+        assert!(matches!(drones[0].try_recv(), Ok(DroneCommand::RemoveSender(100))));
+        assert!(matches!(drones[1].try_recv(), Ok(DroneCommand::RemoveSender(100))));
+        // One update per removed link, and one for the host itself
+        let sent: Vec<Config> = updates.try_iter().collect();
+        assert_eq!(sent.len(), 3);
+        assert!(sent[2].client.is_empty());
+
+        assert!(network.remove_host(1).is_err());
+        assert!(network.remove_host(100).is_err());
+    }
+
+    #[test]
+    fn nodes_cannot_be_spawned_without_a_spawner() {
+        let TestNetwork { network, .. } = network();
+        let drone = Drone { id: 3, connected_node_ids: vec![1], pdr: 0. };
+        assert_eq!(network.spawn_drone(&drone), Err("This network control cannot spawn new nodes".to_string()));
+    }
+}
@@ -8,15 +8,31 @@ use server::{RustBustersServer, RustBustersServerController};
 use simulation_controller::RustBustersSimulationController;
 use std::collections::HashMap;
 use std::net::Ipv4Addr;
-use std::{env, fs, thread};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::{env, thread};
 use wg_2024::config::Config;
 use wg_2024::controller::{DroneCommand, DroneEvent};
 use wg_2024::drone::Drone;
 use wg_2024::network::NodeId;
-use wg_2024::packet::Packet;
+use wg_2024::packet::{FloodResponse, Packet};
 
+use crate::chaos::ChaosEngine;
+use crate::conformance::{ConformanceMonitor, DropEvent};
+use crate::convergence::{ConvergenceMonitor, FloodEvent};
+use crate::debugger::PacketDebugger;
+use crate::discovery_auditor::DiscoveryAuditor;
 use crate::drone_factory::{DroneFactory, DroneRunnable};
-use crate::{drone_factories, utils};
+use crate::event_tap::EventTap;
+use crate::link_layer::LinkLayer;
+use crate::metrics::Metrics;
+use crate::network_control::{NetworkControl, NodeSpawner};
+use crate::queue_monitor::{EventQueueLen, QueueMonitor, SentCounter};
+use crate::route_auditor::RouteAuditor;
+use crate::scenario::Scenario;
+use crate::utils::buffers::BufferSettings;
+use crate::utils::source_map::SourceMap;
+use crate::{conformance, convergence, debugger, discovery_auditor, drone_factories, drone_type_names, metrics, route_auditor, utils, watcher};
 use dotenv::dotenv;
 
 // DRONES
@@ -32,6 +48,52 @@ use wg_2024_rust::drone::RustDrone;
 use RF_drone::RustAndFurious;
 use rustbusters_drone::RustBustersDrone;
 
+/// Expands the given macro with the list of drone implementations to use:
+/// only our drone in RustBusters mode, otherwise the drones of the other groups.
+macro_rules! with_drone_implementations {
+    ($rustbusters_mode:expr, $macro_name:ident) => {
+        if $rustbusters_mode {
+            $macro_name![
+                RustBustersDrone
+            ]
+        } else {
+            $macro_name![
+                RustyDrone,
+                LockheedRustin,
+                FungiDrone,
+                RustasticDrone,
+                RustezeDrone,
+                RustDoIt,
+                RustRoveri,
+                RustAndFurious,
+                CppEnjoyersDrone,
+                RustDrone,
+            ]
+        }
+    };
+}
+
+/// Reads from the .env file which type of drones will be used
+pub fn rustbusters_mode() -> bool {
+    env::var("RUSTBUSTERS_MODE")
+        .expect("RUSTBUSTERS_MODE must be set in .env file")
+        .parse::<bool>()
+        .expect("RUSTBUSTERS_MODE must be a valid boolean value")
+}
+
+/// Returns the drone implementation that will be assigned to each drone of the config.
+/// Implementations are assigned round-robin, in the order the drones appear in the config.
+pub fn assign_drone_types(config: &Config, rustbusters_mode: bool) -> HashMap<NodeId, &'static str> {
+    let drone_types: Vec<&'static str> = with_drone_implementations!(rustbusters_mode, drone_type_names);
+
+    config
+        .drone
+        .iter()
+        .zip(drone_types.iter().cycle())
+        .map(|(drone, drone_type)| (drone.id, *drone_type))
+        .collect()
+}
+
 pub struct NetworkInitializer {
     drone_ids: Vec<NodeId>,
     client_ids: Vec<NodeId>,
@@ -42,11 +104,27 @@ pub struct NetworkInitializer {
     server_controller_channels: HashMap<NodeId, (Sender<HostCommand>, Receiver<HostEvent>)>,
     handles: Vec<thread::JoinHandle<()>>,
     config: Option<Config>,
+    source_map: Option<SourceMap>,
     drone_groups: HashMap<NodeId, String>,
+    link_layer: LinkLayer,
+    event_tap: EventTap,
+    drone_factories: Arc<Vec<DroneFactory>>,
+    next_factory: usize,
+    server_controller_sender: Option<Sender<HostCommand>>,
+    network_control: Option<NetworkControl>,
+    topology_updates: Option<Receiver<Config>>,
+    audited_routes: Option<Receiver<Vec<NodeId>>>,
+    flood_responses: Option<Receiver<(NodeId, FloodResponse)>>,
+    flood_events: Option<(Receiver<FloodEvent>, Instant)>,
+    metrics: Option<(String, Arc<Metrics>)>,
+    sent_counter: Option<Arc<SentCounter>>,
+    drop_events: Option<Receiver<(Instant, DropEvent)>>,
+    config_path: String,
+    watch: bool,
 }
 
 impl NetworkInitializer {
-    pub fn new() -> Self {
+    pub fn new(config_path: String, watch: bool) -> Self {
         Self {
             drone_ids: Vec::new(),
             client_ids: Vec::new(),
@@ -57,17 +135,50 @@ impl NetworkInitializer {
             server_controller_channels: HashMap::new(),
             handles: Vec::new(),
             config: None,
+            source_map: None,
             drone_groups: HashMap::new(),
+            link_layer: LinkLayer::new(),
+            event_tap: EventTap::new(),
+            drone_factories: Arc::new(Vec::new()),
+            next_factory: 0,
+            server_controller_sender: None,
+            network_control: None,
+            topology_updates: None,
+            audited_routes: None,
+            flood_responses: None,
+            flood_events: None,
+            metrics: None,
+            sent_counter: None,
+            drop_events: None,
+            config_path,
+            watch,
         }
     }
 
     pub fn launch(mut self) {
         self.config();
 
+        self.init_debugger();
+        self.init_route_auditor();
+        self.init_discovery_auditor();
+        self.init_convergence_monitor();
+        self.init_metrics();
+        self.init_queue_monitor();
+        self.init_conformance_monitor();
         self.init_communication();
         self.launch_drones();
         self.launch_clients();
         self.launch_servers();
+        self.init_network_control();
+        self.launch_scenario();
+        self.launch_chaos();
+        self.launch_watcher();
+        self.launch_route_auditor();
+        self.launch_discovery_auditor();
+        self.launch_convergence_monitor();
+        self.launch_metrics();
+        self.launch_queue_monitor();
+        self.launch_conformance_monitor();
         self.launch_simulation_controller();
     }
 
@@ -75,16 +186,131 @@ impl NetworkInitializer {
         env_logger::init();
         dotenv().ok();
 
-        let config_data = fs::read_to_string("input.toml").expect("Unable to read config file");
-        let config: Config = toml::from_str(&config_data).expect("Unable to parse TOML");
+        let (config, source_map) = match utils::config_loader::load_config_with_sources(&self.config_path) {
+            Ok(loaded) => loaded,
+            Err(error_message) => {
+                error!("{}", error_message);
+                println!("ERROR: {}", error_message);
+                return;
+            }
+        };
+        let buffers = match BufferSettings::load(&self.config_path)
+            .and_then(|buffers| buffers.check_nodes(&config).map(|_| buffers))
+        {
+            Ok(buffers) => buffers,
+            Err(error_message) => {
+                error!("{}", error_message);
+                println!("ERROR: {}", error_message);
+                return;
+            }
+        };
+
+        if let Some(capacity) = buffers.capacity {
+            info!("Node buffers bounded to {} packets ({:?} on overflow)", capacity, buffers.overflow);
+        }
+        self.link_layer.set_buffers(buffers);
+        self.config = Some(config);
+        self.source_map = Some(source_map);
+    }
+
+    fn init_debugger(&mut self) {
+        // The packet debugger is optional and disabled by default
+        let enabled = env::var("PACKET_DEBUGGER")
+            .map(|value| value.parse::<bool>().expect("PACKET_DEBUGGER must be a valid boolean value"))
+            .unwrap_or(false);
+
+        if enabled && self.config.is_some() {
+            info!("Packet debugger enabled, the network starts paused");
+            let packet_debugger = Arc::new(PacketDebugger::new(true));
+            self.link_layer.add_observer(packet_debugger.clone());
+            debugger::spawn_console(packet_debugger, self.link_layer.clone());
+        }
+    }
+
+    fn init_route_auditor(&mut self) {
+        // The route auditor is optional and disabled by default
+        let enabled = env::var("ROUTE_AUDIT")
+            .map(|value| value.parse::<bool>().expect("ROUTE_AUDIT must be a valid boolean value"))
+            .unwrap_or(false);
+
+        if enabled && self.config.is_some() {
+            info!("Route audit enabled");
+            let (route_auditor, routes) = RouteAuditor::new();
+            self.link_layer.add_observer(route_auditor);
+            self.audited_routes = Some(routes);
+        }
+    }
+
+    fn init_discovery_auditor(&mut self) {
+        // The discovery auditor is optional and disabled by default
+        let enabled = env::var("DISCOVERY_AUDIT")
+            .map(|value| value.parse::<bool>().expect("DISCOVERY_AUDIT must be a valid boolean value"))
+            .unwrap_or(false);
+
+        if enabled && self.config.is_some() {
+            info!("Discovery audit enabled");
+            let (discovery_auditor, responses) = DiscoveryAuditor::new();
+            self.link_layer.add_observer(discovery_auditor);
+            self.flood_responses = Some(responses);
+        }
+    }
+
+    fn init_convergence_monitor(&mut self) {
+        // The convergence monitor is optional and disabled by default
+        let enabled = env::var("CONVERGENCE_MONITOR")
+            .map(|value| value.parse::<bool>().expect("CONVERGENCE_MONITOR must be a valid boolean value"))
+            .unwrap_or(false);
+
+        if enabled && self.config.is_some() {
+            info!("Convergence monitor enabled");
+            let (convergence_monitor, events) = ConvergenceMonitor::new();
+            self.link_layer.add_observer(convergence_monitor.clone());
+            self.event_tap.add_observer(convergence_monitor);
+            // The nodes are launched right after, so this is when discovery can start
+            self.flood_events = Some((events, Instant::now()));
+        }
+    }
 
-        if let Err(error_message) = utils::input_validator::validate_config(&config) {
-            error!("{}", error_message);
-            println!("ERROR: {}", error_message);
+    fn init_metrics(&mut self) {
+        // The metrics endpoint is enabled only when an address is given
+        let Ok(address) = env::var("METRICS_ADDRESS") else { return };
+        if address.is_empty() || self.config.is_none() {
             return;
         }
 
-        self.config = Some(config);
+        let metrics = Arc::new(Metrics::new());
+        self.event_tap.add_observer(metrics.clone());
+        self.link_layer.add_observer(metrics.clone());
+        self.metrics = Some((address, metrics));
+    }
+
+    fn init_queue_monitor(&mut self) {
+        // The queue monitor is optional and disabled by default
+        let enabled = env::var("QUEUE_MONITOR")
+            .map(|value| value.parse::<bool>().expect("QUEUE_MONITOR must be a valid boolean value"))
+            .unwrap_or(false);
+
+        if enabled && self.config.is_some() {
+            // The packets sent by each node are counted on the links, so they must be observed from the start
+            let sent_counter = Arc::new(SentCounter::new());
+            self.link_layer.add_observer(sent_counter.clone());
+            self.sent_counter = Some(sent_counter);
+        }
+    }
+
+    fn init_conformance_monitor(&mut self) {
+        // The PDR conformance monitor is optional and disabled by default
+        let enabled = env::var("PDR_CONFORMANCE")
+            .map(|value| value.parse::<bool>().expect("PDR_CONFORMANCE must be a valid boolean value"))
+            .unwrap_or(false);
+
+        if enabled && self.config.is_some() {
+            info!("PDR conformance monitor enabled");
+            let (conformance_monitor, events) = ConformanceMonitor::new();
+            self.event_tap.add_observer(conformance_monitor.clone());
+            self.link_layer.add_observer(conformance_monitor);
+            self.drop_events = Some(events);
+        }
     }
 
     fn init_communication(&mut self) {
@@ -92,7 +318,7 @@ impl NetworkInitializer {
             // Crossbeam channels for each drone
             info!("Initializing communication for nodes");
             for drone in &config.drone {
-                let (sender, receiver) = unbounded();
+                let (sender, receiver) = self.link_layer.open_link(drone.id);
                 self.intra_node_channels
                     .insert(drone.id, (sender, receiver));
                 self.drone_ids.push(drone.id);
@@ -100,7 +326,7 @@ impl NetworkInitializer {
 
             // Crossbeam channels for each client
             for client in &config.client {
-                let (sender, receiver) = unbounded();
+                let (sender, receiver) = self.link_layer.open_link(client.id);
                 self.intra_node_channels
                     .insert(client.id, (sender, receiver));
                 self.client_ids.push(client.id);
@@ -108,7 +334,7 @@ impl NetworkInitializer {
 
             // Crossbeam channels for each server
             for server in &config.server {
-                let (sender, receiver) = unbounded();
+                let (sender, receiver) = self.link_layer.open_link(server.id);
                 self.intra_node_channels
                     .insert(server.id, (sender, receiver));
                 self.server_ids.push(server.id);
@@ -121,86 +347,37 @@ impl NetworkInitializer {
             // Set up each drone
             info!("Creating and spawning Drones");
 
-            // Ask which type of drones will be used
-            let mode = env::var("RUSTBUSTERS_MODE")
-                .expect("RUSTBUSTERS_MODE must be set in .env file")
-                .parse::<bool>()
-                .expect("RUSTBUSTERS_MODE must be a valid boolean value");
-
-            let drone_factories: Vec<DroneFactory> = if mode {
-                drone_factories![
-                    RustBustersDrone
-                ]
-            } else {
-                drone_factories![
-                    RustyDrone,
-                    LockheedRustin,
-                    FungiDrone,
-                    RustasticDrone,
-                    RustezeDrone,
-                    RustDoIt,
-                    RustRoveri,
-                    RustAndFurious,
-                    CppEnjoyersDrone,
-                    RustDrone,
-                ]
-            };
+            let drone_factories: Vec<DroneFactory> = with_drone_implementations!(rustbusters_mode(), drone_factories);
 
             let mut factory_index = 0;
 
             for drone in config.drone.clone() {
-                // Channels for communication between the drone and the simulation controller
-                let (controller_to_drone_sender, drone_from_controller_receiver) = unbounded();
-                let (drone_to_controller_sender, controller_from_drone_receiver) = unbounded();
-
-                self.drone_controller_channels.insert(
-                    drone.id,
-                    (controller_to_drone_sender, controller_from_drone_receiver),
-                );
-
                 // Set the channels for the communication between the nodes
                 let packet_recv = self.intra_node_channels.get(&drone.id).unwrap().1.clone();
                 let mut packet_send = HashMap::new();
 
-                for neighbour in drone.connected_node_ids {
+                for neighbour in &drone.connected_node_ids {
                     packet_send.insert(
-                        neighbour,
-                        self.intra_node_channels.get(&neighbour).unwrap().0.clone(),
+                        *neighbour,
+                        self.intra_node_channels.get(neighbour).unwrap().0.clone(),
                     );
                 }
 
                 let create_drone = &drone_factories[factory_index];
                 factory_index = (factory_index + 1) % drone_factories.len();
 
-                let mut new_drone = create_drone(
-                    drone.id,
-                    drone_to_controller_sender, // The drone can send events here
-                    drone_from_controller_receiver, // The drone receives commands here
-                    packet_recv,
-                    packet_send,
-                    drone.pdr,
-                );
-
-                if new_drone.drone_type() == "FungiDrone" {
-                    let fungi_drone = new_drone.as_any_mut().downcast_mut::<FungiDrone>().unwrap();
-                    fungi_drone.set_debug_print();     // Enables Debug Print
-                    fungi_drone.disable_request_log(); // Disables Flood Request Log
-                }
-                else if new_drone.drone_type() == "RustezeDrone" {
-                    let rusteze_drone = new_drone.as_any_mut().downcast_mut::<RustezeDrone>().unwrap();
-                    rusteze_drone.with_all(); // Enable all levels
-                }
-
-                self.drone_groups.insert(drone.id, new_drone.drone_type().to_owned());
-                info!("Type of Drone {}: {}", drone.id, new_drone.drone_type());
-
-                let handle = thread::spawn(move || {
-                    let mut current_drone = new_drone;
-                    current_drone.run();
-                });
+                let (command_sender, event_receiver, drone_type, handle) =
+                    spawn_drone(create_drone, &drone, packet_recv, packet_send, &self.event_tap);
+                let event_receiver = self.event_tap.tap_drone(drone.id, drone_type, event_receiver);
 
+                self.drone_controller_channels
+                    .insert(drone.id, (command_sender, event_receiver));
+                self.drone_groups.insert(drone.id, drone_type.to_owned());
                 self.handles.push(handle);
             }
+
+            self.drone_factories = Arc::new(drone_factories);
+            self.next_factory = factory_index;
         }
     }
 
@@ -209,15 +386,6 @@ impl NetworkInitializer {
             // Set up each client
             info!("Creating and spawning Clients");
             for client in config.client.clone() {
-                // Channels for communication between the client and the simulation controller
-                let (controller_to_client_sender, client_from_controller_receiver) = unbounded();
-                let (client_to_controller_sender, controller_from_client_receiver) = unbounded();
-
-                self.client_controller_channels.insert(
-                    client.id,
-                    (controller_to_client_sender, controller_from_client_receiver),
-                );
-
                 // Set the channels for the communication between the nodes
                 let packet_recv = self.intra_node_channels.get(&client.id).unwrap().1.clone();
                 let mut packet_send = HashMap::new();
@@ -229,18 +397,12 @@ impl NetworkInitializer {
                     );
                 }
 
-                // Create and spawn new clients
-                let handle = thread::spawn(move || {
-                    let mut client = RustbustersClient::new(
-                        client.id,
-                        client_to_controller_sender,
-                        client_from_controller_receiver,
-                        packet_recv,
-                        packet_send,
-                        None,
-                    );
-                    client.run();
-                });
+                let (command_sender, event_receiver, handle) =
+                    spawn_client(client.id, packet_recv, packet_send);
+                let event_receiver = self.event_tap.tap_host(client.id, event_receiver);
+
+                self.client_controller_channels
+                    .insert(client.id, (command_sender, event_receiver));
                 self.handles.push(handle);
             }
         }
@@ -266,14 +428,6 @@ impl NetworkInitializer {
             server_controller.run();
 
             for server in config.server.clone() {
-                let (controller_to_server_sender, server_from_controller_receiver) = unbounded();
-                let (server_to_controller_sender, controller_from_server_receiver) = unbounded();
-
-                self.server_controller_channels.insert(
-                    server.id,
-                    (controller_to_server_sender, controller_from_server_receiver),
-                );
-
                 // Set the channels for the communication between the nodes
                 let packet_recv = self.intra_node_channels.get(&server.id).unwrap().1.clone();
                 let mut packet_send = HashMap::new();
@@ -285,20 +439,200 @@ impl NetworkInitializer {
                     );
                 }
 
-                // Create and spawn new servers
-                let server = RustBustersServer::new(
+                let (command_sender, event_receiver, handle) = spawn_server(
                     server.id,
-                    server_to_controller_sender,
-                    server_from_controller_receiver,
-                    packet_send,
                     packet_recv,
+                    packet_send,
                     server_controller_sender.clone(),
-                    None,
                 );
+                let event_receiver = self.event_tap.tap_host(server.id, event_receiver);
+
+                self.server_controller_channels
+                    .insert(server.id, (command_sender, event_receiver));
+                self.handles.push(handle);
+            }
+
+            self.server_controller_sender = Some(server_controller_sender);
+        }
+    }
 
-                let handle = server.run().unwrap();
+    /// Creates the handle used to change the running network from the initializer itself
+    fn init_network_control(&mut self) {
+        let Some(config) = self.config.clone() else { return };
+
+        let drone_commands = self
+            .drone_controller_channels
+            .iter()
+            .map(|(id, (sender, _))| (*id, sender.clone()))
+            .collect();
+        let host_commands = self
+            .client_controller_channels
+            .iter()
+            .chain(self.server_controller_channels.iter())
+            .map(|(id, (sender, _))| (*id, sender.clone()))
+            .collect();
+        let node_senders = self
+            .intra_node_channels
+            .iter()
+            .map(|(id, (sender, _))| (*id, sender.clone()))
+            .collect();
+
+        let spawner = NodeSpawner {
+            link_layer: self.link_layer.clone(),
+            event_tap: self.event_tap.clone(),
+            drone_factories: self.drone_factories.clone(),
+            next_factory: self.next_factory,
+            server_controller_sender: self.server_controller_sender.clone(),
+        };
+        let (updates_sender, updates_receiver) = unbounded();
+        self.topology_updates = Some(updates_receiver);
+
+        self.network_control = Some(
+            NetworkControl::new(
+                config,
+                drone_commands,
+                host_commands,
+                node_senders,
+                self.source_map.take().unwrap_or_default(),
+            )
+            .with_spawner(spawner)
+            .with_controller_updates(updates_sender),
+        );
+    }
+
+    fn launch_scenario(&mut self) {
+        // The scenario file is optional
+        let Ok(path) = env::var("SCENARIO_FILE") else { return };
+        if path.is_empty() {
+            return;
+        }
+
+        let Some(network_control) = self.network_control.clone() else { return };
+        match Scenario::load(&path) {
+            Ok(scenario) => {
+                info!("Running scenario \"{}\" with {} events", path, scenario.event.len());
+                let handle = scenario.spawn(network_control);
+                self.handles.push(handle);
+            }
+            Err(error_message) => {
+                error!("{}", error_message);
+                println!("ERROR: {}", error_message);
+            }
+        }
+    }
+
+    fn launch_chaos(&mut self) {
+        // The chaos mode is enabled only when a seed is given
+        let Ok(seed) = env::var("CHAOS_SEED") else { return };
+        if seed.is_empty() {
+            return;
+        }
+        let seed = seed.parse::<u64>().expect("CHAOS_SEED must be a valid unsigned integer");
+        let interval = env::var("CHAOS_INTERVAL_MS")
+            .map(|value| value.parse::<u64>().expect("CHAOS_INTERVAL_MS must be a valid unsigned integer"))
+            .unwrap_or(3000);
+
+        if let Some(network_control) = self.network_control.clone() {
+            let chaos_engine = ChaosEngine::new(seed, Duration::from_millis(interval));
+            let handle = chaos_engine.spawn(network_control);
+            self.handles.push(handle);
+        }
+    }
+
+    fn launch_watcher(&mut self) {
+        if !self.watch {
+            return;
+        }
+
+        if let Some(network_control) = self.network_control.clone() {
+            let handle = watcher::spawn(self.config_path.clone(), network_control);
+            self.handles.push(handle);
+        }
+    }
+
+    fn launch_route_auditor(&mut self) {
+        let Some(routes) = self.audited_routes.take() else { return };
+
+        if let Some(network_control) = self.network_control.clone() {
+            let handle = route_auditor::spawn(routes, network_control);
+            self.handles.push(handle);
+        }
+    }
+
+    fn launch_discovery_auditor(&mut self) {
+        let Some(responses) = self.flood_responses.take() else { return };
+
+        if let Some(network_control) = self.network_control.clone() {
+            let handle = discovery_auditor::spawn(responses, network_control);
+            self.handles.push(handle);
+        }
+    }
+
+    fn launch_convergence_monitor(&mut self) {
+        let Some((events, launched_at)) = self.flood_events.take() else { return };
+
+        if let Some(network_control) = self.network_control.clone() {
+            let handle = convergence::spawn(events, network_control, launched_at);
+            self.handles.push(handle);
+        }
+    }
+
+    fn launch_metrics(&mut self) {
+        let Some((address, metrics)) = self.metrics.take() else { return };
+
+        if let Some(network_control) = self.network_control.clone() {
+            match metrics::serve(&address, metrics, network_control) {
+                Ok(handle) => self.handles.push(handle),
+                Err(error_message) => {
+                    error!("{}", error_message);
+                    println!("ERROR: {}", error_message);
+                }
+            }
+        }
+    }
+
+    fn launch_queue_monitor(&mut self) {
+        let Some(sent_counter) = self.sent_counter.take() else { return };
+        let interval = env::var("QUEUE_MONITOR_INTERVAL_MS")
+            .map(|value| value.parse::<u64>().expect("QUEUE_MONITOR_INTERVAL_MS must be a valid unsigned integer"))
+            .unwrap_or(1000);
+        let threshold = env::var("QUEUE_THRESHOLD")
+            .map(|value| value.parse::<usize>().expect("QUEUE_THRESHOLD must be a valid unsigned integer"))
+            .unwrap_or(1000);
+        let csv_path = env::var("QUEUE_MONITOR_CSV").ok().filter(|path| !path.is_empty());
+
+        // The simulation controller keeps consuming the events, the monitor only looks at the length of the channels
+        let mut event_queues: HashMap<NodeId, EventQueueLen> = HashMap::new();
+        for (id, (_, receiver)) in &self.drone_controller_channels {
+            let receiver = receiver.clone();
+            event_queues.insert(*id, Box::new(move || receiver.len()));
+        }
+        for (id, (_, receiver)) in self.client_controller_channels.iter().chain(self.server_controller_channels.iter()) {
+            let receiver = receiver.clone();
+            event_queues.insert(*id, Box::new(move || receiver.len()));
+        }
+
+        let Some(network_control) = self.network_control.clone() else { return };
+        match QueueMonitor::new(Duration::from_millis(interval), threshold, csv_path.as_deref()) {
+            Ok(queue_monitor) => {
+                let handle = queue_monitor.spawn(self.link_layer.clone(), event_queues, sent_counter, network_control);
                 self.handles.push(handle);
             }
+            Err(error_message) => {
+                error!("{}", error_message);
+                println!("ERROR: {}", error_message);
+            }
+        }
+    }
+
+    fn launch_conformance_monitor(&mut self) {
+        let Some(events) = self.drop_events.take() else { return };
+
+        if let Some(config) = &self.config {
+            // The PDRs the drones start with, the command tap reports the later ones
+            let pdrs = config.drone.iter().map(|drone| (drone.id, drone.pdr)).collect();
+            let handle = conformance::spawn(events, pdrs);
+            self.handles.push(handle);
         }
     }
 
@@ -373,6 +707,7 @@ impl NetworkInitializer {
                 servers: config.server.clone(),
                 server_ui_url,
                 drone_groups: self.drone_groups.clone(),
+                topology_updates: self.topology_updates.unwrap_or_else(|| unbounded().1),
             };
             let sim_controller = RustBustersSimulationController::new(params);
 
@@ -392,3 +727,110 @@ impl NetworkInitializer {
         }
     }
 }
+
+/// Creates a drone with the given factory and spawns it in its own thread.
+/// Returns the channels for the simulation controller, the type of the drone and the thread handle.
+pub fn spawn_drone(
+    create_drone: &DroneFactory,
+    drone: &wg_2024::config::Drone,
+    packet_recv: Receiver<Packet>,
+    packet_send: HashMap<NodeId, Sender<Packet>>,
+    event_tap: &EventTap,
+) -> (
+    Sender<DroneCommand>,
+    Receiver<DroneEvent>,
+    &'static str,
+    thread::JoinHandle<()>,
+) {
+    // Channels for communication between the drone and the simulation controller
+    let (controller_to_drone_sender, drone_from_controller_receiver) = unbounded();
+    let (drone_to_controller_sender, controller_from_drone_receiver) = unbounded();
+    let drone_from_controller_receiver = event_tap.tap_drone_commands(drone.id, drone_from_controller_receiver);
+
+    let mut new_drone = create_drone(
+        drone.id,
+        drone_to_controller_sender, // The drone can send events here
+        drone_from_controller_receiver, // The drone receives commands here
+        packet_recv,
+        packet_send,
+        drone.pdr,
+    );
+
+    if new_drone.drone_type() == "FungiDrone" {
+        let fungi_drone = new_drone.as_any_mut().downcast_mut::<FungiDrone>().unwrap();
+        fungi_drone.set_debug_print();     // Enables Debug Print
+        fungi_drone.disable_request_log(); // Disables Flood Request Log
+    }
+    else if new_drone.drone_type() == "RustezeDrone" {
+        let rusteze_drone = new_drone.as_any_mut().downcast_mut::<RustezeDrone>().unwrap();
+        rusteze_drone.with_all(); // Enable all levels
+    }
+
+    let drone_type = new_drone.drone_type();
+    info!("Type of Drone {}: {}", drone.id, drone_type);
+
+    let handle = thread::spawn(move || {
+        let mut current_drone = new_drone;
+        current_drone.run();
+    });
+
+    (
+        controller_to_drone_sender,
+        controller_from_drone_receiver,
+        drone_type,
+        handle,
+    )
+}
+
+/// Creates a client and spawns it in its own thread.
+/// Returns the channels for the simulation controller and the thread handle.
+pub fn spawn_client(
+    client_id: NodeId,
+    packet_recv: Receiver<Packet>,
+    packet_send: HashMap<NodeId, Sender<Packet>>,
+) -> (Sender<HostCommand>, Receiver<HostEvent>, thread::JoinHandle<()>) {
+    // Channels for communication between the client and the simulation controller
+    let (controller_to_client_sender, client_from_controller_receiver) = unbounded();
+    let (client_to_controller_sender, controller_from_client_receiver) = unbounded();
+
+    // Create and spawn new clients
+    let handle = thread::spawn(move || {
+        let mut client = RustbustersClient::new(
+            client_id,
+            client_to_controller_sender,
+            client_from_controller_receiver,
+            packet_recv,
+            packet_send,
+            None,
+        );
+        client.run();
+    });
+
+    (controller_to_client_sender, controller_from_client_receiver, handle)
+}
+
+/// Creates a server and spawns it in its own thread.
+/// Returns the channels for the simulation controller and the thread handle.
+pub fn spawn_server(
+    server_id: NodeId,
+    packet_recv: Receiver<Packet>,
+    packet_send: HashMap<NodeId, Sender<Packet>>,
+    server_controller_sender: Sender<HostCommand>,
+) -> (Sender<HostCommand>, Receiver<HostEvent>, thread::JoinHandle<()>) {
+    let (controller_to_server_sender, server_from_controller_receiver) = unbounded();
+    let (server_to_controller_sender, controller_from_server_receiver) = unbounded();
+
+    // Create and spawn new servers
+    let server = RustBustersServer::new(
+        server_id,
+        server_to_controller_sender,
+        server_from_controller_receiver,
+        packet_send,
+        packet_recv,
+        server_controller_sender,
+        None,
+    );
+
+    let handle = server.run().unwrap();
+    (controller_to_server_sender, controller_from_server_receiver, handle)
+}
@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use wg_2024::config::Config;
+use wg_2024::network::NodeId;
+
+use crate::cli::PlanFormat;
+use crate::network_initializer::{assign_drone_types, rustbusters_mode};
+use crate::utils::{config_loader, config_writer, topology};
+
+// One color for each drone implementation, in the order they are assigned
+const PALETTE: [&str; 10] = [
+    "#8dd3c7", "#ffffb3", "#bebada", "#fb8072", "#80b1d3",
+    "#fdb462", "#b3de69", "#fccde5", "#d9d9d9", "#bc80bd",
+];
+const CLIENT_COLOR: &str = "#ffffff";
+const SERVER_COLOR: &str = "#a6a6a6";
+const UNKNOWN_COLOR: &str = "#e5e5e5";
+
+/// Loads and validates the topology file and exports it as a graph, or in the plain `wg_2024` format
+pub fn plan(config_path: &str, format: PlanFormat, output: Option<&str>) -> Result<(), String> {
+    let config = config_loader::load_config(config_path)?;
+    let drone_types = assign_drone_types(&config, rustbusters_mode());
+
+    let graph = match format {
+        PlanFormat::Dot => to_dot(&config, &drone_types),
+        PlanFormat::Mermaid => to_mermaid(&config, &drone_types),
+        PlanFormat::Toml => config_writer::to_toml(&config),
+    };
+
+    match output {
+        Some(path) => fs::write(path, graph).map_err(|e| format!("Unable to write \"{}\": {}", path, e)),
+        None => {
+            print!("{}", graph);
+            Ok(())
+        }
+    }
+}
+
+/// Exports the topology in Graphviz DOT format.
+/// Nodes keep their "type" and "pdr" as attributes, so the graph can be imported back.
+pub fn to_dot(config: &Config, drone_types: &HashMap<NodeId, &'static str>) -> String {
+    let colors = implementation_colors(config, drone_types);
+    let mut dot = String::new();
+
+    writeln!(dot, "graph network {{").unwrap();
+    writeln!(dot, "    node [style=filled, fontname=\"Helvetica\"];").unwrap();
+    writeln!(dot, "    edge [fontname=\"Helvetica\", fontsize=10];").unwrap();
+    writeln!(dot).unwrap();
+
+    for drone in &config.drone {
+        let drone_type = drone_types.get(&drone.id).copied().unwrap_or("Unknown");
+        writeln!(
+            dot,
+            "    {} [type=drone, pdr={}, label=\"Drone {}\\n{}\", shape=ellipse, fillcolor=\"{}\"];",
+            drone.id, drone.pdr, drone.id, drone_type, colors.get(drone_type).unwrap_or(&UNKNOWN_COLOR)
+        )
+        .unwrap();
+    }
+    for client in &config.client {
+        writeln!(
+            dot,
+            "    {} [type=client, label=\"Client {}\", shape=box, fillcolor=\"{}\"];",
+            client.id, client.id, CLIENT_COLOR
+        )
+        .unwrap();
+    }
+    for server in &config.server {
+        writeln!(
+            dot,
+            "    {} [type=server, label=\"Server {}\", shape=box3d, fillcolor=\"{}\"];",
+            server.id, server.id, SERVER_COLOR
+        )
+        .unwrap();
+    }
+    writeln!(dot).unwrap();
+
+    for (a, b) in topology::links(config) {
+        writeln!(dot, "    {} -- {} [label=\"{}\"];", a, b, link_label(config, a, b)).unwrap();
+    }
+
+    writeln!(dot, "}}").unwrap();
+    dot
+}
+
+/// Exports the topology as a Mermaid flowchart
+pub fn to_mermaid(config: &Config, drone_types: &HashMap<NodeId, &'static str>) -> String {
+    let colors = implementation_colors(config, drone_types);
+    let mut mermaid = String::new();
+
+    writeln!(mermaid, "graph LR").unwrap();
+
+    for drone in &config.drone {
+        let drone_type = drone_types.get(&drone.id).copied().unwrap_or("Unknown");
+        writeln!(mermaid, "    n{}((\"Drone {}<br/>{}\")):::{}", drone.id, drone.id, drone_type, drone_type).unwrap();
+    }
+    for client in &config.client {
+        writeln!(mermaid, "    n{}[\"Client {}\"]:::client", client.id, client.id).unwrap();
+    }
+    for server in &config.server {
+        writeln!(mermaid, "    n{}[[\"Server {}\"]]:::server", server.id, server.id).unwrap();
+    }
+
+    for (a, b) in topology::links(config) {
+        writeln!(mermaid, "    n{} ---|\"{}\"| n{}", a, link_label(config, a, b), b).unwrap();
+    }
+
+    let mut classes: Vec<(&&str, &&str)> = colors.iter().collect();
+    classes.sort();
+    for (drone_type, color) in classes {
+        writeln!(mermaid, "    classDef {} fill:{}", drone_type, color).unwrap();
+    }
+    writeln!(mermaid, "    classDef Unknown fill:{}", UNKNOWN_COLOR).unwrap();
+    writeln!(mermaid, "    classDef client fill:{}", CLIENT_COLOR).unwrap();
+    writeln!(mermaid, "    classDef server fill:{}", SERVER_COLOR).unwrap();
+
+    mermaid
+}
+
+// A fragment crossing a link can be dropped by the drones at its ends,
+// so the label shows the PDR of each drone endpoint
+fn link_label(config: &Config, a: NodeId, b: NodeId) -> String {
+    [a, b]
+        .iter()
+        .filter_map(|id| config.drone.iter().find(|d| d.id == *id))
+        .map(|drone| format!("{}", drone.pdr))
+        .collect::<Vec<String>>()
+        .join(" / ")
+}
+
+// Assigns a color of the palette to each implementation, in order of first appearance
+fn implementation_colors(
+    config: &Config,
+    drone_types: &HashMap<NodeId, &'static str>,
+) -> HashMap<&'static str, &'static str> {
+    let mut colors: HashMap<&'static str, &'static str> = HashMap::new();
+
+    for drone in &config.drone {
+        if let Some(drone_type) = drone_types.get(&drone.id) {
+            let next_color = PALETTE[colors.len() % PALETTE.len()];
+            colors.entry(drone_type).or_insert(next_color);
+        }
+    }
+    colors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::importer::{self, dot};
+    use crate::utils::topology::tests::config_with_links;
+
+    // Client 100 and server 200 on the drones 1 (RustyDrone), 2 (FungiDrone) and 3 (RustyDrone)
+    fn sample() -> (Config, HashMap<NodeId, &'static str>) {
+        let config = config_with_links(
+            &[(1, 0.05), (2, 0.25), (3, 1.)],
+            &[100],
+            &[200],
+            &[(100, 1), (1, 2), (2, 3), (1, 3), (2, 200), (3, 200)],
+        );
+        let drone_types = HashMap::from([(1, "RustyDrone"), (2, "FungiDrone"), (3, "RustyDrone")]);
+        (config, drone_types)
+    }
+
+    #[test]
+    fn dot_imports_back_to_the_same_config() {
+        let (config, drone_types) = sample();
+        let imported = importer::to_config(&dot::parse(&to_dot(&config, &drone_types)).unwrap(), 0.5).unwrap();
+
+        let ids = |config: &Config| {
+            (
+                config.drone.iter().map(|d| (d.id, d.pdr)).collect::<Vec<_>>(),
+                config.client.iter().map(|c| c.id).collect::<Vec<_>>(),
+                config.server.iter().map(|s| s.id).collect::<Vec<_>>(),
+            )
+        };
+        assert_eq!(ids(&imported), ids(&config));
+        assert_eq!(topology::links(&imported), topology::links(&config));
+    }
+
+    #[test]
+    fn dot_labels_links_with_the_pdrs_of_their_drones() {
+        let (config, drone_types) = sample();
+        let dot = to_dot(&config, &drone_types);
+
+        assert!(dot.contains("    1 [type=drone, pdr=0.05, label=\"Drone 1\\nRustyDrone\", shape=ellipse, fillcolor=\"#8dd3c7\"];"));
+        assert!(dot.contains("    2 [type=drone, pdr=0.25, label=\"Drone 2\\nFungiDrone\", shape=ellipse, fillcolor=\"#ffffb3\"];"));
+        assert!(dot.contains("    1 -- 100 [label=\"0.05\"];"));
+        assert!(dot.contains("    1 -- 2 [label=\"0.05 / 0.25\"];"));
+    }
+
+    #[test]
+    fn mermaid_assigns_one_class_per_implementation() {
+        let (config, mut drone_types) = sample();
+        drone_types.remove(&3);
+
+        assert_eq!(
+            to_mermaid(&config, &drone_types),
+            "graph LR\n\
+             \x20   n1((\"Drone 1<br/>RustyDrone\")):::RustyDrone\n\
+             \x20   n2((\"Drone 2<br/>FungiDrone\")):::FungiDrone\n\
+             \x20   n3((\"Drone 3<br/>Unknown\")):::Unknown\n\
+             \x20   n100[\"Client 100\"]:::client\n\
+             \x20   n200[[\"Server 200\"]]:::server\n\
+             \x20   n1 ---|\"0.05 / 0.25\"| n2\n\
+             \x20   n1 ---|\"0.05 / 1\"| n3\n\
+             \x20   n1 ---|\"0.05\"| n100\n\
+             \x20   n2 ---|\"0.25 / 1\"| n3\n\
+             \x20   n2 ---|\"0.25\"| n200\n\
+             \x20   n3 ---|\"1\"| n200\n\
+             \x20   classDef FungiDrone fill:#ffffb3\n\
+             \x20   classDef RustyDrone fill:#8dd3c7\n\
+             \x20   classDef Unknown fill:#e5e5e5\n\
+             \x20   classDef client fill:#ffffff\n\
+             \x20   classDef server fill:#a6a6a6\n"
+        );
+    }
+}
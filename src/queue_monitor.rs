@@ -0,0 +1,230 @@
+use log::{debug, error, info, warn};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use wg_2024::network::NodeId;
+use wg_2024::packet::{Packet, PacketType};
+
+use crate::link_layer::{LinkLayer, PacketObserver};
+use crate::network_control::NetworkControl;
+use crate::utils::topology;
+
+// Consecutive samples a node must look stuck for before it is reported as stalled
+const STALL_SAMPLES: usize = 3;
+
+/// Returns how many events are waiting in a controller event channel
+pub type EventQueueLen = Box<dyn Fn() -> usize + Send>;
+
+/// Counts the packets each node sends, as seen on the links of their receivers
+#[derive(Default)]
+pub struct SentCounter {
+    sent: Mutex<HashMap<NodeId, u64>>,
+}
+
+impl SentCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn sent(&self, id: NodeId) -> u64 {
+        self.sent.lock().unwrap().get(&id).copied().unwrap_or(0)
+    }
+}
+
+impl PacketObserver for SentCounter {
+    fn on_packet(&self, _to: NodeId, packet: &Packet) {
+        let sender = match &packet.pack_type {
+            PacketType::FloodRequest(flood_request) => flood_request.path_trace.last().map(|(id, _)| *id),
+            _ => {
+                let header = &packet.routing_header;
+                header.hop_index.checked_sub(1).and_then(|index| header.hops.get(index)).copied()
+            }
+        };
+        if let Some(sender) = sender {
+            *self.sent.lock().unwrap().entry(sender).or_default() += 1;
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct NodeState {
+    depth: usize,
+    sent: u64,
+    over_threshold: bool,
+    stuck_samples: usize,
+}
+
+/// Queue monitor: periodically samples the inbound packet queue of every node (the packets waiting on
+/// its link plus the ones waiting in its inbox) and its controller event queue.
+/// It warns when a queue passes the threshold, and when a node looks stalled: its packet queue does not
+/// shrink for `STALL_SAMPLES` samples while it sends nothing.
+pub struct QueueMonitor {
+    interval: Duration,
+    threshold: usize,
+    csv: Option<File>,
+}
+
+impl QueueMonitor {
+    /// When a CSV file is given, every sample is written to it as `time_ms,node,link,inbox,events`
+    pub fn new(interval: Duration, threshold: usize, csv_path: Option<&str>) -> Result<Self, String> {
+        let csv = match csv_path {
+            Some(path) => {
+                let mut file = File::create(path).map_err(|e| format!("Unable to create \"{}\": {}", path, e))?;
+                writeln!(file, "time_ms,node,link,inbox,events")
+                    .map_err(|e| format!("Unable to write \"{}\": {}", path, e))?;
+                Some(file)
+            }
+            None => None,
+        };
+        Ok(Self { interval, threshold, csv })
+    }
+
+    pub fn spawn(
+        mut self,
+        link_layer: LinkLayer,
+        event_queues: HashMap<NodeId, EventQueueLen>,
+        sent_counter: Arc<SentCounter>,
+        network: NetworkControl,
+    ) -> thread::JoinHandle<()> {
+        info!("Queue monitor started, sampling every {}ms", self.interval.as_millis());
+        thread::spawn(move || {
+            let start = Instant::now();
+            let mut states: BTreeMap<NodeId, NodeState> = BTreeMap::new();
+
+            loop {
+                thread::sleep(self.interval);
+                let current = network.topology();
+                let mut samples = Vec::new();
+
+                for (id, link, inbox) in link_layer.queue_depths() {
+                    // Crashed drones are not expected to empty their queue
+                    if topology::neighbours(&current, id).is_none() {
+                        continue;
+                    }
+                    let events = event_queues.get(&id).map(|len| len()).unwrap_or(0);
+                    samples.push(format!("{}={}+{}/{}", id, link, inbox, events));
+                    self.write_sample(start.elapsed(), id, link, inbox, events);
+
+                    let state = states.entry(id).or_default();
+                    self.check_node(id, state, link + inbox, events, sent_counter.sent(id));
+                }
+                debug!("Queues (node=link+inbox/events): {}", samples.join(" "));
+            }
+        })
+    }
+
+    fn check_node(&self, id: NodeId, state: &mut NodeState, depth: usize, events: usize, sent: u64) {
+        let over_threshold = depth.max(events) > self.threshold;
+        if over_threshold && !state.over_threshold {
+            warn!(
+                "Queue monitor: node {} has {} packets and {} events queued (threshold {})",
+                id, depth, events, self.threshold
+            );
+        } else if !over_threshold && state.over_threshold {
+            info!("Queue monitor: the queues of node {} are back under the threshold", id);
+        }
+        state.over_threshold = over_threshold;
+
+        if depth > 0 && depth >= state.depth && sent == state.sent {
+            state.stuck_samples += 1;
+            if state.stuck_samples == STALL_SAMPLES {
+                warn!("Queue monitor: node {} looks stalled, {} packets queued and none sent", id, depth);
+            }
+        } else {
+            if state.stuck_samples >= STALL_SAMPLES {
+                info!("Queue monitor: node {} is sending again", id);
+            }
+            state.stuck_samples = 0;
+        }
+        state.depth = depth;
+        state.sent = sent;
+    }
+
+    fn write_sample(&mut self, time: Duration, id: NodeId, link: usize, inbox: usize, events: usize) {
+        let Some(csv) = &mut self.csv else { return };
+        if let Err(e) = writeln!(csv, "{},{},{},{},{}", time.as_millis(), id, link, inbox, events) {
+            error!("Unable to write a queue sample, the CSV output is stopped: {}", e);
+            self.csv = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wg_2024::network::SourceRoutingHeader;
+    use wg_2024::packet::{Ack, FloodRequest, NodeType};
+
+    fn monitor() -> QueueMonitor {
+        QueueMonitor::new(Duration::from_millis(10), 100, None).unwrap()
+    }
+
+    fn ack(hops: Vec<NodeId>, hop_index: usize) -> Packet {
+        Packet {
+            routing_header: SourceRoutingHeader { hop_index, hops },
+            session_id: 0,
+            pack_type: PacketType::Ack(Ack { fragment_index: 0 }),
+        }
+    }
+
+    #[test]
+    fn a_growing_queue_without_sends_is_a_stall() {
+        let monitor = monitor();
+        let mut state = NodeState::default();
+        // The first sample sees the packets sent so far
+        monitor.check_node(1, &mut state, 2, 0, 5);
+        assert_eq!(state.stuck_samples, 0);
+
+        for (sample, depth) in [3, 3, 4].into_iter().enumerate() {
+            monitor.check_node(1, &mut state, depth, 0, 5);
+            assert_eq!(state.stuck_samples, sample + 1);
+        }
+        assert!(state.stuck_samples >= STALL_SAMPLES);
+
+        // A single packet sent ends the stall
+        monitor.check_node(1, &mut state, 4, 0, 6);
+        assert_eq!(state.stuck_samples, 0);
+    }
+
+    #[test]
+    fn a_shrinking_queue_is_not_a_stall() {
+        let monitor = monitor();
+        let mut state = NodeState::default();
+        for depth in [9, 9, 8, 8, 7] {
+            monitor.check_node(1, &mut state, depth, 0, 0);
+            assert!(state.stuck_samples < STALL_SAMPLES);
+        }
+        assert_eq!(state.stuck_samples, 0);
+    }
+
+    #[test]
+    fn an_idle_node_is_not_a_stall() {
+        let monitor = monitor();
+        let counter = SentCounter::new();
+        let mut state = NodeState::default();
+        counter.on_packet(200, &ack(vec![100, 1, 200], 2));
+
+        for _ in 0..2 * STALL_SAMPLES {
+            monitor.check_node(2, &mut state, 0, 0, counter.sent(2));
+        }
+        assert_eq!(counter.sent(2), 0);
+        assert_eq!(state.stuck_samples, 0);
+    }
+
+    #[test]
+    fn counts_the_packets_of_each_sender() {
+        let counter = SentCounter::new();
+        counter.on_packet(1, &ack(vec![100, 1, 200], 1));
+        counter.on_packet(200, &ack(vec![100, 1, 200], 2));
+        counter.on_packet(200, &ack(vec![100, 1, 200], 2));
+        // A packet still at its first hop has no sender
+        counter.on_packet(100, &ack(vec![100, 1, 200], 0));
+        let request = FloodRequest { flood_id: 1, initiator_id: 100, path_trace: vec![(100, NodeType::Client), (1, NodeType::Drone)] };
+        counter.on_packet(2, &Packet { pack_type: PacketType::FloodRequest(request), ..ack(vec![], 0) });
+
+        assert_eq!((counter.sent(100), counter.sent(1), counter.sent(200)), (1, 3, 0));
+    }
+}
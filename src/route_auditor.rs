@@ -0,0 +1,204 @@
+use crossbeam_channel::{unbounded, Receiver, RecvTimeoutError, Sender};
+use log::{info, warn};
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use wg_2024::config::Config;
+use wg_2024::network::NodeId;
+use wg_2024::packet::{Packet, PacketType};
+
+use crate::analyzer::reliability;
+use crate::link_layer::PacketObserver;
+use crate::network_control::NetworkControl;
+use crate::utils::topology;
+
+// How often the per-host summary is logged
+const REPORT_INTERVAL: Duration = Duration::from_secs(10);
+// Routes whose delivery probability is this close to the best one are as reliable
+const RELIABILITY_TOLERANCE: f64 = 1e-9;
+
+/// Taps the links and extracts the source routing header of each `MsgFragment`
+/// when it leaves its client or server. The routes are audited on another thread by `spawn`,
+/// so the links are not slowed down.
+pub struct RouteAuditor {
+    routes: Sender<Vec<NodeId>>,
+}
+
+impl RouteAuditor {
+    /// Returns the observer to register on the link layer and the receiver of the routes it sees
+    pub fn new() -> (Arc<Self>, Receiver<Vec<NodeId>>) {
+        let (sender, receiver) = unbounded();
+        (Arc::new(Self { routes: sender }), receiver)
+    }
+}
+
+impl PacketObserver for RouteAuditor {
+    fn on_packet(&self, to: NodeId, packet: &Packet) {
+        let header = &packet.routing_header;
+        // The first hop of a fragment, retransmissions included
+        if matches!(packet.pack_type, PacketType::MsgFragment(_)) && header.hop_index == 1 && header.hops.get(1) == Some(&to) {
+            let _ = self.routes.send(header.hops.clone());
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct HostAudit {
+    fragments: usize,
+    optimal: usize,
+    longer: usize,
+    less_reliable: usize,
+    stale: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Finding {
+    Stale(String),
+    Longer { hops: usize, shortest: usize },
+    LessReliable { delivery: String, best: String },
+}
+
+/// Audits the routes against the current topology: a route is stale if it uses a node or a link
+/// that does not exist anymore, and it may be longer than the shortest route or less reliable
+/// than the most reliable one. When the two differ a host can only pick one of them, so a route
+/// is optimal if it is either the shortest or the most reliable one. Each stale or suboptimal route
+/// is logged the first time it is seen, and a summary for each host is logged periodically.
+pub fn spawn(routes: Receiver<Vec<NodeId>>, network: NetworkControl) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let mut audits: BTreeMap<NodeId, HostAudit> = BTreeMap::new();
+        let mut flagged: HashSet<(Vec<NodeId>, Finding)> = HashSet::new();
+        let mut next_report = Instant::now() + REPORT_INTERVAL;
+
+        loop {
+            match routes.recv_timeout(next_report.saturating_duration_since(Instant::now())) {
+                Ok(route) if route.len() >= 2 => {
+                    let current = network.topology();
+                    let findings = audit(&current, &route);
+                    let host = audits.entry(route[0]).or_default();
+                    host.fragments += 1;
+
+                    let optimal = is_optimal(&findings);
+                    if optimal {
+                        host.optimal += 1;
+                    }
+                    for finding in findings {
+                        match finding {
+                            Finding::Stale(_) => host.stale += 1,
+                            Finding::Longer { .. } => host.longer += 1,
+                            Finding::LessReliable { .. } => host.less_reliable += 1,
+                        }
+                        if !optimal && flagged.insert((route.clone(), finding.clone())) {
+                            warn!("Route audit: {} {} uses {}", topology::host_kind(&current, route[0]), route[0], describe(&route, &finding));
+                        }
+                    }
+                }
+                Ok(_) => {}
+                Err(RecvTimeoutError::Timeout) => {
+                    let current = network.topology();
+                    for (id, audit) in &audits {
+                        info!(
+                            "Route audit: {} {} sent {} fragments: {} optimal (shortest or most reliable), {} longer than the shortest route, {} less reliable than the best route, {} stale",
+                            topology::host_kind(&current, *id), id, audit.fragments, audit.optimal, audit.longer, audit.less_reliable, audit.stale
+                        );
+                    }
+                    next_report = Instant::now() + REPORT_INTERVAL;
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+    })
+}
+
+fn audit(current: &Config, route: &[NodeId]) -> Vec<Finding> {
+    let (from, to) = (route[0], route[route.len() - 1]);
+
+    for (i, id) in route.iter().enumerate() {
+        if topology::neighbours(current, *id).is_none() {
+            return vec![Finding::Stale(format!("node {} does not exist anymore", id))];
+        }
+        if i > 0 && i < route.len() - 1 && !topology::is_drone(current, *id) {
+            return vec![Finding::Stale(format!("node {} is not a drone", id))];
+        }
+    }
+    for pair in route.windows(2) {
+        if !topology::linked(current, pair[0], pair[1]) {
+            return vec![Finding::Stale(format!("link {}-{} does not exist anymore", pair[0], pair[1]))];
+        }
+    }
+
+    let mut findings = Vec::new();
+    let hops = route.len() - 1;
+    if let Some(shortest) = topology::hop_distances(current, from).get(&to).copied().filter(|s| hops > *s) {
+        findings.push(Finding::Longer { hops, shortest });
+    }
+    if let Some(best_route) = reliability::most_reliable_route(current, from, to) {
+        let delivery = reliability::fragment_delivery(current, route);
+        let best = reliability::fragment_delivery(current, &best_route);
+        if delivery < best - RELIABILITY_TOLERANCE {
+            findings.push(Finding::LessReliable { delivery: format!("{:.4}", delivery), best: format!("{:.4}", best) });
+        }
+    }
+    findings
+}
+
+// The shortest and the most reliable routes may differ: matching either one is optimal
+fn is_optimal(findings: &[Finding]) -> bool {
+    let longer = findings.iter().any(|f| matches!(f, Finding::Longer { .. }));
+    let less_reliable = findings.iter().any(|f| matches!(f, Finding::LessReliable { .. }));
+    let stale = findings.iter().any(|f| matches!(f, Finding::Stale(_)));
+    !stale && (!longer || !less_reliable)
+}
+
+fn describe(route: &[NodeId], finding: &Finding) -> String {
+    let route: Vec<String> = route.iter().map(|id| id.to_string()).collect();
+    let route = route.join("-");
+    match finding {
+        Finding::Stale(reason) => format!("stale route {}: {}", route, reason),
+        Finding::Longer { hops, shortest } => format!("route {} of {} hops, the shortest has {}", route, hops, shortest),
+        Finding::LessReliable { delivery, best } => {
+            format!("route {} delivering {} of the fragments, the most reliable delivers {}", route, delivery, best)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::topology::tests::config_with_links;
+
+    // 100 - 1 - 200 is the shortest route, 100 - 2 - 3 - 200 the most reliable one
+    fn two_ways() -> Config {
+        config_with_links(
+            &[(1, 0.5), (2, 0.), (3, 0.), (4, 0.5)],
+            &[100],
+            &[200],
+            &[(100, 1), (1, 200), (100, 2), (2, 3), (3, 200), (2, 4), (4, 3)],
+        )
+    }
+
+    #[test]
+    fn the_shortest_and_the_most_reliable_routes_are_both_optimal() {
+        let config = two_ways();
+
+        let shortest = audit(&config, &[100, 1, 200]);
+        assert!(matches!(shortest[..], [Finding::LessReliable { .. }]));
+        assert!(is_optimal(&shortest));
+
+        let most_reliable = audit(&config, &[100, 2, 3, 200]);
+        assert!(matches!(most_reliable[..], [Finding::Longer { hops: 3, shortest: 2 }]));
+        assert!(is_optimal(&most_reliable));
+
+        let neither = audit(&config, &[100, 2, 4, 3, 200]);
+        assert_eq!(neither.len(), 2);
+        assert!(!is_optimal(&neither));
+    }
+
+    #[test]
+    fn flags_stale_routes() {
+        let config = two_ways();
+        assert_eq!(audit(&config, &[100, 9, 200]), [Finding::Stale("node 9 does not exist anymore".to_string())]);
+        assert_eq!(audit(&config, &[100, 1, 3, 200]), [Finding::Stale("link 1-3 does not exist anymore".to_string())]);
+        assert!(!is_optimal(&audit(&config, &[100, 200])));
+    }
+}
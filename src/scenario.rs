@@ -0,0 +1,185 @@
+use log::{error, info, warn};
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+use std::{fs, thread};
+use wg_2024::network::NodeId;
+
+use crate::network_control::NetworkControl;
+use crate::utils::topology::Mutation;
+
+/// A scenario is a timeline of network events, loaded from a TOML file like:
+///
+/// ```toml
+/// allow_invalid = false  # apply the events that break the validator rules too
+///
+/// [[event]]
+/// at = 5.0
+/// action = "set_pdr"
+/// drone = 4
+/// pdr = 0.5
+///
+/// [[event]]
+/// at = 12
+/// action = "remove_link"
+/// nodes = [2, 9]
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct Scenario {
+    #[serde(default)]
+    pub allow_invalid: bool,
+    #[serde(default)]
+    pub event: Vec<ScenarioEvent>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScenarioEvent {
+    /// Seconds elapsed since the launch of the network
+    pub at: f64,
+    #[serde(flatten)]
+    pub action: ScenarioAction,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ScenarioAction {
+    SetPdr { drone: NodeId, pdr: f32 },
+    Crash { drone: NodeId },
+    RemoveLink { nodes: [NodeId; 2] },
+    AddLink { nodes: [NodeId; 2] },
+    SendMessage { from: NodeId, to: NodeId, server: NodeId, text: String },
+}
+
+impl Scenario {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let data = fs::read_to_string(path).map_err(|e| format!("Unable to read scenario file \"{}\": {}", path, e))?;
+        let mut scenario: Scenario =
+            toml::from_str(&data).map_err(|e| format!("Unable to parse scenario file \"{}\": {}", path, e))?;
+
+        if let Some(event) = scenario.event.iter().find(|e| !e.at.is_finite() || e.at < 0.) {
+            return Err(format!("Scenario event {:?} has an invalid time", event));
+        }
+        scenario.event.sort_by(|a, b| a.at.total_cmp(&b.at));
+
+        Ok(scenario)
+    }
+
+    /// Runs the scenario in its own thread. Times are measured from the moment this is called.
+    pub fn spawn(self, network: NetworkControl) -> thread::JoinHandle<()> {
+        let start = Instant::now();
+        thread::spawn(move || {
+            for event in self.event {
+                let due = Duration::from_secs_f64(event.at);
+                if let Some(remaining) = due.checked_sub(start.elapsed()) {
+                    thread::sleep(remaining);
+                }
+
+                info!("Scenario at {:.1}s: {:?}", event.at, event.action);
+                if let Err(error_message) = apply(&network, &event.action, self.allow_invalid) {
+                    error!("Scenario event at {:.1}s failed: {}", event.at, error_message);
+                }
+            }
+            info!("Scenario completed");
+        })
+    }
+}
+
+// Changes that break the validator rules are rejected, unless the scenario opts in with
+// `allow_invalid` since it may want to test exactly that. A warning is logged in that case.
+fn apply(network: &NetworkControl, action: &ScenarioAction, allow_invalid: bool) -> Result<(), String> {
+    let mutation = match action {
+        ScenarioAction::SetPdr { drone, pdr } => Mutation::SetPdr(*drone, *pdr),
+        ScenarioAction::Crash { drone } => Mutation::Crash(*drone),
+        ScenarioAction::RemoveLink { nodes: [a, b] } => Mutation::RemoveLink(*a, *b),
+        ScenarioAction::AddLink { nodes: [a, b] } => Mutation::AddLink(*a, *b),
+        ScenarioAction::SendMessage { from, to, server, text } => {
+            return network.send_message(*from, *to, *server, text)
+        }
+    };
+
+    if !allow_invalid {
+        return network.apply_validated(&mutation);
+    }
+    if let Err(error_message) = network.check(&mutation) {
+        warn!("Scenario breaks the topology rules, applied anyway: {}", error_message);
+    }
+    network.apply(&mutation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::topology::{self, tests::config_with_links};
+    use common_utils::HostCommand;
+    use crossbeam_channel::{unbounded, Receiver};
+    use std::collections::HashMap;
+    use wg_2024::controller::DroneCommand;
+
+    fn load(name: &str, data: &str) -> Result<Scenario, String> {
+        let path = std::env::temp_dir().join(format!("scenario-{}-{}.toml", name, std::process::id()));
+        fs::write(&path, data).unwrap();
+        let scenario = Scenario::load(&path.display().to_string());
+        fs::remove_file(path).unwrap();
+        scenario
+    }
+
+    // Client 100 and server 200 joined by drone 1 only. The host receivers are returned to keep them open.
+    fn chain() -> (NetworkControl, Receiver<DroneCommand>, Vec<Receiver<HostCommand>>) {
+        let config = config_with_links(&[(1, 0.)], &[100], &[200], &[(100, 1), (1, 200)]);
+        let (drone_sender, drone_receiver) = unbounded();
+        let (host_commands, host_receivers): (HashMap<_, _>, Vec<_>) = [100, 200]
+            .into_iter()
+            .map(|id| {
+                let (sender, receiver) = unbounded();
+                ((id, sender), receiver)
+            })
+            .unzip();
+        let node_senders = [1, 100, 200].into_iter().map(|id| (id, unbounded().0)).collect();
+        let network = NetworkControl::new(
+            config,
+            HashMap::from([(1, drone_sender)]),
+            host_commands,
+            node_senders,
+            Default::default(),
+        );
+        (network, drone_receiver, host_receivers)
+    }
+
+    #[test]
+    fn events_are_sorted_by_time() {
+        let scenario = load(
+            "sorted",
+            "[[event]]\nat = 12\naction = \"crash\"\ndrone = 1\n\n\
+             [[event]]\nat = 0.5\naction = \"set_pdr\"\ndrone = 2\npdr = 0.3\n\n\
+             [[event]]\nat = 5\naction = \"remove_link\"\nnodes = [1, 2]\n",
+        )
+        .unwrap();
+
+        let times: Vec<f64> = scenario.event.iter().map(|e| e.at).collect();
+        assert_eq!(times, [0.5, 5., 12.]);
+        assert!(matches!(scenario.event[0].action, ScenarioAction::SetPdr { drone: 2, .. }));
+        assert!(!scenario.allow_invalid);
+    }
+
+    #[test]
+    fn invalid_times_are_rejected() {
+        for at in ["-1", "nan", "inf"] {
+            let error = load("time", &format!("[[event]]\nat = {}\naction = \"crash\"\ndrone = 1\n", at)).unwrap_err();
+            assert!(error.contains("has an invalid time"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn invalid_changes_are_only_applied_when_allowed() {
+        let action = ScenarioAction::RemoveLink { nodes: [100, 1] };
+
+        let (network, commands, _hosts) = chain();
+        assert!(apply(&network, &action, false).unwrap_err().contains("Refusing to"));
+        assert!(commands.try_recv().is_err());
+        assert!(topology::linked(&network.topology(), 100, 1));
+
+        let (network, commands, _hosts) = chain();
+        assert_eq!(apply(&network, &action, true), Ok(()));
+        assert!(matches!(commands.try_recv(), Ok(DroneCommand::RemoveSender(100))));
+        assert!(!topology::linked(&network.topology(), 100, 1));
+    }
+}
@@ -0,0 +1,188 @@
+use std::collections::{BTreeSet, HashMap};
+use wg_2024::config::Config;
+use wg_2024::network::NodeId;
+
+use crate::utils::{config_loader, extended_config, topology};
+
+/// What happens to a packet sent to a node whose buffer is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// The sender waits until there is room
+    #[default]
+    Block,
+    /// The packet is dropped by the link layer
+    Drop,
+}
+
+/// Size of the inbound buffer of the nodes, read from the `[buffers]` table of the topology file.
+/// Without a capacity the buffers are unbounded:
+///
+/// ```toml
+/// [buffers]
+/// capacity = 64      # packets, for every node
+/// overflow = "drop"  # or "block" (default)
+///
+/// [buffers.nodes]
+/// 3 = 8                                    # capacity of node 3
+/// 5 = { capacity = 16, overflow = "block" }
+/// ```
+///
+/// The tables of included files apply to their nodes, with the ids shifted by the include offset.
+#[derive(Debug, Clone, Default)]
+pub struct BufferSettings {
+    pub capacity: Option<usize>,
+    pub overflow: OverflowPolicy,
+    nodes: HashMap<NodeId, NodeBuffer>,
+}
+
+// The settings of a single node, what is missing comes from the settings of every node
+#[derive(Debug, Clone, Copy, Default)]
+struct NodeBuffer {
+    capacity: Option<usize>,
+    overflow: Option<OverflowPolicy>,
+}
+
+impl BufferSettings {
+    /// Reads the `[buffers]` tables of a topology file and of the files it includes,
+    /// the buffers are unbounded if there is none
+    pub fn load(path: &str) -> Result<Self, String> {
+        let mut settings = BufferSettings::default();
+        for (table, offset) in config_loader::read_settings(path, "buffers")? {
+            settings.apply(&table, offset)?;
+        }
+        Ok(settings)
+    }
+
+    fn apply(&mut self, table: &toml::Table, offset: NodeId) -> Result<(), String> {
+        for (key, value) in table {
+            match key.as_str() {
+                "capacity" => self.capacity = Some(parse_capacity(key, value)?),
+                "overflow" => self.overflow = parse_overflow(value)?,
+                "nodes" => {
+                    let nodes = value.as_table().ok_or("\"buffers.nodes\" must be a table".to_string())?;
+                    for (id, node) in nodes {
+                        let id = id
+                            .parse::<NodeId>()
+                            .map_err(|_| format!("Invalid node Id \"{}\" in buffers.nodes", id))?;
+                        let id = extended_config::shift_id(id, offset)
+                            .map_err(|error_message| format!("Invalid node Id in buffers.nodes: {}", error_message))?;
+                        let buffer = self.nodes.entry(id).or_default();
+                        match node {
+                            toml::Value::Table(node) => {
+                                for (key, value) in node {
+                                    match key.as_str() {
+                                        "capacity" => buffer.capacity = Some(parse_capacity(&id.to_string(), value)?),
+                                        "overflow" => buffer.overflow = Some(parse_overflow(value)?),
+                                        _ => {
+                                            return Err(format!(
+                                                "Unknown buffer setting \"{}\" of node {}! Valid settings: capacity, overflow",
+                                                key, id
+                                            ))
+                                        }
+                                    }
+                                }
+                            }
+                            capacity => buffer.capacity = Some(parse_capacity(&id.to_string(), capacity)?),
+                        }
+                    }
+                }
+                _ => return Err(format!("Unknown buffer setting \"{}\"! Valid settings: capacity, overflow, nodes", key)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the nodes of `buffers.nodes` are in the topology
+    pub fn check_nodes(&self, config: &Config) -> Result<(), String> {
+        let existing = topology::node_ids(config);
+        let unknown: BTreeSet<NodeId> = self.nodes.keys().copied().filter(|id| !existing.contains(id)).collect();
+        if unknown.is_empty() {
+            return Ok(());
+        }
+        let unknown: Vec<String> = unknown.iter().map(|id| id.to_string()).collect();
+        Err(format!("buffers.nodes refers to nodes that do not exist: {}", unknown.join(", ")))
+    }
+
+    /// Capacity of the buffer of a node, None if it is unbounded
+    pub fn capacity(&self, id: NodeId) -> Option<usize> {
+        self.nodes.get(&id).and_then(|node| node.capacity).or(self.capacity)
+    }
+
+    /// What happens to the packets that do not fit in the buffer of a node
+    pub fn overflow(&self, id: NodeId) -> OverflowPolicy {
+        self.nodes.get(&id).and_then(|node| node.overflow).unwrap_or(self.overflow)
+    }
+}
+
+fn parse_capacity(key: &str, value: &toml::Value) -> Result<usize, String> {
+    value
+        .as_integer()
+        .and_then(|v| usize::try_from(v).ok())
+        .filter(|v| *v > 0)
+        .ok_or(format!("Invalid buffer capacity {} = {}, expected a positive integer", key, value))
+}
+
+fn parse_overflow(value: &toml::Value) -> Result<OverflowPolicy, String> {
+    match value.as_str() {
+        Some("block") => Ok(OverflowPolicy::Block),
+        Some("drop") => Ok(OverflowPolicy::Drop),
+        _ => Err(format!("Invalid overflow policy {}, expected \"block\" or \"drop\"", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(source: &str) -> toml::Table {
+        toml::from_str(source).unwrap()
+    }
+
+    #[test]
+    fn nodes_override_the_default_buffer() {
+        let mut settings = BufferSettings::default();
+        settings
+            .apply(&table("capacity = 64\noverflow = \"drop\"\n[nodes]\n3 = 8\n5 = { overflow = \"block\" }"), 0)
+            .unwrap();
+
+        assert_eq!((settings.capacity(3), settings.overflow(3)), (Some(8), OverflowPolicy::Drop));
+        assert_eq!((settings.capacity(5), settings.overflow(5)), (Some(64), OverflowPolicy::Block));
+        assert_eq!((settings.capacity(7), settings.overflow(7)), (Some(64), OverflowPolicy::Drop));
+    }
+
+    #[test]
+    fn shifts_the_nodes_of_included_files() {
+        let mut settings = BufferSettings::default();
+        settings.apply(&table("[nodes]\n3 = { capacity = 2, overflow = \"drop\" }"), 50).unwrap();
+
+        assert_eq!(settings.capacity(3), None);
+        assert_eq!((settings.capacity(53), settings.overflow(53)), (Some(2), OverflowPolicy::Drop));
+    }
+
+    #[test]
+    fn rejects_nodes_shifted_past_the_maximum_id() {
+        let error = BufferSettings::default().apply(&table("[nodes]\n200 = 4"), 100).unwrap_err();
+        assert_eq!(error, "Invalid node Id in buffers.nodes: Node 200 with offset 100 exceeds the maximum id 255");
+    }
+
+    #[test]
+    fn reports_nodes_missing_from_the_topology() {
+        let config = crate::utils::topology::tests::config_with_links(&[(1, 0.1), (2, 0.1)], &[100], &[], &[]);
+        let mut settings = BufferSettings::default();
+        settings.apply(&table("capacity = 4\n[nodes]\n2 = 8\n100 = { overflow = \"drop\" }"), 0).unwrap();
+        assert_eq!(settings.check_nodes(&config), Ok(()));
+
+        settings.apply(&table("[nodes]\n9 = { overflow = \"drop\" }\n3 = 2"), 0).unwrap();
+        assert_eq!(
+            settings.check_nodes(&config),
+            Err("buffers.nodes refers to nodes that do not exist: 3, 9".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_settings() {
+        for source in ["capacity = 0", "overflow = \"wait\"", "size = 3", "nodes = 3", "[nodes]\nx = 3", "[nodes]\n3 = { size = 3 }"] {
+            assert!(BufferSettings::default().apply(&table(source), 0).is_err(), "{}", source);
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::HashSet;
+use wg_2024::config::Config;
+use wg_2024::network::NodeId;
+
+/// Corrects the mechanical mistakes of a topology, returning a description of each change:
+/// 1. neighbours that do not exist are removed;
+/// 2. nodes listed among their own neighbours are removed from them;
+/// 3. repeated neighbours are removed;
+/// 4. one-way links get their missing reverse side;
+/// 5. PDRs out of range are clamped to [0, 1].
+///
+/// Other mistakes, like clients with too many links, need a decision and are left to the validator.
+pub fn fix_config(config: &mut Config) -> Vec<String> {
+    let mut changes = Vec::new();
+    let ids: HashSet<NodeId> = nodes(config).map(|(_, id, _)| id).collect();
+
+    for (kind, id, neighbours) in nodes(config) {
+        let mut seen = HashSet::new();
+        neighbours.retain(|n| {
+            if !ids.contains(n) {
+                changes.push(format!("Removed neighbour {} of {} {}, which does not exist", n, kind, id));
+                false
+            } else if *n == id {
+                changes.push(format!("Removed {} {} from its own neighbours", kind, id));
+                false
+            } else if !seen.insert(*n) {
+                changes.push(format!("Removed repeated neighbour {} of {} {}", n, kind, id));
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    let links: HashSet<(NodeId, NodeId)> = nodes(config)
+        .flat_map(|(_, id, neighbours)| neighbours.iter().map(move |n| (id, *n)))
+        .collect();
+    for (kind, id, neighbours) in nodes(config) {
+        let mut missing: Vec<NodeId> = links
+            .iter()
+            .filter(|(a, b)| *b == id && !links.contains(&(*b, *a)))
+            .map(|(a, _)| *a)
+            .collect();
+        missing.sort();
+        for n in missing {
+            neighbours.push(n);
+            changes.push(format!("Added neighbour {} to {} {}, to complete the one-way link from {}", n, kind, id, n));
+        }
+    }
+
+    for drone in &mut config.drone {
+        if !(0. ..=1.).contains(&drone.pdr) {
+            let pdr = if drone.pdr.is_nan() { 0. } else { drone.pdr.clamp(0., 1.) };
+            changes.push(format!("Clamped the PDR of drone {} from {} to {}", drone.id, drone.pdr, pdr));
+            drone.pdr = pdr;
+        }
+    }
+
+    changes
+}
+
+// Every node, with its kind and its neighbours
+fn nodes(config: &mut Config) -> impl Iterator<Item = (&'static str, NodeId, &mut Vec<NodeId>)> {
+    let drones = config.drone.iter_mut().map(|d| ("drone", d.id, &mut d.connected_node_ids));
+    let clients = config.client.iter_mut().map(|c| ("client", c.id, &mut c.connected_drone_ids));
+    let servers = config.server.iter_mut().map(|s| ("server", s.id, &mut s.connected_drone_ids));
+    drones.chain(clients).chain(servers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wg_2024::config::{Client, Drone, Server};
+
+    fn drone(id: NodeId, connected_node_ids: Vec<NodeId>, pdr: f32) -> Drone {
+        Drone { id, connected_node_ids, pdr }
+    }
+
+    #[test]
+    fn fixes_the_neighbour_lists() {
+        let mut config = Config {
+            drone: vec![drone(1, vec![1, 2, 2, 9, 100], 0.1), drone(2, vec![200], 0.1), drone(3, vec![2, 200], 0.1)],
+            client: vec![Client { id: 100, connected_drone_ids: vec![1] }],
+            server: vec![Server { id: 200, connected_drone_ids: vec![2] }],
+        };
+        let changes = fix_config(&mut config);
+
+        assert_eq!(config.drone[0].connected_node_ids, [2, 100]);
+        assert_eq!(config.drone[1].connected_node_ids, [200, 1, 3]);
+        assert_eq!(config.drone[2].connected_node_ids, [2, 200]);
+        assert_eq!(config.server[0].connected_drone_ids, [2, 3]);
+        assert_eq!(
+            changes,
+            [
+                "Removed drone 1 from its own neighbours",
+                "Removed repeated neighbour 2 of drone 1",
+                "Removed neighbour 9 of drone 1, which does not exist",
+                "Added neighbour 1 to drone 2, to complete the one-way link from 1",
+                "Added neighbour 3 to drone 2, to complete the one-way link from 3",
+                "Added neighbour 3 to server 200, to complete the one-way link from 3",
+            ]
+        );
+    }
+
+    #[test]
+    fn clamps_the_pdrs() {
+        let mut config = Config {
+            drone: vec![drone(1, vec![], -0.5), drone(2, vec![], 1.5), drone(3, vec![], f32::NAN), drone(4, vec![], 0.3)],
+            client: vec![],
+            server: vec![],
+        };
+        let changes = fix_config(&mut config);
+
+        assert_eq!(config.drone.iter().map(|d| d.pdr).collect::<Vec<_>>(), [0., 1., 0., 0.3]);
+        assert_eq!(changes.len(), 3);
+    }
+
+    #[test]
+    fn leaves_a_valid_topology_untouched() {
+        let mut config = Config {
+            drone: vec![drone(1, vec![100, 200], 0.1)],
+            client: vec![Client { id: 100, connected_drone_ids: vec![1] }],
+            server: vec![Server { id: 200, connected_drone_ids: vec![1] }],
+        };
+        assert!(fix_config(&mut config).is_empty());
+    }
+}
@@ -0,0 +1,240 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use wg_2024::config::Config;
+use wg_2024::network::NodeId;
+
+use crate::utils::extended_config::{self, ExtendedConfig, Include};
+use crate::utils::input_validator::{self, Subject};
+use crate::utils::source_map::SourceMap;
+
+/// Reads, parses and validates a topology file.
+/// The file can use the extended format, whose includes, ranges and links are expanded
+/// before the validation. Validation errors point at the lines of the files they refer to.
+pub fn load_config(path: &str) -> Result<Config, String> {
+    load_config_with_sources(path).map(|(config, _)| config)
+}
+
+/// Like `load_config`, also returning where each part of the topology is written
+pub fn load_config_with_sources(path: &str) -> Result<(Config, SourceMap), String> {
+    let (config, source_map) = load_pieces(Path::new(path), &mut Vec::new())?;
+
+    input_validator::validate_config(&config).map_err(|error| source_map.render(&error))?;
+
+    Ok((config, source_map))
+}
+
+/// Reads and parses a topology file like `load_config`, without validating it
+pub fn read_config(path: &str) -> Result<Config, String> {
+    load_pieces(Path::new(path), &mut Vec::new()).map(|(config, _)| config)
+}
+
+/// Reads the settings table `name` (e.g. "lints") of a topology file and of the files it includes.
+/// The tables are returned in the order they must be applied, the included files first,
+/// so that the including file has the last word. Each table comes with the offset of the ids
+/// of its file, for the settings that refer to nodes.
+pub fn read_settings(path: &str, name: &str) -> Result<Vec<(toml::Table, NodeId)>, String> {
+    let mut tables = Vec::new();
+    collect_settings(Path::new(path), name, 0, &mut Vec::new(), &mut tables)?;
+    Ok(tables)
+}
+
+fn collect_settings(
+    path: &Path,
+    name: &str,
+    offset: NodeId,
+    including: &mut Vec<PathBuf>,
+    tables: &mut Vec<(toml::Table, NodeId)>,
+) -> Result<(), String> {
+    let data = fs::read_to_string(path)
+        .map_err(|e| format!("Unable to read config file \"{}\": {}", path.display(), e))?;
+    let mut file: toml::Table = toml::from_str(&data)
+        .map_err(|e| format!("Unable to parse TOML file \"{}\": {}", path.display(), e))?;
+
+    let canonical = path.canonicalize().unwrap_or(path.to_path_buf());
+    if including.contains(&canonical) {
+        return Err(format!("\"{}\" includes itself", path.display()));
+    }
+    including.push(canonical);
+
+    if let Some(includes) = file.remove("include") {
+        let includes: Vec<Include> = includes
+            .try_into()
+            .map_err(|e| format!("Invalid include in \"{}\": {}", path.display(), e))?;
+        for include in includes {
+            let piece_path = path.parent().unwrap_or(Path::new("")).join(&include.path);
+            let piece_offset = offset.checked_add(include.offset).ok_or(format!(
+                "The offset {} of \"{}\" added to the offset {} of \"{}\" exceeds the maximum id {}",
+                include.offset,
+                include.path,
+                offset,
+                path.display(),
+                NodeId::MAX
+            ))?;
+            collect_settings(&piece_path, name, piece_offset, including, tables)?;
+        }
+    }
+    including.pop();
+
+    match file.remove(name) {
+        Some(toml::Value::Table(table)) => tables.push((table, offset)),
+        Some(_) => return Err(format!("\"{}\" must be a table in \"{}\"", name, path.display())),
+        None => {}
+    }
+    Ok(())
+}
+
+// Reads a topology file and, recursively, the files it includes.
+// `including` holds the files being read, to reject cyclic includes.
+// Errors are rendered with the locations they refer to; the errors of an included file
+// also point at the include that brought it in.
+fn load_pieces(path: &Path, including: &mut Vec<PathBuf>) -> Result<(Config, SourceMap), String> {
+    let config_data = fs::read_to_string(path)
+        .map_err(|e| format!("Unable to read config file \"{}\": {}", path.display(), e))?;
+    let mut config: ExtendedConfig = toml::from_str(&config_data)
+        .map_err(|e| format!("Unable to parse TOML file \"{}\": {}", path.display(), e))?;
+    let mut source_map = SourceMap::default();
+    source_map.add_file(&path.display().to_string(), &config_data);
+
+    including.push(path.canonicalize().unwrap_or(path.to_path_buf()));
+
+    let mut pieces = Vec::new();
+    for include in std::mem::take(&mut config.include) {
+        let included_here = [Subject::Include(include.path.clone())];
+        let piece_path = path.parent().unwrap_or(Path::new("")).join(&include.path);
+        if including.contains(&piece_path.canonicalize().unwrap_or(piece_path.clone())) {
+            let error_message = format!("\"{}\" includes itself", include.path);
+            return Err(source_map.render_subjects(&error_message, &included_here));
+        }
+
+        let (mut piece, piece_map) = load_pieces(&piece_path, including)
+            .map_err(|error_message| source_map.render_subjects(&error_message, &included_here))?;
+        let unable_to_include = |source_map: &SourceMap, error_message: String| {
+            let error_message = format!("Unable to include \"{}\": {}", include.path, error_message);
+            source_map.render_subjects(&error_message, &included_here)
+        };
+        extended_config::shift_ids(&mut piece, include.offset)
+            .map_err(|error_message| unable_to_include(&source_map, error_message))?;
+
+        let name = if include.offset == 0 {
+            format!("\"{}\"", include.path)
+        } else {
+            format!("\"{}\" (offset {})", include.path, include.offset)
+        };
+        pieces.push((name, piece));
+        if let Err(error_message) = source_map.include(piece_map, include.offset) {
+            return Err(unable_to_include(&source_map, error_message));
+        }
+    }
+
+    including.pop();
+    let config = config.expand(pieces).map_err(|error| source_map.render(&error))?;
+    Ok((config, source_map))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_settings_of_the_included_files_first() {
+        let dir = std::env::temp_dir().join(format!("settings-{}", std::process::id()));
+        fs::create_dir_all(dir.join("regions")).unwrap();
+        fs::write(dir.join("main.toml"), "[[include]]\npath = \"regions/a.toml\"\noffset = 10\n\n[lints]\nlong_path = \"deny\"\n").unwrap();
+        fs::write(dir.join("regions/a.toml"), "[[include]]\npath = \"b.toml\"\noffset = 5\n\n[lints]\nlong_path = \"allow\"\n").unwrap();
+        fs::write(dir.join("regions/b.toml"), "[buffers]\ncapacity = 4\n").unwrap();
+
+        let main = dir.join("main.toml").display().to_string();
+        let lints: Vec<(String, NodeId)> = read_settings(&main, "lints")
+            .unwrap()
+            .into_iter()
+            .map(|(table, offset)| (table["long_path"].to_string(), offset))
+            .collect();
+        assert_eq!(lints, [("\"allow\"".to_string(), 10), ("\"deny\"".to_string(), 0)]);
+        let buffers = read_settings(&main, "buffers").unwrap();
+        assert_eq!(buffers.len(), 1);
+        assert_eq!(buffers[0].1, 15);
+
+        fs::write(dir.join("regions/b.toml"), "[[include]]\npath = \"../main.toml\"\n").unwrap();
+        assert!(read_settings(&main, "lints").is_err());
+
+        fs::write(dir.join("regions/b.toml"), "[[include]]\npath = \"c.toml\"\noffset = 250\n").unwrap();
+        fs::write(dir.join("regions/c.toml"), "[buffers]\ncapacity = 4\n").unwrap();
+        let error = read_settings(&main, "buffers").unwrap_err();
+        assert!(error.starts_with("The offset 250 of \"c.toml\" added to the offset 15 of "), "{}", error);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    // Writes the files in a fresh directory and returns its path
+    fn write_files(name: &str, files: &[(&str, &str)]) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+        for (path, data) in files {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, data).unwrap();
+        }
+        dir
+    }
+
+    const REGION: &str = "[[drone]]\nid = 1\npdr = 0.1\n\n[[drone]]\nid = 2\npdr = 0.2\n\n[[client]]\nid = 10\n\n\
+                          [[link]]\na = 1\nb = 2\n\n[[link]]\na = 10\nb = 1\n";
+
+    #[test]
+    fn includes_are_shifted_by_their_offsets() {
+        let dir = write_files(
+            "include-offsets",
+            &[
+                ("main.toml", "[[include]]\npath = \"regions/a.toml\"\n\n[[include]]\npath = \"regions/a.toml\"\noffset = 50\n\n[[link]]\na = 2\nb = 51\n"),
+                ("regions/a.toml", REGION),
+            ],
+        );
+
+        let config = read_config(&dir.join("main.toml").display().to_string()).unwrap();
+        let drones: Vec<(NodeId, Vec<NodeId>)> = config.drone.iter().map(|d| (d.id, d.connected_node_ids.clone())).collect();
+        assert_eq!(drones, [(1, vec![2, 10]), (2, vec![1, 51]), (51, vec![52, 60, 2]), (52, vec![51])]);
+        assert_eq!(config.client.iter().map(|c| c.id).collect::<Vec<_>>(), [10, 60]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn colliding_includes_are_errors() {
+        let dir = write_files(
+            "include-collisions",
+            &[
+                ("main.toml", "[[include]]\npath = \"a.toml\"\n\n[[include]]\npath = \"a.toml\"\noffset = 1\n"),
+                ("a.toml", REGION),
+            ],
+        );
+
+        let error = read_config(&dir.join("main.toml").display().to_string()).unwrap_err();
+        assert!(
+            error.starts_with("Node 2 of \"a.toml\" (offset 1) collides with a node of \"a.toml\", change the offsets"),
+            "{}",
+            error
+        );
+        // Both definitions of the node are shown
+        assert!(error.contains("a.toml:5:1") && error.contains("a.toml:1:1"), "{}", error);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn cyclic_includes_are_errors() {
+        let dir = write_files(
+            "include-cycles",
+            &[
+                ("self.toml", "[[include]]\npath = \"self.toml\"\noffset = 10\n"),
+                ("a.toml", "[[include]]\npath = \"b.toml\"\noffset = 10\n"),
+                ("b.toml", "[[include]]\npath = \"a.toml\"\noffset = 10\n"),
+            ],
+        );
+
+        let error = read_config(&dir.join("self.toml").display().to_string()).unwrap_err();
+        assert!(error.contains("\"self.toml\" includes itself"), "{}", error);
+        let error = read_config(&dir.join("a.toml").display().to_string()).unwrap_err();
+        assert!(error.contains("\"a.toml\" includes itself"), "{}", error);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,40 @@
+use std::fmt::Write;
+use wg_2024::config::Config;
+use wg_2024::network::NodeId;
+
+/// Writes the topology in the same layout as `input.toml`.
+/// PDRs are written with their shortest representation (`toml` would widen them to f64).
+pub fn to_toml(config: &Config) -> String {
+    let mut toml = String::new();
+
+    for drone in &config.drone {
+        writeln!(toml, "[[drone]]").unwrap();
+        writeln!(toml, "id = {}", drone.id).unwrap();
+        writeln!(toml, "connected_node_ids = {}", id_list(&drone.connected_node_ids)).unwrap();
+        writeln!(toml, "pdr = {:?}", drone.pdr).unwrap();
+        writeln!(toml).unwrap();
+    }
+
+    for client in &config.client {
+        writeln!(toml, "[[client]]").unwrap();
+        writeln!(toml, "id = {}", client.id).unwrap();
+        writeln!(toml, "connected_drone_ids = {}", id_list(&client.connected_drone_ids)).unwrap();
+        writeln!(toml).unwrap();
+    }
+
+    for server in &config.server {
+        writeln!(toml, "[[server]]").unwrap();
+        writeln!(toml, "id = {}", server.id).unwrap();
+        writeln!(toml, "connected_drone_ids = {}", id_list(&server.connected_drone_ids)).unwrap();
+        writeln!(toml).unwrap();
+    }
+
+    toml.truncate(toml.trim_end().len());
+    toml.push('\n');
+    toml
+}
+
+fn id_list(ids: &[NodeId]) -> String {
+    let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+    format!("[{}]", ids.join(", "))
+}
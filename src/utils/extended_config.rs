@@ -0,0 +1,495 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use wg_2024::config::{Client, Config, Drone, Server};
+use wg_2024::network::NodeId;
+
+use crate::generator::PdrDistribution;
+use crate::utils::input_validator::{Subject, ValidationError};
+use crate::utils::topology;
+
+/// Topology file in the extended format: the classic `wg_2024` sections, where the neighbour
+/// lists may be omitted, plus a list of links that are expanded into both endpoints:
+///
+/// ```toml
+/// [[drone]]
+/// id = 1
+/// pdr = 0.05
+///
+/// [[client]]
+/// id = 100
+///
+/// [[link]]
+/// a = 1
+/// b = 100
+/// ```
+///
+/// Many similar nodes can be defined at once with ranges, whose hosts are attached
+/// to a pool of drones:
+///
+/// ```toml
+/// [[drone_range]]
+/// ids = "1..=40"
+/// pdr = "uniform(0.0, 0.2)"
+/// seed = 7
+///
+/// [[client_range]]
+/// ids = "100..=109"
+/// drones = "1..=10"
+/// links = 2
+/// ```
+///
+/// Other topology files can be included as pieces of the network, with their ids shifted
+/// by an offset; the links of the including file can then bridge the pieces:
+///
+/// ```toml
+/// [[include]]
+/// path = "region.toml"
+///
+/// [[include]]
+/// path = "region.toml"
+/// offset = 50
+///
+/// [[link]]
+/// a = 3
+/// b = 53
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExtendedConfig {
+    #[serde(default)]
+    pub drone: Vec<ExtendedDrone>,
+    #[serde(default)]
+    pub client: Vec<ExtendedHost>,
+    #[serde(default)]
+    pub server: Vec<ExtendedHost>,
+    #[serde(default)]
+    pub link: Vec<Link>,
+    #[serde(default)]
+    pub drone_range: Vec<DroneRange>,
+    #[serde(default)]
+    pub client_range: Vec<HostRange>,
+    #[serde(default)]
+    pub server_range: Vec<HostRange>,
+    #[serde(default)]
+    pub include: Vec<Include>,
+}
+
+/// A topology file merged into the including one, with `offset` added to all its ids.
+/// Relative paths start from the directory of the including file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Include {
+    pub path: String,
+    #[serde(default)]
+    pub offset: NodeId,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExtendedDrone {
+    pub id: NodeId,
+    #[serde(default)]
+    pub connected_node_ids: Vec<NodeId>,
+    pub pdr: f32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExtendedHost {
+    pub id: NodeId,
+    #[serde(default)]
+    pub connected_drone_ids: Vec<NodeId>,
+}
+
+/// A bidirectional link between two nodes
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct Link {
+    pub a: NodeId,
+    pub b: NodeId,
+}
+
+/// Node ids, either as a range ("1..=40", "1..41") or as a list
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Ids {
+    Range(String),
+    List(Vec<NodeId>),
+}
+
+/// Either a PDR value or a distribution, e.g. "uniform(0.0, 0.2)"
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Pdr {
+    Value(f32),
+    Distribution(String),
+}
+
+/// Drones without neighbours, whose links come from the `[[link]]` sections and the host ranges
+#[derive(Debug, Clone, Deserialize)]
+pub struct DroneRange {
+    pub ids: Ids,
+    pub pdr: Pdr,
+    /// Seed of the PDR distribution, so that the same file always gives the same PDRs
+    #[serde(default)]
+    pub seed: u64,
+}
+
+/// Hosts attached to a pool of drones: each host takes the next `links` drones of the pool,
+/// wrapping around, so the hosts are spread evenly over the pool
+#[derive(Debug, Clone, Deserialize)]
+pub struct HostRange {
+    pub ids: Ids,
+    pub drones: Ids,
+    /// Drones each host is connected to, by default 1 for clients and 2 for servers
+    pub links: Option<usize>,
+}
+
+impl Ids {
+    pub fn expand(&self) -> Result<Vec<NodeId>, String> {
+        let range = match self {
+            Ids::List(ids) => return Ok(ids.clone()),
+            Ids::Range(range) => range,
+        };
+        let invalid = || format!("Invalid id range \"{}\", expected \"first..=last\" or \"first..end\"", range);
+
+        let (first, last, inclusive) = match range.split_once("..=") {
+            Some((first, last)) => (first, last, true),
+            None => range.split_once("..").map(|(first, end)| (first, end, false)).ok_or_else(invalid)?,
+        };
+        let first: NodeId = first.trim().parse().map_err(|_| invalid())?;
+        let last: NodeId = last.trim().parse().map_err(|_| invalid())?;
+
+        let ids: Vec<NodeId> = if inclusive { (first..=last).collect() } else { (first..last).collect() };
+        if ids.is_empty() {
+            return Err(format!("Id range \"{}\" is empty, its first id must come before its end", range));
+        }
+        Ok(ids)
+    }
+}
+
+impl fmt::Display for Ids {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Ids::Range(range) => write!(f, "{}", range),
+            Ids::List(ids) => write!(f, "{:?}", ids),
+        }
+    }
+}
+
+impl ExtendedConfig {
+    /// Expands the ranges into single nodes, and their host attachments into links
+    fn expand_ranges(&mut self) -> Result<(), ValidationError> {
+        for range in &self.drone_range {
+            let in_range = |error_message: String| ValidationError::new(error_message, vec![Subject::Range(range.ids.to_string())]);
+            let distribution = match &range.pdr {
+                Pdr::Value(pdr) => PdrDistribution::Constant(*pdr),
+                Pdr::Distribution(distribution) => PdrDistribution::parse(distribution).map_err(in_range)?,
+            };
+            let mut rng = ChaCha8Rng::seed_from_u64(range.seed);
+
+            for id in range.ids.expand().map_err(in_range)? {
+                self.drone.push(ExtendedDrone { id, connected_node_ids: Vec::new(), pdr: distribution.sample(&mut rng) });
+            }
+        }
+
+        for (ranges, is_client) in [(&self.client_range, true), (&self.server_range, false)] {
+            for range in ranges {
+                let in_range = |error_message: String| ValidationError::new(error_message, vec![Subject::Range(range.ids.to_string())]);
+                let pool = range.drones.expand().map_err(in_range)?;
+                let links = range.links.unwrap_or(if is_client { 1 } else { 2 });
+                if links > pool.len() {
+                    return Err(in_range(format!(
+                        "Host range \"{}\" needs {} drones, but its pool has {}",
+                        range.ids, links, pool.len()
+                    )));
+                }
+
+                for (i, id) in range.ids.expand().map_err(in_range)?.into_iter().enumerate() {
+                    let host = ExtendedHost { id, connected_drone_ids: Vec::new() };
+                    if is_client {
+                        self.client.push(host);
+                    } else {
+                        self.server.push(host);
+                    }
+                    for j in 0..links {
+                        self.link.push(Link { a: id, b: pool[(i * links + j) % pool.len()] });
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Adds the nodes of the included pieces, already expanded and shifted by their offsets.
+    /// Each piece is named by its path, to report the ids that collide with other pieces.
+    fn add_pieces(&mut self, pieces: Vec<(String, Config)>) -> Result<(), ValidationError> {
+        let mut origins: HashMap<NodeId, String> = HashMap::new();
+        for id in self.drone.iter().map(|d| d.id).chain(self.client.iter().chain(&self.server).map(|h| h.id)) {
+            origins.insert(id, "the including file".to_string());
+        }
+
+        for (name, piece) in pieces {
+            let piece_ids = piece.drone.iter().map(|d| d.id).chain(piece.client.iter().map(|c| c.id));
+            for id in piece_ids.chain(piece.server.iter().map(|s| s.id)) {
+                if let Some(origin) = origins.insert(id, name.clone()) {
+                    return Err(ValidationError::new(
+                        format!("Node {} of {} collides with a node of {}, change the offsets", id, name, origin),
+                        vec![Subject::Node(id)],
+                    ));
+                }
+            }
+
+            for drone in piece.drone {
+                self.drone.push(ExtendedDrone { id: drone.id, connected_node_ids: drone.connected_node_ids, pdr: drone.pdr });
+            }
+            for client in piece.client {
+                self.client.push(ExtendedHost { id: client.id, connected_drone_ids: client.connected_drone_ids });
+            }
+            for server in piece.server {
+                self.server.push(ExtendedHost { id: server.id, connected_drone_ids: server.connected_drone_ids });
+            }
+        }
+        Ok(())
+    }
+
+    /// Expands the ranges and the links into the neighbour lists of their endpoints, giving a plain `Config`.
+    /// `pieces` are the included topologies, see `add_pieces`.
+    /// Links already present in a neighbour list are not repeated, while the neighbour lists
+    /// are kept as they are, so one-way mistakes in them are still caught by the validator.
+    /// Errors refer to the ranges, nodes and links they come from.
+    pub fn expand(mut self, pieces: Vec<(String, Config)>) -> Result<Config, ValidationError> {
+        self.expand_ranges()?;
+        self.add_pieces(pieces)?;
+
+        let mut config = Config {
+            drone: self
+                .drone
+                .into_iter()
+                .map(|d| Drone { id: d.id, connected_node_ids: d.connected_node_ids, pdr: d.pdr })
+                .collect(),
+            client: self
+                .client
+                .into_iter()
+                .map(|c| Client { id: c.id, connected_drone_ids: c.connected_drone_ids })
+                .collect(),
+            server: self
+                .server
+                .into_iter()
+                .map(|s| Server { id: s.id, connected_drone_ids: s.connected_drone_ids })
+                .collect(),
+        };
+
+        for link in &self.link {
+            if link.a == link.b {
+                return Err(ValidationError::new(
+                    format!("Link {}-{} connects a node to itself!", link.a, link.b),
+                    vec![Subject::Neighbour(link.a, link.b)],
+                ));
+            }
+            for (from, to) in [(link.a, link.b), (link.b, link.a)] {
+                let neighbours = topology::neighbours_mut(&mut config, from).ok_or(ValidationError::new(
+                    format!("Link {}-{} refers to node {}, which does not exist", link.a, link.b, from),
+                    vec![Subject::Neighbour(link.a, link.b)],
+                ))?;
+                if !neighbours.contains(&to) {
+                    neighbours.push(to);
+                }
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+/// Whether a topology file uses the sections of the extended format, which the plain format cannot keep
+pub fn uses_extended_format(source: &str) -> bool {
+    let Ok(file) = toml::from_str::<toml::Table>(source) else {
+        return false;
+    };
+    ["include", "link", "drone_range", "client_range", "server_range"]
+        .iter()
+        .any(|section| file.contains_key(*section))
+}
+
+/// Adds `offset` to an id of an included file
+pub fn shift_id(id: NodeId, offset: NodeId) -> Result<NodeId, String> {
+    id.checked_add(offset)
+        .ok_or(format!("Node {} with offset {} exceeds the maximum id {}", id, offset, NodeId::MAX))
+}
+
+/// Adds `offset` to every id of the topology
+pub fn shift_ids(config: &mut Config, offset: NodeId) -> Result<(), String> {
+    let shift = |id: &mut NodeId| -> Result<(), String> {
+        *id = shift_id(*id, offset)?;
+        Ok(())
+    };
+
+    for drone in &mut config.drone {
+        shift(&mut drone.id)?;
+        drone.connected_node_ids.iter_mut().try_for_each(shift)?;
+    }
+    for client in &mut config.client {
+        shift(&mut client.id)?;
+        client.connected_drone_ids.iter_mut().try_for_each(shift)?;
+    }
+    for server in &mut config.server {
+        shift(&mut server.id)?;
+        server.connected_drone_ids.iter_mut().try_for_each(shift)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(source: &str) -> Result<Config, ValidationError> {
+        toml::from_str::<ExtendedConfig>(source).unwrap().expand(Vec::new())
+    }
+
+    fn neighbours(config: &Config, id: NodeId) -> Vec<NodeId> {
+        topology::neighbours(config, id).unwrap().clone()
+    }
+
+    #[test]
+    fn links_connect_both_endpoints() {
+        let config = expand(
+            "[[drone]]\nid = 1\npdr = 0.1\n\n[[drone]]\nid = 2\npdr = 0.2\n\n[[client]]\nid = 100\n\n\
+             [[link]]\na = 1\nb = 2\n\n[[link]]\na = 100\nb = 1\n",
+        )
+        .unwrap();
+
+        assert_eq!(neighbours(&config, 1), [2, 100]);
+        assert_eq!(neighbours(&config, 2), [1]);
+        assert_eq!(neighbours(&config, 100), [1]);
+        assert_eq!(config.drone[1].pdr, 0.2);
+    }
+
+    #[test]
+    fn links_mix_with_neighbour_lists() {
+        let config = expand(
+            "[[drone]]\nid = 1\nconnected_node_ids = [2]\npdr = 0.1\n\n\
+             [[drone]]\nid = 2\nconnected_node_ids = [1]\npdr = 0.1\n\n\
+             [[drone]]\nid = 3\npdr = 0.1\n\n[[server]]\nid = 200\nconnected_drone_ids = [2]\n\n\
+             [[link]]\na = 1\nb = 2\n\n[[link]]\na = 3\nb = 200\n",
+        )
+        .unwrap();
+
+        // The link 1-2 is already in the lists and is not repeated
+        assert_eq!(neighbours(&config, 1), [2]);
+        assert_eq!(neighbours(&config, 2), [1]);
+        assert_eq!(neighbours(&config, 200), [2, 3]);
+        assert_eq!(neighbours(&config, 3), [200]);
+        // The one-way neighbour 200 -> 2 is kept for the validator to report
+        assert!(!neighbours(&config, 2).contains(&200));
+    }
+
+    #[test]
+    fn links_to_missing_nodes_are_errors() {
+        let error = expand("[[drone]]\nid = 1\npdr = 0.1\n\n[[link]]\na = 1\nb = 7\n").unwrap_err();
+        assert_eq!(error.message, "Link 1-7 refers to node 7, which does not exist");
+        assert_eq!(error.subjects, [Subject::Neighbour(1, 7)]);
+
+        let error = expand("[[drone]]\nid = 1\npdr = 0.1\n\n[[link]]\na = 1\nb = 1\n").unwrap_err();
+        assert_eq!(error.message, "Link 1-1 connects a node to itself!");
+    }
+
+    #[test]
+    fn expands_id_ranges() {
+        assert_eq!(Ids::Range("1..=4".to_string()).expand(), Ok(vec![1, 2, 3, 4]));
+        assert_eq!(Ids::Range(" 1 .. 4 ".to_string()).expand(), Ok(vec![1, 2, 3]));
+        assert_eq!(Ids::Range("255..=255".to_string()).expand(), Ok(vec![255]));
+        assert_eq!(Ids::List(vec![7, 3]).expand(), Ok(vec![7, 3]));
+
+        for range in ["1-4", "a..=4", "1..=256", "..=4"] {
+            assert!(Ids::Range(range.to_string()).expand().unwrap_err().starts_with("Invalid id range"), "{}", range);
+        }
+    }
+
+    #[test]
+    fn inverted_and_empty_ranges_are_errors() {
+        let error = expand("[[drone_range]]\nids = \"9..=3\"\npdr = 0.1\n").unwrap_err();
+        assert_eq!(error.message, "Id range \"9..=3\" is empty, its first id must come before its end");
+        assert_eq!(error.subjects, [Subject::Range("9..=3".to_string())]);
+
+        assert!(Ids::Range("3..3".to_string()).expand().is_err());
+    }
+
+    #[test]
+    fn seeded_pdrs_are_the_same_on_every_run() {
+        let source = "[[drone_range]]\nids = \"1..=20\"\npdr = \"uniform(0.0, 0.2)\"\nseed = 7\n";
+        let pdrs = |source: &str| expand(source).unwrap().drone.iter().map(|d| d.pdr).collect::<Vec<f32>>();
+
+        let first = pdrs(source);
+        assert_eq!(first.len(), 20);
+        assert!(first.iter().all(|pdr| (0.0..=0.2).contains(pdr)));
+        assert_eq!(pdrs(source), first);
+        assert_ne!(pdrs(&source.replace("seed = 7", "seed = 8")), first);
+    }
+
+    #[test]
+    fn hosts_wrap_around_the_drone_pool() {
+        let config = expand(
+            "[[drone_range]]\nids = \"1..=3\"\npdr = 0.1\n\n\
+             [[client_range]]\nids = \"100..=103\"\ndrones = \"1..=3\"\n\n\
+             [[server_range]]\nids = [200, 201]\ndrones = \"1..=3\"\n",
+        )
+        .unwrap();
+
+        let attached: Vec<Vec<NodeId>> = [100, 101, 102, 103, 200, 201].iter().map(|id| neighbours(&config, *id)).collect();
+        assert_eq!(attached, [vec![1], vec![2], vec![3], vec![1], vec![1, 2], vec![3, 1]]);
+        assert_eq!(neighbours(&config, 1), [100, 103, 200, 201]);
+
+        let error = expand(
+            "[[drone_range]]\nids = \"1..=3\"\npdr = 0.1\n\n[[client_range]]\nids = [100]\ndrones = [1]\nlinks = 2\n",
+        )
+        .unwrap_err();
+        assert_eq!(error.message, "Host range \"[100]\" needs 2 drones, but its pool has 1");
+    }
+
+    #[test]
+    fn pieces_are_added_unless_their_ids_collide() {
+        let piece = |offset: NodeId| {
+            let mut piece = topology::tests::config_with_links(&[(1, 0.1)], &[10], &[], &[(10, 1)]);
+            shift_ids(&mut piece, offset).unwrap();
+            piece
+        };
+        let including = "[[drone]]\nid = 1\npdr = 0.1\n";
+
+        let config = toml::from_str::<ExtendedConfig>(including)
+            .unwrap()
+            .expand(vec![("\"a.toml\" (offset 20)".to_string(), piece(20)), ("\"a.toml\" (offset 40)".to_string(), piece(40))])
+            .unwrap();
+        assert_eq!(topology::node_ids(&config).into_iter().collect::<Vec<_>>(), [1, 21, 30, 41, 50]);
+        assert_eq!(neighbours(&config, 30), [21]);
+
+        let error = toml::from_str::<ExtendedConfig>(including)
+            .unwrap()
+            .expand(vec![("\"a.toml\"".to_string(), piece(0))])
+            .unwrap_err();
+        assert_eq!(error.message, "Node 1 of \"a.toml\" collides with a node of the including file, change the offsets");
+        assert_eq!(error.subjects, [Subject::Node(1)]);
+
+        let error = toml::from_str::<ExtendedConfig>("")
+            .unwrap()
+            .expand(vec![("\"a.toml\"".to_string(), piece(5)), ("\"b.toml\"".to_string(), piece(5))])
+            .unwrap_err();
+        assert_eq!(error.message, "Node 6 of \"b.toml\" collides with a node of \"a.toml\", change the offsets");
+    }
+
+    #[test]
+    fn shifted_ids_must_fit() {
+        let mut piece = topology::tests::config_with_links(&[(1, 0.1), (250, 0.1)], &[], &[], &[(1, 250)]);
+        assert_eq!(
+            shift_ids(&mut piece, 10),
+            Err("Node 250 with offset 10 exceeds the maximum id 255".to_string())
+        );
+    }
+
+    #[test]
+    fn detects_the_extended_format() {
+        assert!(uses_extended_format("[[link]]\na = 1\nb = 2\n"));
+        assert!(!uses_extended_format("[[drone]]\nid = 1\nconnected_node_ids = []\npdr = 0.1\n"));
+        assert!(!uses_extended_format("not toml ["));
+    }
+}
@@ -1,9 +1,52 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
 use std::result::Result;
 use wg_2024::config::{Config};
 use wg_2024::network::NodeId;
 
-pub fn validate_config(config: &Config) -> Result<(), String> {
+use crate::utils::topology::{self, Mutation};
+
+/// A broken rule of the protocol, with the parts of the topology it refers to,
+/// so that the error can point at them in the topology file
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    pub message: String,
+    pub subjects: Vec<Subject>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Subject {
+    /// The definition of a node
+    Node(NodeId),
+    /// The presence of the second node among the neighbours of the first one
+    Neighbour(NodeId, NodeId),
+    /// The PDR of a drone
+    Pdr(NodeId),
+    /// A range of nodes, by its ids as written in the file
+    Range(String),
+    /// The inclusion of a topology file, by its path as written in the including file
+    Include(String),
+}
+
+impl ValidationError {
+    pub fn new(message: String, subjects: Vec<Subject>) -> Self {
+        Self { message, subjects }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl From<ValidationError> for String {
+    fn from(error: ValidationError) -> Self {
+        error.message
+    }
+}
+
+pub fn validate_config(config: &Config) -> Result<(), ValidationError> {
     check_uniqueness_of_ids(config)?;
     check_drones(config)?;
     check_servers(config)?;
@@ -13,25 +56,61 @@ pub fn validate_config(config: &Config) -> Result<(), String> {
     Ok(())
 }
 
+/// Checks that the topology obtained by applying the mutation to `state` still respects the
+/// protocol: the same rules of `validate_config`, plus every client must still reach every server.
+/// Besides the subjects of the broken rule, the error refers to the nodes touched by the mutation.
+pub fn validate_mutation(state: &Config, mutation: &Mutation) -> Result<(), ValidationError> {
+    let touched = match *mutation {
+        Mutation::Crash(id) => vec![Subject::Node(id)],
+        Mutation::SetPdr(id, _) => vec![Subject::Pdr(id)],
+        Mutation::AddLink(a, b) => vec![Subject::Node(a), Subject::Node(b)],
+        Mutation::RemoveLink(a, b) => vec![Subject::Neighbour(a, b), Subject::Neighbour(b, a)],
+    };
+
+    let mut projected = state.clone();
+    topology::apply_mutation(&mut projected, mutation)
+        .map_err(|error_message| ValidationError::new(error_message, Vec::new()))
+        .and_then(|_| validate_config(&projected))
+        .and_then(|_| check_reachability(&projected))
+        .map_err(|error| {
+            let mut subjects = touched;
+            for subject in error.subjects {
+                if !subjects.contains(&subject) {
+                    subjects.push(subject);
+                }
+            }
+            ValidationError::new(format!("Refusing to {}: {}", mutation, error.message), subjects)
+        })
+}
+
 // Each id present in the file must be unique
-fn check_uniqueness_of_ids(config: &Config) -> Result<(), String> {
+fn check_uniqueness_of_ids(config: &Config) -> Result<(), ValidationError> {
     let mut id_set: HashSet<NodeId> = HashSet::new();
 
     for drone in &config.drone {
         if !id_set.insert(drone.id) {
-            return Err(format!("There are multiple items with the same Id in the input file! Id = \"{}\"", drone.id))
+            return Err(ValidationError::new(
+                format!("There are multiple items with the same Id in the input file! Id = \"{}\"", drone.id),
+                vec![Subject::Node(drone.id)],
+            ))
         }
     }
 
     for server in &config.server {
         if !id_set.insert(server.id) {
-            return Err(format!("There are multiple items with the same Id in the input file! Id = \"{}\"", server.id))
+            return Err(ValidationError::new(
+                format!("There are multiple items with the same Id in the input file! Id = \"{}\"", server.id),
+                vec![Subject::Node(server.id)],
+            ))
         }
     }
 
     for client in &config.client {
         if !id_set.insert(client.id) {
-            return Err(format!("There are multiple items with the same Id in the input file! Id = \"{}\"", client.id))
+            return Err(ValidationError::new(
+                format!("There are multiple items with the same Id in the input file! Id = \"{}\"", client.id),
+                vec![Subject::Node(client.id)],
+            ))
         }
     }
 
@@ -40,68 +119,95 @@ fn check_uniqueness_of_ids(config: &Config) -> Result<(), String> {
 
 
 // This function checks that all the parameters for a drone respect the protocol
-fn check_drones(config: &Config) -> Result<(), String> {
+fn check_drones(config: &Config) -> Result<(), ValidationError> {
     for drone in &config.drone {
         if let Err(id) = check_ids(&drone.connected_node_ids, drone.id) {
             if drone.id == id {
-                return Err(format!("Drone {} has itself as a neighbour!", drone.id))
+                return Err(ValidationError::new(
+                    format!("Drone {} has itself as a neighbour!", drone.id),
+                    vec![Subject::Neighbour(drone.id, id)],
+                ))
             } else {
-                return Err(format!("Drone {} has some repetitions between its neighbours! Repeated id = {}", drone.id, id))
+                return Err(ValidationError::new(
+                    format!("Drone {} has some repetitions between its neighbours! Repeated id = {}", drone.id, id),
+                    vec![Subject::Neighbour(drone.id, id)],
+                ))
             }
         }
 
         if let Err(()) = check_pdr_value(drone.pdr) {
-            return Err(format!("The packet drop rate of drone \"{}\" is not in the right range! Correct range: [0 - 1]", drone.id))
+            return Err(ValidationError::new(
+                format!("The packet drop rate of drone \"{}\" is not in the right range! Correct range: [0 - 1]", drone.id),
+                vec![Subject::Pdr(drone.id)],
+            ))
         }
     }
     Ok(())
 }
 
 // This function checks that all the parameters for a server respect the protocol
-fn check_servers(config: &Config) -> Result<(), String> {
+fn check_servers(config: &Config) -> Result<(), ValidationError> {
     for server in &config.server {
         if let Err(id) = check_ids(&server.connected_drone_ids, server.id) {
             if server.id == id {
-                return Err(format!("Server {} has itself as a neighbour!", server.id))
+                return Err(ValidationError::new(
+                    format!("Server {} has itself as a neighbour!", server.id),
+                    vec![Subject::Neighbour(server.id, id)],
+                ))
             } else {
-                return Err(format!("Server {} has some repetitions between its neighbours! Repeated id = {}", server.id, id))
+                return Err(ValidationError::new(
+                    format!("Server {} has some repetitions between its neighbours! Repeated id = {}", server.id, id),
+                    vec![Subject::Neighbour(server.id, id)],
+                ))
             }
         }
 
         if server.connected_drone_ids.len() < 2 {
-            return Err(format!("Server {} has less than 2 connections", server.id))
+            return Err(ValidationError::new(
+                format!("Server {} has less than 2 connections", server.id),
+                vec![Subject::Node(server.id)],
+            ))
         }
     }
     Ok(())
 }
 
 // This function checks that all the parameters for a client respect the protocol
-fn check_clients(config: &Config) -> Result<(), String> {
+fn check_clients(config: &Config) -> Result<(), ValidationError> {
     for client in &config.client {
         if let Err(id) = check_ids(&client.connected_drone_ids, client.id) {
             if client.id == id {
-                return Err(format!("Client {} has itself as a neighbour!", client.id))
+                return Err(ValidationError::new(
+                    format!("Client {} has itself as a neighbour!", client.id),
+                    vec![Subject::Neighbour(client.id, id)],
+                ))
             } else {
-                return Err(format!("Client {} has some repetitions between its neighbours! Repeated id = {}", client.id, id))
+                return Err(ValidationError::new(
+                    format!("Client {} has some repetitions between its neighbours! Repeated id = {}", client.id, id),
+                    vec![Subject::Neighbour(client.id, id)],
+                ))
             }
         }
 
         if client.connected_drone_ids.is_empty() || client.connected_drone_ids.len() > 2 {
-            return Err(format!("Client {} has less than 1 connection or more than 2", client.id))
+            return Err(ValidationError::new(
+                format!("Client {} has less than 1 connection or more than 2", client.id),
+                vec![Subject::Node(client.id)],
+            ))
         }
     }
     Ok(())
 }
 
 // Check that all connections are bidirectional
-fn check_connections(config: &Config) -> Result<(), String> {
+fn check_connections(config: &Config) -> Result<(), ValidationError> {
     let mut nodes_connections: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
 
     for drone in &config.drone {
         if let std::collections::hash_map::Entry::Vacant(e) = nodes_connections.entry(drone.id) {
             e.insert(drone.connected_node_ids.clone());
         } else {
-            return Err("Duplicated id".to_string())
+            return Err(ValidationError::new("Duplicated id".to_string(), vec![Subject::Node(drone.id)]))
         }
 
     }
@@ -110,7 +216,7 @@ fn check_connections(config: &Config) -> Result<(), String> {
         if let std::collections::hash_map::Entry::Vacant(e) = nodes_connections.entry(server.id) {
             e.insert(server.connected_drone_ids.clone());
         } else {
-            return Err("Duplicated id".to_string())
+            return Err(ValidationError::new("Duplicated id".to_string(), vec![Subject::Node(server.id)]))
         }
     }
 
@@ -118,7 +224,7 @@ fn check_connections(config: &Config) -> Result<(), String> {
         if let std::collections::hash_map::Entry::Vacant(e) = nodes_connections.entry(client.id) {
             e.insert(client.connected_drone_ids.clone());
         } else {
-            return Err("Duplicated id".to_string())
+            return Err(ValidationError::new("Duplicated id".to_string(), vec![Subject::Node(client.id)]))
         }
 
     }
@@ -126,9 +232,50 @@ fn check_connections(config: &Config) -> Result<(), String> {
     for key in nodes_connections.keys() {
         for id in nodes_connections.get(key).unwrap().iter() {
             if !nodes_connections.contains_key(id) {
-                return Err(format!("Connection to a node that does not exists! From {} to {}", key, id));
+                return Err(ValidationError::new(
+                    format!("Connection to a node that does not exists! From {} to {}", key, id),
+                    vec![Subject::Neighbour(*key, *id)],
+                ));
             } else if !nodes_connections.get(id).unwrap().contains(key) {
-                return Err(format!("Trying to establish a mono-directional connection! From {} to {}", key, id));
+                return Err(ValidationError::new(
+                    format!("Trying to establish a mono-directional connection! From {} to {}", key, id),
+                    vec![Subject::Neighbour(*key, *id), Subject::Node(*id)],
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Check that every client can reach every server.
+// Clients and servers do not forward packets, so the paths can only pass through drones.
+pub fn check_reachability(config: &Config) -> Result<(), ValidationError> {
+    let drones: HashMap<NodeId, &Vec<NodeId>> = config
+        .drone
+        .iter()
+        .map(|drone| (drone.id, &drone.connected_node_ids))
+        .collect();
+
+    for client in &config.client {
+        let mut visited: HashSet<NodeId> = HashSet::new();
+        let mut queue: VecDeque<NodeId> = client.connected_drone_ids.iter().copied().collect();
+
+        while let Some(id) = queue.pop_front() {
+            if !visited.insert(id) {
+                continue;
+            }
+            if let Some(neighbours) = drones.get(&id) {
+                queue.extend(neighbours.iter().filter(|n| !visited.contains(n)));
+            }
+        }
+
+        for server in &config.server {
+            if !visited.contains(&server.id) {
+                return Err(ValidationError::new(
+                    format!("Client {} cannot reach server {}", client.id, server.id),
+                    vec![Subject::Node(client.id), Subject::Node(server.id)],
+                ));
             }
         }
     }
@@ -160,4 +307,120 @@ fn check_pdr_value(pdr: f32) -> Result<(), ()> {
     } else {
         Ok(())
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::topology::tests::config_with_links;
+    use wg_2024::config::{Client, Drone, Server};
+
+    // Client 100 (on drones 1 and 2) and client 101 (on drone 1 only) reach server 200 (on drones 3 and 4)
+    // only through drone 5
+    fn bridged() -> Config {
+        config_with_links(
+            &[(1, 0.1), (2, 0.1), (3, 0.1), (4, 0.1), (5, 0.1)],
+            &[100, 101],
+            &[200],
+            &[(100, 1), (100, 2), (101, 1), (1, 5), (2, 5), (5, 3), (5, 4), (3, 200), (4, 200)],
+        )
+    }
+
+    #[test]
+    fn crash_splitting_clients_from_servers_is_refused() {
+        let error = validate_mutation(&bridged(), &Mutation::Crash(5)).unwrap_err();
+        assert_eq!(error.message, "Refusing to crash drone 5: Client 100 cannot reach server 200");
+    }
+
+    #[test]
+    fn removing_the_last_drone_of_a_client_is_refused() {
+        let error = validate_mutation(&bridged(), &Mutation::RemoveLink(101, 1)).unwrap_err();
+        assert_eq!(error.message, "Refusing to remove link 101-1: Client 101 has less than 1 connection or more than 2");
+    }
+
+    #[test]
+    fn subjects_list_every_node_involved() {
+        let error = validate_mutation(&bridged(), &Mutation::Crash(5)).unwrap_err();
+        assert_eq!(error.subjects, [Subject::Node(5), Subject::Node(100), Subject::Node(200)]);
+
+        let error = validate_mutation(&bridged(), &Mutation::RemoveLink(101, 1)).unwrap_err();
+        assert_eq!(
+            error.subjects,
+            [Subject::Neighbour(101, 1), Subject::Neighbour(1, 101), Subject::Node(101)]
+        );
+
+        let error = validate_mutation(&bridged(), &Mutation::SetPdr(3, 1.5)).unwrap_err();
+        assert_eq!(error.subjects, [Subject::Pdr(3)]);
+    }
+
+    #[test]
+    fn valid_changes_are_accepted() {
+        assert_eq!(validate_mutation(&bridged(), &Mutation::SetPdr(3, 0.7)), Ok(()));
+        assert_eq!(validate_mutation(&bridged(), &Mutation::RemoveLink(100, 2)), Ok(()));
+        assert_eq!(validate_mutation(&bridged(), &Mutation::AddLink(2, 3)), Ok(()));
+    }
+
+    #[test]
+    fn changes_to_missing_nodes_are_refused() {
+        let error = validate_mutation(&bridged(), &Mutation::Crash(9)).unwrap_err();
+        assert!(error.message.starts_with("Refusing to crash drone 9: "), "{}", error);
+        assert_eq!(error.subjects, [Subject::Node(9)]);
+    }
+
+    #[test]
+    fn reachability_only_goes_through_drones() {
+        assert_eq!(check_reachability(&bridged()), Ok(()));
+
+        // Server 201 is only linked to client 100, which does not forward packets for client 101
+        let mut config = bridged();
+        config.server.push(Server { id: 201, connected_drone_ids: Vec::new() });
+        config.client[0].connected_drone_ids.push(201);
+        let error = check_reachability(&config).unwrap_err();
+        assert_eq!(error.message, "Client 101 cannot reach server 201");
+    }
+
+    fn message(config: &Config) -> String {
+        validate_config(config).unwrap_err().into()
+    }
+
+    #[test]
+    fn validate_config_messages_are_unchanged() {
+        let mut config = bridged();
+        config.client.push(Client { id: 3, connected_drone_ids: vec![1] });
+        assert_eq!(message(&config), "There are multiple items with the same Id in the input file! Id = \"3\"");
+
+        let mut config = bridged();
+        config.drone[0].connected_node_ids.push(1);
+        assert_eq!(message(&config), "Drone 1 has itself as a neighbour!");
+
+        let mut config = bridged();
+        config.drone[0].connected_node_ids.push(5);
+        assert_eq!(message(&config), "Drone 1 has some repetitions between its neighbours! Repeated id = 5");
+
+        let mut config = bridged();
+        config.drone[0].pdr = -0.1;
+        assert_eq!(
+            message(&config),
+            "The packet drop rate of drone \"1\" is not in the right range! Correct range: [0 - 1]"
+        );
+
+        let mut config = bridged();
+        config.server[0].connected_drone_ids.pop();
+        assert_eq!(message(&config), "Server 200 has less than 2 connections");
+
+        let mut config = bridged();
+        config.client[0].connected_drone_ids.push(3);
+        assert_eq!(message(&config), "Client 100 has less than 1 connection or more than 2");
+
+        let mut config = bridged();
+        config.drone.push(Drone { id: 6, connected_node_ids: vec![7], pdr: 0. });
+        assert_eq!(message(&config), "Connection to a node that does not exists! From 6 to 7");
+
+        let mut config = bridged();
+        config.drone.push(Drone { id: 6, connected_node_ids: vec![3], pdr: 0. });
+        assert_eq!(message(&config), "Trying to establish a mono-directional connection! From 6 to 3");
+        assert_eq!(
+            validate_config(&config).unwrap_err().subjects,
+            [Subject::Neighbour(6, 3), Subject::Node(3)]
+        );
+    }
+}
@@ -0,0 +1,334 @@
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use wg_2024::config::Config;
+use wg_2024::network::NodeId;
+
+use crate::utils::input_validator::Subject;
+use crate::utils::{config_loader, topology};
+
+/// How a lint is reported: `Deny` makes the validation fail
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Allow,
+    Warn,
+    Deny,
+}
+
+/// Topologies that respect the protocol, but are fragile or useless in practice
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LintRule {
+    /// A drone whose crash disconnects a client from a server
+    ArticulationDrone,
+    /// A link between drones whose removal disconnects a client from a server
+    BridgeLink,
+    /// A client connected to a single drone
+    SingleLinkClient,
+    /// A server connected only to drones with a high PDR
+    FlakyServer,
+    /// A drone that drops every fragment
+    FullDropDrone,
+    /// A client whose shortest path to a server is too long
+    LongPath,
+}
+
+impl LintRule {
+    pub const ALL: [LintRule; 6] = [
+        LintRule::ArticulationDrone,
+        LintRule::BridgeLink,
+        LintRule::SingleLinkClient,
+        LintRule::FlakyServer,
+        LintRule::FullDropDrone,
+        LintRule::LongPath,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LintRule::ArticulationDrone => "articulation_drone",
+            LintRule::BridgeLink => "bridge_link",
+            LintRule::SingleLinkClient => "single_link_client",
+            LintRule::FlakyServer => "flaky_server",
+            LintRule::FullDropDrone => "full_drop_drone",
+            LintRule::LongPath => "long_path",
+        }
+    }
+}
+
+/// Severity of each rule and thresholds of the lints, read from the `[lints]` table of the topology file:
+///
+/// ```toml
+/// [lints]
+/// bridge_link = "allow"
+/// long_path = "deny"
+/// max_path_length = 6  # hops, for long_path
+/// high_pdr = 0.3       # for flaky_server
+/// ```
+#[derive(Debug, Clone)]
+pub struct LintSettings {
+    levels: HashMap<LintRule, Severity>,
+    pub max_path_length: usize,
+    pub high_pdr: f32,
+}
+
+impl Default for LintSettings {
+    fn default() -> Self {
+        Self {
+            levels: HashMap::new(),
+            max_path_length: 8,
+            high_pdr: 0.5,
+        }
+    }
+}
+
+impl LintSettings {
+    /// Reads the `[lints]` tables of a topology file and of the files it includes,
+    /// the defaults are used for what is missing
+    pub fn load(path: &str) -> Result<Self, String> {
+        let mut settings = LintSettings::default();
+        for (table, _) in config_loader::read_settings(path, "lints")? {
+            settings.apply(&table)?;
+        }
+        Ok(settings)
+    }
+
+    fn apply(&mut self, table: &toml::Table) -> Result<(), String> {
+        for (key, value) in table {
+            match key.as_str() {
+                "max_path_length" => {
+                    self.max_path_length = value
+                        .as_integer()
+                        .and_then(|v| usize::try_from(v).ok())
+                        .ok_or(format!("Invalid lint setting {} = {}", key, value))?;
+                }
+                "high_pdr" => {
+                    self.high_pdr = value
+                        .as_float()
+                        .or(value.as_integer().map(|v| v as f64))
+                        .ok_or(format!("Invalid lint setting {} = {}", key, value))? as f32;
+                }
+                _ => {
+                    let rule = LintRule::ALL.into_iter().find(|r| r.name() == key).ok_or(format!(
+                        "Unknown lint \"{}\"! Valid lints: {}",
+                        key,
+                        LintRule::ALL.map(|r| r.name()).join(", ")
+                    ))?;
+                    let severity = value
+                        .clone()
+                        .try_into::<Severity>()
+                        .map_err(|_| format!("Invalid level of lint {}: {}, expected \"allow\", \"warn\" or \"deny\"", key, value))?;
+                    self.levels.insert(rule, severity);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn level(&self, rule: LintRule) -> Severity {
+        self.levels.get(&rule).copied().unwrap_or(Severity::Warn)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Lint {
+    pub rule: LintRule,
+    pub severity: Severity,
+    pub message: String,
+    pub subjects: Vec<Subject>,
+}
+
+/// Checks a valid topology against the lint rules that are not allowed
+pub fn lint(config: &Config, settings: &LintSettings) -> Vec<Lint> {
+    let mut lints = Vec::new();
+    let distances = topology::client_server_distances(config);
+    let mut report = |rule: LintRule, message: String, subjects: Vec<Subject>| {
+        let severity = settings.level(rule);
+        if severity != Severity::Allow {
+            lints.push(Lint { rule, severity, message, subjects });
+        }
+    };
+
+    // The connectivity lints simulate each removal, skip them when they are not needed
+    let articulation_drones = settings.level(LintRule::ArticulationDrone) != Severity::Allow;
+    let bridge_links = settings.level(LintRule::BridgeLink) != Severity::Allow;
+
+    for drone in config.drone.iter().filter(|_| articulation_drones) {
+        let mut projected = config.clone();
+        topology::remove_drone(&mut projected, drone.id).unwrap();
+        let lost = lost_pairs(&distances, &projected);
+        if !lost.is_empty() {
+            report(
+                LintRule::ArticulationDrone,
+                format!("Crashing drone {} disconnects {}", drone.id, describe_pairs(&lost)),
+                vec![Subject::Node(drone.id)],
+            );
+        }
+    }
+
+    for (a, b) in topology::links(config).into_iter().filter(|_| bridge_links) {
+        if !topology::is_drone(config, a) || !topology::is_drone(config, b) {
+            continue;
+        }
+        let mut projected = config.clone();
+        topology::remove_link(&mut projected, a, b).unwrap();
+        let lost = lost_pairs(&distances, &projected);
+        if !lost.is_empty() {
+            report(
+                LintRule::BridgeLink,
+                format!("Removing link {}-{} disconnects {}", a, b, describe_pairs(&lost)),
+                vec![Subject::Neighbour(a, b), Subject::Neighbour(b, a)],
+            );
+        }
+    }
+
+    for client in &config.client {
+        if let [drone] = client.connected_drone_ids[..] {
+            report(
+                LintRule::SingleLinkClient,
+                format!("Client {} is connected to drone {} only, a crash isolates it", client.id, drone),
+                vec![Subject::Node(client.id)],
+            );
+        }
+    }
+
+    for server in &config.server {
+        let pdrs: Vec<f32> = config
+            .drone
+            .iter()
+            .filter(|d| server.connected_drone_ids.contains(&d.id))
+            .map(|d| d.pdr)
+            .collect();
+        if !pdrs.is_empty() && pdrs.iter().all(|pdr| *pdr >= settings.high_pdr) {
+            report(
+                LintRule::FlakyServer,
+                format!("Server {} is only connected to drones with a PDR of at least {}", server.id, settings.high_pdr),
+                vec![Subject::Node(server.id)],
+            );
+        }
+    }
+
+    for drone in &config.drone {
+        if drone.pdr == 1. {
+            report(
+                LintRule::FullDropDrone,
+                format!("Drone {} has a PDR of 1 and drops every fragment", drone.id),
+                vec![Subject::Pdr(drone.id)],
+            );
+        }
+    }
+
+    for ((client, server), distance) in &distances {
+        if let Some(hops) = distance.filter(|hops| *hops > settings.max_path_length) {
+            report(
+                LintRule::LongPath,
+                format!(
+                    "The shortest path from client {} to server {} is {} hops long (more than {})",
+                    client, server, hops, settings.max_path_length
+                ),
+                vec![Subject::Node(*client), Subject::Node(*server)],
+            );
+        }
+    }
+
+    lints
+}
+
+// Client-server pairs that were connected and are not anymore in the projected topology
+fn lost_pairs(distances: &BTreeMap<(NodeId, NodeId), Option<usize>>, projected: &Config) -> Vec<(NodeId, NodeId)> {
+    let projected_distances = topology::client_server_distances(projected);
+    distances
+        .iter()
+        .filter(|(pair, distance)| distance.is_some() && projected_distances.get(pair).copied().flatten().is_none())
+        .map(|(pair, _)| *pair)
+        .collect()
+}
+
+fn describe_pairs(pairs: &[(NodeId, NodeId)]) -> String {
+    const SHOWN: usize = 3;
+    let mut description: Vec<String> = pairs
+        .iter()
+        .take(SHOWN)
+        .map(|(client, server)| format!("client {} from server {}", client, server))
+        .collect();
+    if pairs.len() > SHOWN {
+        description.push(format!("{} more pairs", pairs.len() - SHOWN));
+    }
+    description.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::topology::tests::config_with_links;
+
+    fn rules(lints: &[Lint]) -> Vec<(LintRule, Vec<Subject>)> {
+        lints.iter().map(|lint| (lint.rule, lint.subjects.clone())).collect()
+    }
+
+    #[test]
+    fn finds_articulation_drones_and_single_link_clients() {
+        // 100 - 1 - {2, 3} - 4 - 200, with drone 5 as a second link of the server
+        let config = config_with_links(
+            &[(1, 0.1), (2, 0.1), (3, 0.1), (4, 0.1), (5, 0.1)],
+            &[100],
+            &[200],
+            &[(100, 1), (1, 2), (1, 3), (2, 4), (3, 4), (4, 5), (4, 200), (5, 200)],
+        );
+
+        assert_eq!(
+            rules(&lint(&config, &LintSettings::default())),
+            [
+                (LintRule::ArticulationDrone, vec![Subject::Node(1)]),
+                (LintRule::ArticulationDrone, vec![Subject::Node(4)]),
+                (LintRule::SingleLinkClient, vec![Subject::Node(100)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn applies_the_levels_and_thresholds() {
+        // 100 - {1, 2} - 3 - {4} - 200, where 2-3 is the only way across
+        let config = config_with_links(
+            &[(1, 1.), (2, 0.1), (3, 0.6), (4, 0.6)],
+            &[100],
+            &[200],
+            &[(100, 1), (100, 2), (1, 2), (2, 3), (3, 4), (3, 200), (4, 200)],
+        );
+        let mut settings = LintSettings { max_path_length: 2, ..LintSettings::default() };
+        settings.levels.insert(LintRule::ArticulationDrone, Severity::Allow);
+        settings.levels.insert(LintRule::LongPath, Severity::Deny);
+
+        let lints = lint(&config, &settings);
+        assert_eq!(
+            rules(&lints),
+            [
+                (LintRule::BridgeLink, vec![Subject::Neighbour(2, 3), Subject::Neighbour(3, 2)]),
+                (LintRule::FlakyServer, vec![Subject::Node(200)]),
+                (LintRule::FullDropDrone, vec![Subject::Pdr(1)]),
+                (LintRule::LongPath, vec![Subject::Node(100), Subject::Node(200)]),
+            ]
+        );
+        assert_eq!(lints[3].severity, Severity::Deny);
+        assert_eq!(lints[3].message, "The shortest path from client 100 to server 200 is 3 hops long (more than 2)");
+
+        settings.high_pdr = 0.7;
+        assert!(lint(&config, &settings).iter().all(|lint| lint.rule != LintRule::FlakyServer));
+    }
+
+    #[test]
+    fn loads_the_lints_of_included_files_first() {
+        let dir = std::env::temp_dir().join(format!("lints-includes-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("main.toml"),
+            "[[include]]\npath = \"region.toml\"\n\n[lints]\nlong_path = \"deny\"\nmax_path_length = 6\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("region.toml"), "[lints]\nlong_path = \"allow\"\nhigh_pdr = 0.3\nmax_path_length = 4\n").unwrap();
+
+        let settings = LintSettings::load(dir.join("main.toml").to_str().unwrap()).unwrap();
+        assert_eq!(settings.level(LintRule::LongPath), Severity::Deny);
+        assert_eq!(settings.max_path_length, 6);
+        assert_eq!(settings.high_pdr, 0.3);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1 +1,9 @@
-pub mod input_validator;
\ No newline at end of file
+pub mod buffers;
+pub mod config_fixer;
+pub mod config_loader;
+pub mod config_writer;
+pub mod extended_config;
+pub mod input_validator;
+pub mod lints;
+pub mod source_map;
+pub mod topology;
\ No newline at end of file
@@ -0,0 +1,316 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::ops::Range;
+use toml::Spanned;
+use wg_2024::network::NodeId;
+
+use crate::utils::extended_config::{self, Ids};
+use crate::utils::input_validator::{Subject, ValidationError};
+
+/// Where each node, neighbour and PDR of a topology is written in its files,
+/// to report the validation errors like a compiler does:
+///
+/// ```text
+/// Trying to establish a mono-directional connection! From 1 to 2
+///  --> input.toml:3:23
+///   |
+/// 3 | connected_node_ids = [2, 101]
+///   |                       ^ neighbour 2 of node 1
+///  --> input.toml:7:1
+///   |
+/// 7 | [[drone]]
+///   | ^^^^^^^^^ node 2 is defined here
+/// ```
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+    nodes: HashMap<NodeId, Vec<Location>>,
+    neighbours: HashMap<(NodeId, NodeId), Vec<Location>>,
+    pdrs: HashMap<NodeId, Vec<Location>>,
+    ranges: HashMap<String, Vec<Location>>,
+    includes: HashMap<String, Vec<Location>>,
+}
+
+#[derive(Debug)]
+struct SourceFile {
+    path: String,
+    source: String,
+}
+
+#[derive(Debug, Clone)]
+struct Location {
+    file: usize,
+    span: Range<usize>,
+}
+
+// The parts of a topology file that can be pointed at
+#[derive(Deserialize)]
+struct SpannedFile {
+    #[serde(default)]
+    drone: Vec<Spanned<SpannedNode>>,
+    #[serde(default)]
+    client: Vec<Spanned<SpannedNode>>,
+    #[serde(default)]
+    server: Vec<Spanned<SpannedNode>>,
+    #[serde(default)]
+    link: Vec<Spanned<SpannedLink>>,
+    #[serde(default)]
+    drone_range: Vec<Spanned<SpannedRange>>,
+    #[serde(default)]
+    client_range: Vec<Spanned<SpannedRange>>,
+    #[serde(default)]
+    server_range: Vec<Spanned<SpannedRange>>,
+    #[serde(default)]
+    include: Vec<Spanned<SpannedInclude>>,
+}
+
+#[derive(Deserialize)]
+struct SpannedNode {
+    id: NodeId,
+    #[serde(default, alias = "connected_drone_ids")]
+    connected_node_ids: Vec<Spanned<NodeId>>,
+    pdr: Option<Spanned<f32>>,
+}
+
+#[derive(Deserialize)]
+struct SpannedLink {
+    a: NodeId,
+    b: NodeId,
+}
+
+#[derive(Deserialize)]
+struct SpannedInclude {
+    path: String,
+}
+
+#[derive(Deserialize)]
+struct SpannedRange {
+    ids: Ids,
+    drones: Option<Ids>,
+}
+
+impl SourceMap {
+    /// Records the locations of a topology file. Files that cannot be parsed are kept
+    /// without locations, their errors are reported by the loader.
+    pub fn add_file(&mut self, path: &str, source: &str) {
+        let file = self.files.len();
+        self.files.push(SourceFile { path: path.to_string(), source: source.to_string() });
+        let Ok(parsed) = toml::from_str::<SpannedFile>(source) else {
+            return;
+        };
+        let location = |span: Range<usize>| Location { file, span };
+
+        for node in parsed.drone.iter().chain(&parsed.client).chain(&parsed.server) {
+            let id = node.get_ref().id;
+            self.nodes.entry(id).or_default().push(location(node.span()));
+            for neighbour in &node.get_ref().connected_node_ids {
+                self.neighbours.entry((id, *neighbour.get_ref())).or_default().push(location(neighbour.span()));
+            }
+            if let Some(pdr) = &node.get_ref().pdr {
+                self.pdrs.entry(id).or_default().push(location(pdr.span()));
+            }
+        }
+
+        for link in &parsed.link {
+            let SpannedLink { a, b } = *link.get_ref();
+            self.neighbours.entry((a, b)).or_default().push(location(link.span()));
+            self.neighbours.entry((b, a)).or_default().push(location(link.span()));
+        }
+
+        // Ranges are pointed at as a whole, for their nodes and the links to their pool
+        for range in parsed.drone_range.iter().chain(&parsed.client_range).chain(&parsed.server_range) {
+            self.ranges.entry(range.get_ref().ids.to_string()).or_default().push(location(range.span()));
+            let ids = range.get_ref().ids.expand().unwrap_or_default();
+            let pool = range.get_ref().drones.as_ref().and_then(|d| d.expand().ok()).unwrap_or_default();
+            for id in ids {
+                self.nodes.entry(id).or_default().push(location(range.span()));
+                for drone in &pool {
+                    self.neighbours.entry((id, *drone)).or_default().push(location(range.span()));
+                    self.neighbours.entry((*drone, id)).or_default().push(location(range.span()));
+                }
+            }
+        }
+
+        for include in &parsed.include {
+            self.includes.entry(include.get_ref().path.clone()).or_default().push(location(include.span()));
+        }
+    }
+
+    /// Adds the locations of an included piece, whose ids are shifted by `offset`.
+    /// Fails like `extended_config::shift_ids` when a shifted id does not fit in a `NodeId`.
+    pub fn include(&mut self, piece: SourceMap, offset: NodeId) -> Result<(), String> {
+        let first_file = self.files.len();
+        self.files.extend(piece.files);
+        let moved = |locations: Vec<Location>| {
+            locations.into_iter().map(move |l| Location { file: first_file + l.file, span: l.span })
+        };
+        let shift = |id: NodeId| extended_config::shift_id(id, offset);
+
+        for (id, locations) in piece.nodes {
+            self.nodes.entry(shift(id)?).or_default().extend(moved(locations));
+        }
+        for ((a, b), locations) in piece.neighbours {
+            self.neighbours.entry((shift(a)?, shift(b)?)).or_default().extend(moved(locations));
+        }
+        for (id, locations) in piece.pdrs {
+            self.pdrs.entry(shift(id)?).or_default().extend(moved(locations));
+        }
+        for (ids, locations) in piece.ranges {
+            self.ranges.entry(ids).or_default().extend(moved(locations));
+        }
+        for (path, locations) in piece.includes {
+            self.includes.entry(path).or_default().extend(moved(locations));
+        }
+        Ok(())
+    }
+
+    /// Writes the error message followed by a snippet for each location of its subjects
+    pub fn render(&self, error: &ValidationError) -> String {
+        self.render_subjects(&error.message, &error.subjects)
+    }
+
+    /// Writes the headline followed by a snippet for each location of the subjects
+    pub fn render_subjects(&self, headline: &str, subjects: &[Subject]) -> String {
+        let mut rendered = headline.to_string();
+
+        for subject in subjects {
+            let (locations, label) = match subject {
+                Subject::Node(id) => (self.nodes.get(id), format!("node {} is defined here", id)),
+                Subject::Neighbour(a, b) => (self.neighbours.get(&(*a, *b)), format!("neighbour {} of node {}", b, a)),
+                Subject::Pdr(id) => (
+                    self.pdrs.get(id).or(self.nodes.get(id)),
+                    format!("PDR of drone {}", id),
+                ),
+                Subject::Range(ids) => (self.ranges.get(ids), format!("range \"{}\" is defined here", ids)),
+                Subject::Include(path) => (self.includes.get(path), format!("\"{}\" is included here", path)),
+            };
+            for location in locations.into_iter().flatten() {
+                rendered.push('\n');
+                rendered.push_str(&self.snippet(location, &label));
+            }
+        }
+        rendered
+    }
+
+    fn snippet(&self, location: &Location, label: &str) -> String {
+        let file = &self.files[location.file];
+        let start = location.span.start.min(file.source.len());
+        let line_start = file.source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = file.source[start..].find('\n').map_or(file.source.len(), |i| start + i);
+        let line_number = file.source[..start].matches('\n').count() + 1;
+
+        // Multi-line spans, like whole tables, are underlined up to the end of their first line
+        let column = file.source[line_start..start].chars().count();
+        let width = file.source[start..location.span.end.clamp(start, line_end)].chars().count().max(1);
+        let margin = " ".repeat(line_number.to_string().len());
+
+        let mut snippet = String::new();
+        writeln!(snippet, "{}--> {}:{}:{}", margin, file.path, line_number, column + 1).unwrap();
+        writeln!(snippet, "{} |", margin).unwrap();
+        writeln!(snippet, "{} | {}", line_number, &file.source[line_start..line_end]).unwrap();
+        write!(snippet, "{} | {}{} {}", margin, " ".repeat(column), "^".repeat(width), label).unwrap();
+        snippet
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAIN: &str = "[[drone]]\nid = 1\nconnected_node_ids = [2, 101]\npdr = 0.05\n\n[[drone]]\nid = 2\nconnected_node_ids = []\npdr = 0.1\n";
+    const PIECE: &str = "[[client]]\nid = 1\nconnected_drone_ids = [3]\n\n[[link]]\na = 3\nb = 1\n";
+
+    fn error(message: &str, subjects: Vec<Subject>) -> ValidationError {
+        ValidationError::new(message.to_string(), subjects)
+    }
+
+    #[test]
+    fn snippets_point_at_the_line_and_column() {
+        let mut source_map = SourceMap::default();
+        source_map.add_file("input.toml", MAIN);
+
+        assert_eq!(
+            source_map.render(&error("Mono-directional", vec![Subject::Neighbour(1, 2), Subject::Node(2)])),
+            "Mono-directional\n \
+             --> input.toml:3:23\n  \
+             |\n\
+             3 | connected_node_ids = [2, 101]\n  \
+             |                       ^ neighbour 2 of node 1\n \
+             --> input.toml:6:1\n  \
+             |\n\
+             6 | [[drone]]\n  \
+             | ^^^^^^^^^ node 2 is defined here"
+        );
+    }
+
+    #[test]
+    fn carets_cover_the_whole_value() {
+        let mut source_map = SourceMap::default();
+        source_map.add_file("input.toml", MAIN);
+
+        let rendered = source_map.render(&error("Bad PDR", vec![Subject::Pdr(1), Subject::Neighbour(1, 101)]));
+        assert!(rendered.contains("4 | pdr = 0.05\n  |       ^^^^ PDR of drone 1"), "{}", rendered);
+        assert!(rendered.contains("  |                          ^^^ neighbour 101 of node 1"), "{}", rendered);
+    }
+
+    #[test]
+    fn line_numbers_widen_the_margin() {
+        let source = format!("{}[[server]]\nid = 200\n", "\n".repeat(10));
+        let mut source_map = SourceMap::default();
+        source_map.add_file("input.toml", &source);
+
+        assert_eq!(
+            source_map.render(&error("Lonely server", vec![Subject::Node(200)])),
+            "Lonely server\n  --> input.toml:11:1\n   |\n11 | [[server]]\n   | ^^^^^^^^^^ node 200 is defined here"
+        );
+    }
+
+    #[test]
+    fn included_files_are_pointed_at_with_their_offsets() {
+        let mut piece = SourceMap::default();
+        piece.add_file("piece.toml", PIECE);
+        let mut source_map = SourceMap::default();
+        source_map.add_file("main.toml", "[[include]]\npath = \"piece.toml\"\noffset = 50\n");
+        source_map.include(piece, 50).unwrap();
+
+        let rendered = source_map.render(&error(
+            "Broken piece",
+            vec![Subject::Node(51), Subject::Neighbour(51, 53), Subject::Include("piece.toml".to_string())],
+        ));
+        assert_eq!(
+            rendered,
+            "Broken piece\n \
+             --> piece.toml:1:1\n  \
+             |\n\
+             1 | [[client]]\n  \
+             | ^^^^^^^^^^ node 51 is defined here\n \
+             --> piece.toml:3:24\n  \
+             |\n\
+             3 | connected_drone_ids = [3]\n  \
+             |                        ^ neighbour 53 of node 51\n \
+             --> piece.toml:5:1\n  \
+             |\n\
+             5 | [[link]]\n  \
+             | ^^^^^^^^ neighbour 53 of node 51\n \
+             --> main.toml:1:1\n  \
+             |\n\
+             1 | [[include]]\n  \
+             | ^^^^^^^^^^^ \"piece.toml\" is included here"
+        );
+        // The ids of the piece are only known shifted
+        assert_eq!(source_map.render(&error("Nothing", vec![Subject::Node(1)])), "Nothing");
+    }
+
+    #[test]
+    fn offsets_past_the_maximum_id_are_errors() {
+        let mut piece = SourceMap::default();
+        piece.add_file("piece.toml", PIECE);
+        let mut source_map = SourceMap::default();
+
+        assert_eq!(
+            source_map.include(piece, 254),
+            Err("Node 3 with offset 254 exceeds the maximum id 255".to_string())
+        );
+    }
+}
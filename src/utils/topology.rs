@@ -0,0 +1,282 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::fmt;
+use wg_2024::config::Config;
+use wg_2024::network::NodeId;
+use wg_2024::packet::NodeType;
+
+/// A change to the topology of a running network
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mutation {
+    Crash(NodeId),
+    SetPdr(NodeId, f32),
+    AddLink(NodeId, NodeId),
+    RemoveLink(NodeId, NodeId),
+}
+
+impl fmt::Display for Mutation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mutation::Crash(id) => write!(f, "crash drone {}", id),
+            Mutation::SetPdr(id, pdr) => write!(f, "set pdr of drone {} to {:.3}", id, pdr),
+            Mutation::AddLink(a, b) => write!(f, "add link {}-{}", a, b),
+            Mutation::RemoveLink(a, b) => write!(f, "remove link {}-{}", a, b),
+        }
+    }
+}
+
+/// Applies the mutation to the topology
+pub fn apply_mutation(config: &mut Config, mutation: &Mutation) -> Result<(), String> {
+    match *mutation {
+        Mutation::Crash(id) => remove_drone(config, id),
+        Mutation::SetPdr(id, pdr) => set_pdr(config, id, pdr),
+        Mutation::AddLink(a, b) => add_link(config, a, b),
+        Mutation::RemoveLink(a, b) => remove_link(config, a, b),
+    }
+}
+
+/// Returns the neighbours of a node, or None if the node does not exist
+pub fn neighbours(config: &Config, id: NodeId) -> Option<&Vec<NodeId>> {
+    if let Some(drone) = config.drone.iter().find(|d| d.id == id) {
+        Some(&drone.connected_node_ids)
+    } else if let Some(client) = config.client.iter().find(|c| c.id == id) {
+        Some(&client.connected_drone_ids)
+    } else {
+        config.server.iter().find(|s| s.id == id).map(|s| &s.connected_drone_ids)
+    }
+}
+
+/// Returns every link of the topology once, as (smaller id, bigger id)
+pub fn links(config: &Config) -> BTreeSet<(NodeId, NodeId)> {
+    let drone_links = config.drone.iter().map(|d| (d.id, &d.connected_node_ids));
+    let client_links = config.client.iter().map(|c| (c.id, &c.connected_drone_ids));
+    let server_links = config.server.iter().map(|s| (s.id, &s.connected_drone_ids));
+
+    drone_links
+        .chain(client_links)
+        .chain(server_links)
+        .flat_map(|(id, neighbours)| neighbours.iter().map(move |n| (id.min(*n), id.max(*n))))
+        .collect()
+}
+
+/// Number of hops from `from` to every node it can reach.
+/// Only drones forward packets, so the paths never pass through a client or a server.
+pub fn hop_distances(config: &Config, from: NodeId) -> HashMap<NodeId, usize> {
+    let mut distances = HashMap::from([(from, 0)]);
+    let mut queue = VecDeque::from([from]);
+
+    while let Some(id) = queue.pop_front() {
+        if id != from && !is_drone(config, id) {
+            continue;
+        }
+        for neighbour in neighbours(config, id).into_iter().flatten() {
+            if !distances.contains_key(neighbour) {
+                distances.insert(*neighbour, distances[&id] + 1);
+                queue.push_back(*neighbour);
+            }
+        }
+    }
+    distances
+}
+
+/// Length in hops of the shortest path between each client and each server, None if unreachable
+pub fn client_server_distances(config: &Config) -> BTreeMap<(NodeId, NodeId), Option<usize>> {
+    let mut distances = BTreeMap::new();
+    for client in &config.client {
+        let from_client = hop_distances(config, client.id);
+        for server in &config.server {
+            distances.insert((client.id, server.id), from_client.get(&server.id).copied());
+        }
+    }
+    distances
+}
+
+/// Returns the neighbours of a node to change them, or None if the node does not exist
+pub(crate) fn neighbours_mut(config: &mut Config, id: NodeId) -> Option<&mut Vec<NodeId>> {
+    if let Some(drone) = config.drone.iter_mut().find(|d| d.id == id) {
+        Some(&mut drone.connected_node_ids)
+    } else if let Some(client) = config.client.iter_mut().find(|c| c.id == id) {
+        Some(&mut client.connected_drone_ids)
+    } else {
+        config.server.iter_mut().find(|s| s.id == id).map(|s| &mut s.connected_drone_ids)
+    }
+}
+
+pub fn is_drone(config: &Config, id: NodeId) -> bool {
+    config.drone.iter().any(|d| d.id == id)
+}
+
+pub fn is_client(config: &Config, id: NodeId) -> bool {
+    config.client.iter().any(|c| c.id == id)
+}
+
+pub fn is_server(config: &Config, id: NodeId) -> bool {
+    config.server.iter().any(|s| s.id == id)
+}
+
+/// The ids of every node of the topology
+pub fn node_ids(config: &Config) -> BTreeSet<NodeId> {
+    let drones = config.drone.iter().map(|d| d.id);
+    drones.chain(config.client.iter().map(|c| c.id)).chain(config.server.iter().map(|s| s.id)).collect()
+}
+
+pub fn node_type_of(config: &Config, id: NodeId) -> Option<NodeType> {
+    if is_drone(config, id) {
+        Some(NodeType::Drone)
+    } else if is_client(config, id) {
+        Some(NodeType::Client)
+    } else if is_server(config, id) {
+        Some(NodeType::Server)
+    } else {
+        None
+    }
+}
+
+/// Name of the kind of a host, for the logs: "client", "server", or "node" for anything else
+pub fn host_kind(config: &Config, id: NodeId) -> &'static str {
+    match node_type_of(config, id) {
+        Some(NodeType::Client) => "client",
+        Some(NodeType::Server) => "server",
+        _ => "node",
+    }
+}
+
+/// Whether `b` is among the neighbours of `a`
+pub fn linked(config: &Config, a: NodeId, b: NodeId) -> bool {
+    neighbours(config, a).is_some_and(|n| n.contains(&b))
+}
+
+/// Adds the link in both directions. Fails if one of the nodes does not exist,
+/// if the link already exists or if it would connect two hosts.
+pub fn add_link(config: &mut Config, a: NodeId, b: NodeId) -> Result<(), String> {
+    if a == b {
+        return Err(format!("Node {} cannot be linked to itself", a));
+    }
+    if !is_drone(config, a) && !is_drone(config, b) {
+        return Err(format!("Link {}-{} would connect two hosts without a drone", a, b));
+    }
+
+    match (neighbours(config, a), neighbours(config, b)) {
+        (Some(neighbours_a), Some(_)) if neighbours_a.contains(&b) => {
+            Err(format!("Link {}-{} already exists", a, b))
+        }
+        (Some(_), Some(_)) => {
+            neighbours_mut(config, a).unwrap().push(b);
+            neighbours_mut(config, b).unwrap().push(a);
+            Ok(())
+        }
+        _ => Err(format!("Link {}-{} refers to a node that does not exist", a, b)),
+    }
+}
+
+/// Removes the link in both directions. Fails if the link does not exist.
+pub fn remove_link(config: &mut Config, a: NodeId, b: NodeId) -> Result<(), String> {
+    match neighbours(config, a) {
+        Some(neighbours_a) if neighbours_a.contains(&b) => {
+            neighbours_mut(config, a).unwrap().retain(|id| *id != b);
+            if let Some(neighbours_b) = neighbours_mut(config, b) {
+                neighbours_b.retain(|id| *id != a);
+            }
+            Ok(())
+        }
+        _ => Err(format!("Link {}-{} does not exist", a, b)),
+    }
+}
+
+/// Removes a drone and all of its links
+pub fn remove_drone(config: &mut Config, id: NodeId) -> Result<(), String> {
+    if !is_drone(config, id) {
+        return Err(format!("Drone {} does not exist", id));
+    }
+
+    config.drone.retain(|d| d.id != id);
+    for drone in &mut config.drone {
+        drone.connected_node_ids.retain(|n| *n != id);
+    }
+    for client in &mut config.client {
+        client.connected_drone_ids.retain(|n| *n != id);
+    }
+    for server in &mut config.server {
+        server.connected_drone_ids.retain(|n| *n != id);
+    }
+    Ok(())
+}
+
+pub fn set_pdr(config: &mut Config, id: NodeId, pdr: f32) -> Result<(), String> {
+    if !(0. ..=1.).contains(&pdr) {
+        return Err(format!("The packet drop rate {} is not in the right range! Correct range: [0 - 1]", pdr));
+    }
+
+    match config.drone.iter_mut().find(|d| d.id == id) {
+        Some(drone) => {
+            drone.pdr = pdr;
+            Ok(())
+        }
+        None => Err(format!("Drone {} does not exist", id)),
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use wg_2024::config::{Client, Drone, Server};
+
+    /// Builds a topology from its nodes and links, for the tests of the modules that read topologies
+    pub fn config_with_links(
+        drones: &[(NodeId, f32)],
+        clients: &[NodeId],
+        servers: &[NodeId],
+        links: &[(NodeId, NodeId)],
+    ) -> Config {
+        let mut config = Config {
+            drone: drones.iter().map(|(id, pdr)| Drone { id: *id, connected_node_ids: Vec::new(), pdr: *pdr }).collect(),
+            client: clients.iter().map(|id| Client { id: *id, connected_drone_ids: Vec::new() }).collect(),
+            server: servers.iter().map(|id| Server { id: *id, connected_drone_ids: Vec::new() }).collect(),
+        };
+        for (a, b) in links {
+            add_link(&mut config, *a, *b).unwrap();
+        }
+        config
+    }
+
+    // Client 100 and server 200 on opposite corners of the square 1-2-4-3
+    fn square() -> Config {
+        config_with_links(
+            &[(1, 0.1), (2, 0.1), (3, 0.1), (4, 0.1)],
+            &[100],
+            &[200],
+            &[(100, 1), (1, 2), (1, 3), (2, 4), (3, 4), (4, 200)],
+        )
+    }
+
+    #[test]
+    fn hosts_do_not_forward_packets() {
+        let mut config = square();
+        add_link(&mut config, 100, 4).unwrap();
+        remove_link(&mut config, 4, 200).unwrap();
+        add_link(&mut config, 3, 200).unwrap();
+
+        let distances = hop_distances(&config, 1);
+        assert_eq!(distances[&4], 2);
+        assert_eq!(distances[&200], 2);
+        assert_eq!(client_server_distances(&config)[&(100, 200)], Some(3));
+
+        remove_drone(&mut config, 3).unwrap();
+        assert_eq!(client_server_distances(&config)[&(100, 200)], None);
+    }
+
+    #[test]
+    fn mutations_keep_both_sides_of_a_link() {
+        let mut config = square();
+        apply_mutation(&mut config, &Mutation::RemoveLink(2, 1)).unwrap();
+        assert!(!neighbours(&config, 1).unwrap().contains(&2));
+        assert!(!neighbours(&config, 2).unwrap().contains(&1));
+
+        apply_mutation(&mut config, &Mutation::Crash(4)).unwrap();
+        assert_eq!(links(&config), BTreeSet::from([(1, 3), (1, 100)]));
+
+        assert!(apply_mutation(&mut config, &Mutation::AddLink(100, 200)).is_err());
+        assert!(apply_mutation(&mut config, &Mutation::AddLink(1, 3)).is_err());
+        assert!(apply_mutation(&mut config, &Mutation::SetPdr(1, 1.5)).is_err());
+        assert!(apply_mutation(&mut config, &Mutation::Crash(100)).is_err());
+    }
+}
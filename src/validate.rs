@@ -0,0 +1,92 @@
+use similar::TextDiff;
+use std::fs;
+
+use crate::utils::buffers::BufferSettings;
+use crate::utils::lints::{self, LintSettings, Severity};
+use crate::utils::{config_fixer, config_loader, config_writer, extended_config};
+
+/// Validates the topology file. With `fix`, its mechanical mistakes are corrected first:
+/// the result is written to `output` (by default over the file itself) in the plain format,
+/// the changes are printed as a diff and the written file is validated again.
+/// Files in the extended format are only fixed into another file, since the plain format
+/// would replace their includes, ranges and links.
+/// A valid topology is then checked against the lints, the denied ones make the validation fail.
+pub fn validate(config_path: &str, fix: bool, output: Option<&str>) -> Result<(), String> {
+    let mut checked_path = config_path;
+
+    if fix {
+        let original = fs::read_to_string(config_path)
+            .map_err(|e| format!("Unable to read config file \"{}\": {}", config_path, e))?;
+        let extended = extended_config::uses_extended_format(&original);
+
+        // The fixer works on the expanded topology, so the mistakes that stop the expansion
+        // (e.g. a link to a missing node) must be corrected by hand
+        let mut config = config_loader::read_config(config_path).map_err(|error_message| {
+            if extended {
+                format!("{}\n--fix cannot correct the includes, ranges and links of the extended format, fix them by hand", error_message)
+            } else {
+                error_message
+            }
+        })?;
+        let changes = config_fixer::fix_config(&mut config);
+
+        if changes.is_empty() {
+            println!("Nothing to fix in \"{}\"", config_path);
+        } else {
+            if extended && output.is_none() {
+                return Err(format!(
+                    "\"{}\" uses the extended format, which --fix would flatten into plain neighbour lists: \
+                     write the fixed topology to another file with --output",
+                    config_path
+                ));
+            }
+            for change in &changes {
+                println!("Fixed: {}", change);
+            }
+
+            let fixed = config_writer::to_toml(&config) + &settings_tables(&original);
+            let output = output.unwrap_or(config_path);
+            let diff = TextDiff::from_lines(&original, &fixed);
+            println!("\n{}", diff.unified_diff().header(config_path, output));
+
+            fs::write(output, fixed).map_err(|e| format!("Unable to write \"{}\": {}", output, e))?;
+            checked_path = output;
+        }
+    }
+
+    let (config, source_map) = config_loader::load_config_with_sources(checked_path)?;
+    BufferSettings::load(checked_path)?.check_nodes(&config)?;
+    let settings = LintSettings::load(checked_path)?;
+    let lints = lints::lint(&config, &settings);
+
+    for lint in &lints {
+        let level = if lint.severity == Severity::Deny { "error" } else { "warning" };
+        let headline = format!("{}[{}]: {}", level, lint.rule.name(), lint.message);
+        println!("{}\n", source_map.render_subjects(&headline, &lint.subjects));
+    }
+
+    let denied = lints.iter().filter(|l| l.severity == Severity::Deny).count();
+    if denied > 0 {
+        return Err(format!("\"{}\" breaks {} denied lints", checked_path, denied));
+    }
+    println!("\"{}\" is a valid topology ({} warnings)", checked_path, lints.len());
+    Ok(())
+}
+
+// The [lints] and [buffers] tables of the original file, kept in the fixed one
+fn settings_tables(original: &str) -> String {
+    let Ok(mut file) = toml::from_str::<toml::Table>(original) else {
+        return String::new();
+    };
+    let mut settings = toml::Table::new();
+    for name in ["lints", "buffers"] {
+        if let Some(table @ toml::Value::Table(_)) = file.remove(name) {
+            settings.insert(name.to_string(), table);
+        }
+    }
+    if settings.is_empty() {
+        String::new()
+    } else {
+        format!("\n{}", toml::to_string(&settings).unwrap_or_default())
+    }
+}
@@ -0,0 +1,192 @@
+use log::{error, info};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::thread;
+use std::time::{Duration, SystemTime};
+use wg_2024::config::{Client, Config, Drone, Server};
+use wg_2024::network::NodeId;
+
+use crate::network_control::NetworkControl;
+use crate::utils::topology::{self, Mutation};
+use crate::utils::{config_loader, input_validator};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A difference between the running topology and the topology file
+#[derive(Debug)]
+enum TopologyChange {
+    SpawnDrone(Drone),
+    SpawnClient(Client),
+    SpawnServer(Server),
+    Mutate(Mutation),
+    RemoveHost(NodeId),
+}
+
+impl fmt::Display for TopologyChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TopologyChange::SpawnDrone(drone) => write!(f, "spawn drone {}", drone.id),
+            TopologyChange::SpawnClient(client) => write!(f, "spawn client {}", client.id),
+            TopologyChange::SpawnServer(server) => write!(f, "spawn server {}", server.id),
+            TopologyChange::Mutate(mutation) => write!(f, "{}", mutation),
+            TopologyChange::RemoveHost(id) => write!(f, "remove host {}", id),
+        }
+    }
+}
+
+/// Watches the topology file and applies its changes to the running network
+pub fn spawn(path: String, network: NetworkControl) -> thread::JoinHandle<()> {
+    info!("Watching \"{}\" for topology changes", path);
+    thread::spawn(move || {
+        let mut last_modified = modified_time(&path);
+        loop {
+            thread::sleep(POLL_INTERVAL);
+
+            let modified = modified_time(&path);
+            if modified == last_modified {
+                continue;
+            }
+            last_modified = modified;
+
+            info!("\"{}\" changed, reloading the topology", path);
+            let target = match config_loader::load_config_with_sources(&path).and_then(|(config, source_map)| {
+                input_validator::check_reachability(&config)
+                    .map(|_| config)
+                    .map_err(|error| source_map.render(&error))
+            }) {
+                Ok(config) => config,
+                Err(error_message) => {
+                    error!("The new topology is not valid, no change applied: {}", error_message);
+                    continue;
+                }
+            };
+
+            for change in diff(&network.topology(), &target) {
+                info!("Watch: {}", change);
+                if let Err(error_message) = apply(&network, &change) {
+                    error!("Watch: \"{}\" failed: {}", change, error_message);
+                }
+            }
+        }
+    })
+}
+
+fn modified_time(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+// Returns the changes that turn the current topology into the target one.
+// New nodes come first so that links can be added to them, removals come last.
+fn diff(current: &Config, target: &Config) -> Vec<TopologyChange> {
+    let mut changes = Vec::new();
+
+    let current_drones: HashMap<NodeId, &Drone> = current.drone.iter().map(|d| (d.id, d)).collect();
+    let target_drones: HashMap<NodeId, &Drone> = target.drone.iter().map(|d| (d.id, d)).collect();
+    let current_hosts: BTreeSet<NodeId> =
+        current.client.iter().map(|c| c.id).chain(current.server.iter().map(|s| s.id)).collect();
+    let target_hosts: BTreeSet<NodeId> =
+        target.client.iter().map(|c| c.id).chain(target.server.iter().map(|s| s.id)).collect();
+
+    let exists_in_current = |id: &NodeId| current_drones.contains_key(id) || current_hosts.contains(id);
+    let exists_in_target = |id: &NodeId| target_drones.contains_key(id) || target_hosts.contains(id);
+
+    for drone in target.drone.iter().filter(|d| !exists_in_current(&d.id)) {
+        changes.push(TopologyChange::SpawnDrone(drone.clone()));
+    }
+    for client in target.client.iter().filter(|c| !exists_in_current(&c.id)) {
+        changes.push(TopologyChange::SpawnClient(client.clone()));
+    }
+    for server in target.server.iter().filter(|s| !exists_in_current(&s.id)) {
+        changes.push(TopologyChange::SpawnServer(server.clone()));
+    }
+
+    // Links of new nodes are added when they are spawned, links of removed nodes go away with them
+    let current_links = topology::links(current);
+    let target_links = topology::links(target);
+    for (a, b) in target_links.difference(&current_links) {
+        if exists_in_current(a) && exists_in_current(b) {
+            changes.push(TopologyChange::Mutate(Mutation::AddLink(*a, *b)));
+        }
+    }
+
+    for drone in &target.drone {
+        if let Some(current_drone) = current_drones.get(&drone.id) {
+            if current_drone.pdr != drone.pdr {
+                changes.push(TopologyChange::Mutate(Mutation::SetPdr(drone.id, drone.pdr)));
+            }
+        }
+    }
+
+    for (a, b) in current_links.difference(&target_links) {
+        if exists_in_target(a) && exists_in_target(b) {
+            changes.push(TopologyChange::Mutate(Mutation::RemoveLink(*a, *b)));
+        }
+    }
+
+    for drone in current.drone.iter().filter(|d| !exists_in_target(&d.id)) {
+        changes.push(TopologyChange::Mutate(Mutation::Crash(drone.id)));
+    }
+    for host in current_hosts.iter().filter(|id| !exists_in_target(id)) {
+        changes.push(TopologyChange::RemoveHost(*host));
+    }
+
+    changes
+}
+
+fn apply(network: &NetworkControl, change: &TopologyChange) -> Result<(), String> {
+    match change {
+        TopologyChange::SpawnDrone(drone) => network.spawn_drone(drone),
+        TopologyChange::SpawnClient(client) => network.spawn_client(client),
+        TopologyChange::SpawnServer(server) => network.spawn_server(server),
+        TopologyChange::Mutate(mutation) => network.apply(mutation),
+        TopologyChange::RemoveHost(id) => network.remove_host(*id),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::topology::tests::config_with_links;
+
+    fn describe(changes: &[TopologyChange]) -> Vec<String> {
+        changes.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn new_nodes_are_spawned_before_the_other_changes() {
+        let current = config_with_links(&[(1, 0.1), (2, 0.1)], &[100], &[200], &[(100, 1), (1, 2), (2, 200)]);
+        let target = config_with_links(
+            &[(1, 0.2), (2, 0.1), (3, 0.1)],
+            &[100, 101],
+            &[200],
+            &[(100, 1), (1, 2), (2, 200), (1, 3), (3, 200), (101, 3)],
+        );
+
+        assert_eq!(
+            describe(&diff(&current, &target)),
+            ["spawn drone 3", "spawn client 101", "set pdr of drone 1 to 0.200"]
+        );
+    }
+
+    #[test]
+    fn removed_nodes_come_last() {
+        let current = config_with_links(
+            &[(1, 0.1), (2, 0.1), (3, 0.1)],
+            &[100, 101],
+            &[200],
+            &[(100, 1), (101, 3), (1, 2), (1, 3), (2, 200), (3, 200)],
+        );
+        let target = config_with_links(&[(1, 0.1), (2, 0.1)], &[100], &[200], &[(100, 1), (1, 2), (2, 200), (1, 200)]);
+
+        assert_eq!(
+            describe(&diff(&current, &target)),
+            ["add link 1-200", "crash drone 3", "remove host 101"]
+        );
+    }
+
+    #[test]
+    fn an_unchanged_file_gives_no_changes() {
+        let current = config_with_links(&[(1, 0.1)], &[100], &[200], &[(100, 1), (1, 200)]);
+        assert!(diff(&current, &current.clone()).is_empty());
+    }
+}